@@ -17,6 +17,127 @@ pub struct Account {
     pub delegated_balance: PooledVenearBalance,
     /// The delegation details, in case this account has delegated balance to another account.
     pub delegation: Option<AccountDelegation>,
+    /// The boost of the veNEAR balance for the active lock commitment of the account.
+    pub lock_boost: Option<LockBoost>,
+}
+
+/// The account details before the lock boost was introduced.
+#[derive(Clone)]
+#[near(serializers=[borsh, json])]
+pub struct AccountV0 {
+    pub account_id: AccountId,
+    pub update_timestamp: TimestampNs,
+    pub balance: VenearBalance,
+    pub delegated_balance: PooledVenearBalance,
+    pub delegation: Option<AccountDelegation>,
+}
+
+/// The boost of the veNEAR balance for the lock commitment. The boost decreases linearly with the
+/// remaining duration of the commitment and reaches zero when the commitment ends.
+/// The boost decreases by a whole number of yoctoNEAR every second, so the boosts of all accounts
+/// are pooled exactly in the total veNEAR balance, see `PooledLockBoost`. The boost is not
+/// delegated and doesn't grow.
+#[derive(Clone, Copy)]
+#[near(serializers=[borsh, json])]
+pub struct LockBoost {
+    /// The boost until the start timestamp. A multiple of the number of seconds between the start
+    /// and the end timestamps.
+    pub near_balance: NearToken,
+    /// The timestamp in nanoseconds when the boost starts decreasing. Truncated to seconds.
+    pub start_timestamp: TimestampNs,
+    /// The timestamp in nanoseconds when the lock commitment ends. Truncated to seconds.
+    pub end_timestamp: TimestampNs,
+}
+
+impl LockBoost {
+    /// Creates the boost that decreases from the start timestamp to the end timestamp, or `None`
+    /// if it's empty. The timestamps are truncated to seconds, and the boost is rounded down to a
+    /// multiple of the number of seconds between them.
+    pub fn new(
+        near_balance: NearToken,
+        start_timestamp: TimestampNs,
+        end_timestamp: TimestampNs,
+    ) -> Option<Self> {
+        let start_timestamp = truncate_to_seconds(start_timestamp);
+        let end_timestamp = truncate_to_seconds(end_timestamp);
+        let num_seconds = (end_timestamp.0.checked_sub(start_timestamp.0)? / 10u64.pow(9)) as u128;
+        if num_seconds == 0 {
+            return None;
+        }
+        let decrease_per_second = near_balance.as_yoctonear() / num_seconds;
+        if decrease_per_second == 0 {
+            return None;
+        }
+        Some(Self {
+            near_balance: NearToken::from_yoctonear(decrease_per_second * num_seconds),
+            start_timestamp,
+            end_timestamp,
+        })
+    }
+
+    /// Returns the decrease of the boost per second between the start and the end timestamps.
+    pub fn decrease_per_second(&self) -> NearToken {
+        let num_seconds = (self.end_timestamp.0 - self.start_timestamp.0) / 10u64.pow(9);
+        NearToken::from_yoctonear(self.near_balance.as_yoctonear() / num_seconds as u128)
+    }
+
+    /// Returns the boost at the given timestamp.
+    pub fn balance(&self, timestamp: TimestampNs) -> NearToken {
+        let timestamp = truncate_to_seconds(timestamp);
+        if timestamp >= self.end_timestamp {
+            return NearToken::from_yoctonear(0);
+        }
+        if timestamp <= self.start_timestamp {
+            return self.near_balance;
+        }
+        let num_seconds = (self.end_timestamp.0 - timestamp.0) / 10u64.pow(9);
+        NearToken::from_yoctonear(self.decrease_per_second().as_yoctonear() * num_seconds as u128)
+    }
+}
+
+/// The total boost of the lock commitments, that is pooled in the total veNEAR balance.
+/// The pool decreases by the sum of the decreases per second of the boosts between their start
+/// and end timestamps. The contract applies `LockBoostRateChange` at these timestamps.
+#[derive(Clone, Copy, Default)]
+#[near(serializers=[borsh, json])]
+pub struct PooledLockBoost {
+    /// The total boost at the update timestamp of the global state.
+    pub near_balance: NearToken,
+    /// The total decrease per second of the boosts that are decreasing.
+    pub decrease_per_second: NearToken,
+}
+
+impl PooledLockBoost {
+    pub fn update(&mut self, previous_timestamp: TimestampNs, current_timestamp: TimestampNs) {
+        let num_seconds = (current_timestamp.0 - previous_timestamp.0) / 10u64.pow(9);
+        self.near_balance = self
+            .near_balance
+            .saturating_sub(self.decrease_per_second.saturating_mul(num_seconds as u128));
+    }
+
+    /// Applies the change of the total decrease per second at its timestamp.
+    pub fn apply(&mut self, change: &LockBoostRateChange) {
+        self.decrease_per_second = near_sub(
+            near_add(self.decrease_per_second, change.started),
+            change.ended,
+        );
+    }
+}
+
+/// The change of the total decrease per second of the pooled boosts at a timestamp.
+#[derive(Clone, Copy, Default)]
+#[near(serializers=[borsh, json])]
+pub struct LockBoostRateChange {
+    /// The total decrease per second of the boosts that start decreasing at the timestamp.
+    pub started: NearToken,
+    /// The total decrease per second of the boosts that end at the timestamp.
+    pub ended: NearToken,
+}
+
+impl LockBoostRateChange {
+    pub fn is_empty(&self) -> bool {
+        self.started.is_zero() && self.ended.is_zero()
+    }
 }
 
 /// The details of the delegation of veNEAR from one account to another.
@@ -31,19 +152,28 @@ pub struct AccountDelegation {
 #[derive(Clone)]
 #[near(serializers=[borsh, json])]
 pub enum VAccount {
-    V0(Account),
+    V0(AccountV0),
+    V1(Account),
 }
 
 impl From<Account> for VAccount {
     fn from(account: Account) -> Self {
-        Self::V0(account)
+        Self::V1(account)
     }
 }
 
 impl From<VAccount> for Account {
     fn from(value: VAccount) -> Self {
         match value {
-            VAccount::V0(account) => account,
+            VAccount::V0(account) => Account {
+                account_id: account.account_id,
+                update_timestamp: account.update_timestamp,
+                balance: account.balance,
+                delegated_balance: account.delegated_balance,
+                delegation: account.delegation,
+                lock_boost: None,
+            },
+            VAccount::V1(account) => account,
         }
     }
 }
@@ -61,7 +191,7 @@ impl Account {
             "Timestamp must be increasing"
         );
         let mut delegated_balance = self.delegated_balance;
-        delegated_balance.update(
+        delegated_balance.update_delegated(
             self.update_timestamp,
             current_timestamp,
             venear_growth_config,
@@ -74,12 +204,22 @@ impl Account {
                 current_timestamp,
                 venear_growth_config,
             );
-            near_add(total, balance.total())
+            near_add(
+                near_add(total, balance.total()),
+                self.lock_boost_balance(current_timestamp),
+            )
         } else {
             total
         }
     }
 
+    /// Returns the lock boost of the account at the given timestamp.
+    pub fn lock_boost_balance(&self, timestamp: TimestampNs) -> NearToken {
+        self.lock_boost
+            .map(|lock_boost| lock_boost.balance(timestamp))
+            .unwrap_or_default()
+    }
+
     pub fn update(
         &mut self,
         current_timestamp: TimestampNs,
@@ -90,16 +230,134 @@ impl Account {
             current_timestamp >= self.update_timestamp,
             "Timestamp must be increasing"
         );
-        self.balance.update(
-            self.update_timestamp,
-            current_timestamp,
-            venear_growth_config,
-        );
-        self.delegated_balance.update(
+        if self.delegation.is_some() {
+            self.balance.update_delegated(
+                self.update_timestamp,
+                current_timestamp,
+                venear_growth_config,
+            );
+        } else {
+            self.balance.update(
+                self.update_timestamp,
+                current_timestamp,
+                venear_growth_config,
+            );
+        }
+        self.delegated_balance.update_delegated(
             self.update_timestamp,
             current_timestamp,
             venear_growth_config,
         );
         self.update_timestamp = current_timestamp;
+        if self.lock_boost_balance(current_timestamp).is_zero() {
+            self.lock_boost = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::venear::VenearGrowthConfigFixedRate;
+
+    fn seconds(num_seconds: u64) -> TimestampNs {
+        (num_seconds * 10u64.pow(9)).into()
+    }
+
+    #[test]
+    fn test_lock_boost_decreases_to_zero() {
+        let lock_boost = LockBoost {
+            near_balance: NearToken::from_near(10),
+            start_timestamp: seconds(100),
+            end_timestamp: seconds(500),
+        };
+        assert_eq!(lock_boost.balance(seconds(50)), NearToken::from_near(10));
+        assert_eq!(lock_boost.balance(seconds(300)), NearToken::from_near(5));
+        assert_eq!(lock_boost.balance(seconds(500)), NearToken::from_near(0));
+        assert_eq!(lock_boost.balance(seconds(600)), NearToken::from_near(0));
+
+        let venear_growth_config: VenearGrowthConfig = VenearGrowthConfigFixedRate {
+            annual_growth_rate_ns: Fraction {
+                numerator: 0.into(),
+                denominator: 1.into(),
+            },
+        }
+        .into();
+        let mut account = Account {
+            account_id: "alice.near".parse().unwrap(),
+            update_timestamp: seconds(100),
+            balance: VenearBalance::from_near(NearToken::from_near(10)),
+            delegated_balance: Default::default(),
+            delegation: None,
+            lock_boost: Some(lock_boost),
+        };
+        assert_eq!(
+            account.total_balance(seconds(300), &venear_growth_config),
+            NearToken::from_near(15)
+        );
+        account.update(seconds(300), &venear_growth_config);
+        assert!(account.lock_boost.is_some());
+        // The boost expires without an update of the account.
+        assert_eq!(
+            account.total_balance(seconds(500), &venear_growth_config),
+            NearToken::from_near(10)
+        );
+        account.update(seconds(500), &venear_growth_config);
+        assert!(account.lock_boost.is_none());
+
+        // The boost isn't delegated.
+        account.lock_boost = Some(lock_boost);
+        account.delegation = Some(AccountDelegation {
+            account_id: "bob.near".parse().unwrap(),
+        });
+        assert_eq!(
+            account.total_balance(seconds(500), &venear_growth_config),
+            NearToken::from_near(0)
+        );
+    }
+
+    #[test]
+    fn test_pooled_lock_boost_matches_boosts() {
+        // The boost is rounded down to a multiple of the number of seconds.
+        let lock_boost_a =
+            LockBoost::new(NearToken::from_yoctonear(1001), seconds(100), seconds(300)).unwrap();
+        assert_eq!(lock_boost_a.near_balance, NearToken::from_yoctonear(1000));
+        assert_eq!(
+            lock_boost_a.decrease_per_second(),
+            NearToken::from_yoctonear(5)
+        );
+        let lock_boost_b =
+            LockBoost::new(NearToken::from_yoctonear(700), seconds(200), seconds(400)).unwrap();
+        assert!(
+            LockBoost::new(NearToken::from_yoctonear(100), seconds(100), seconds(100)).is_none()
+        );
+
+        // Both boosts are pooled at 150 seconds, when only the first one is decreasing.
+        let mut pool = PooledLockBoost {
+            near_balance: near_add(
+                lock_boost_a.balance(seconds(150)),
+                lock_boost_b.balance(seconds(150)),
+            ),
+            decrease_per_second: lock_boost_a.decrease_per_second(),
+        };
+        pool.update(seconds(150), seconds(200));
+        pool.apply(&LockBoostRateChange {
+            started: lock_boost_b.decrease_per_second(),
+            ended: NearToken::from_yoctonear(0),
+        });
+        pool.update(seconds(200), seconds(300));
+        pool.apply(&LockBoostRateChange {
+            started: NearToken::from_yoctonear(0),
+            ended: lock_boost_a.decrease_per_second(),
+        });
+        pool.update(seconds(300), seconds(350));
+        assert_eq!(
+            pool.near_balance,
+            near_add(
+                lock_boost_a.balance(seconds(350)),
+                lock_boost_b.balance(seconds(350)),
+            )
+        );
+        assert_eq!(pool.near_balance, NearToken::from_yoctonear(150));
     }
 }
@@ -1,3 +1,4 @@
+use crate::account::PooledLockBoost;
 use crate::venear::VenearGrowthConfig;
 use crate::*;
 
@@ -10,6 +11,20 @@ pub struct GlobalState {
     pub total_venear_balance: PooledVenearBalance,
 
     pub venear_growth_config: VenearGrowthConfig,
+
+    /// The total boost of the lock commitments of the accounts that don't delegate.
+    pub total_lock_boost: PooledLockBoost,
+}
+
+/// The global state before the lock boost was introduced.
+#[derive(Clone)]
+#[near(serializers=[borsh, json])]
+pub struct GlobalStateV0 {
+    pub update_timestamp: TimestampNs,
+
+    pub total_venear_balance: PooledVenearBalance,
+
+    pub venear_growth_config: VenearGrowthConfig,
 }
 
 impl GlobalState {
@@ -18,9 +33,18 @@ impl GlobalState {
             update_timestamp: truncate_to_seconds(timestamp),
             total_venear_balance: PooledVenearBalance::default(),
             venear_growth_config,
+            total_lock_boost: PooledLockBoost::default(),
         }
     }
 
+    /// Returns the total supply of veNEAR, including the lock boosts.
+    pub fn total_balance(&self) -> NearToken {
+        near_add(
+            self.total_venear_balance.total(),
+            self.total_lock_boost.near_balance,
+        )
+    }
+
     pub fn update(&mut self, current_timestamp: TimestampNs) {
         let current_timestamp = truncate_to_seconds(current_timestamp);
         self.total_venear_balance.update(
@@ -28,6 +52,8 @@ impl GlobalState {
             current_timestamp,
             &self.venear_growth_config,
         );
+        self.total_lock_boost
+            .update(self.update_timestamp, current_timestamp);
         self.update_timestamp = current_timestamp;
     }
 }
@@ -35,19 +61,26 @@ impl GlobalState {
 #[derive(Clone)]
 #[near(serializers=[borsh, json])]
 pub enum VGlobalState {
-    V0(GlobalState),
+    V0(GlobalStateV0),
+    V1(GlobalState),
 }
 
 impl From<GlobalState> for VGlobalState {
     fn from(global_state: GlobalState) -> Self {
-        Self::V0(global_state)
+        Self::V1(global_state)
     }
 }
 
 impl From<VGlobalState> for GlobalState {
     fn from(value: VGlobalState) -> Self {
         match value {
-            VGlobalState::V0(global_state) => global_state,
+            VGlobalState::V0(global_state) => GlobalState {
+                update_timestamp: global_state.update_timestamp,
+                total_venear_balance: global_state.total_venear_balance,
+                venear_growth_config: global_state.venear_growth_config,
+                total_lock_boost: PooledLockBoost::default(),
+            },
+            VGlobalState::V1(global_state) => global_state,
         }
     }
 }
@@ -56,6 +89,7 @@ impl VGlobalState {
     pub fn get_venear_growth_config(&self) -> &VenearGrowthConfig {
         match self {
             VGlobalState::V0(global_state) => &global_state.venear_growth_config,
+            VGlobalState::V1(global_state) => &global_state.venear_growth_config,
         }
     }
 }
@@ -1,5 +1,19 @@
 use crate::*;
-use near_sdk::json_types::U64;
+use near_sdk::json_types::{U128, U64};
+
+/// The balance of a fungible token held by the lockup contract.
+#[derive(Clone)]
+#[near(serializers=[borsh, json])]
+pub struct FungibleTokenBalance {
+    /// The account ID of the fungible token contract.
+    pub token_account_id: AccountId,
+
+    /// The balance of the fungible token.
+    pub balance: U128,
+
+    /// The value of the balance in NEAR at the last known price of the token.
+    pub near_balance: NearToken,
+}
 
 /// The lockup update is the information passed from the lockup contract to update veNEAR balances.
 /// It includes the total amount of NEAR that is locked in the lockup contract and the list of
@@ -18,7 +32,73 @@ pub struct LockupUpdateV1 {
     pub lockup_update_nonce: U64,
 }
 
+/// The lockup update that also includes the locked fungible tokens. The locked amount is
+/// covered by NEAR first, and the rest is covered by the fungible tokens.
+#[derive(Clone)]
+#[near(serializers=[borsh, json])]
+pub struct LockupUpdateV2 {
+    /// The amount of locked NEAR that is covered by NEAR held by the lockup contract.
+    pub locked_near_balance: NearToken,
+
+    /// The timestamp in nanoseconds when the update was created.
+    pub timestamp: TimestampNs,
+
+    /// The nonce of the lockup update. It should be incremented for every new update by the lockup
+    /// contract.
+    pub lockup_update_nonce: U64,
+
+    /// The locked balances of the fungible tokens with their NEAR equivalent, covering the rest
+    /// of the locked amount.
+    pub fungible_tokens: Vec<FungibleTokenBalance>,
+}
+
+/// The lockup update that also includes the lock commitment. The locked amount is boosted by
+/// veNEAR based on the committed lock duration until the commitment ends.
+#[derive(Clone)]
+#[near(serializers=[borsh, json])]
+pub struct LockupUpdateV3 {
+    /// The amount of locked NEAR that is covered by NEAR held by the lockup contract.
+    pub locked_near_balance: NearToken,
+
+    /// The timestamp in nanoseconds when the update was created.
+    pub timestamp: TimestampNs,
+
+    /// The nonce of the lockup update. It should be incremented for every new update by the lockup
+    /// contract.
+    pub lockup_update_nonce: U64,
+
+    /// The locked balances of the fungible tokens with their NEAR equivalent, covering the rest
+    /// of the locked amount.
+    pub fungible_tokens: Vec<FungibleTokenBalance>,
+
+    /// The timestamp in nanoseconds when the active lock commitment ends, or 0 if there is none.
+    pub lock_end_timestamp: TimestampNs,
+}
+
 #[near(serializers=[borsh, json])]
 pub enum VLockupUpdate {
     V1(LockupUpdateV1),
+    V2(LockupUpdateV2),
+    V3(LockupUpdateV3),
+}
+
+/// The update sent by the lockup contract when it migrates to the new lockup contract. The whole
+/// locked amount moves to the new lockup contract, so veNEAR doesn't treat it as a decrease of the
+/// locked NEAR and keeps the extra veNEAR balance.
+#[derive(Clone)]
+#[near(serializers=[borsh, json])]
+pub struct LockupMigrationUpdate {
+    /// The amount of NEAR that is locked in the lockup contract and moves to the new lockup
+    /// contract.
+    pub locked_near_balance: NearToken,
+
+    /// The timestamp in nanoseconds when the update was created.
+    pub timestamp: TimestampNs,
+
+    /// The nonce of the lockup update. It should be incremented for every new update by the lockup
+    /// contract.
+    pub lockup_update_nonce: U64,
+
+    /// The account ID of the new lockup contract deployed by veNEAR for the migration.
+    pub new_lockup_account_id: AccountId,
 }
@@ -36,11 +36,52 @@ impl VenearBalance {
         near_add(self.near_balance, self.extra_venear_balance)
     }
 
+    /// Updates the balance that is pooled in the total veNEAR balance. With the capped growth, the
+    /// extra veNEAR balance is capped if the balance was updated after the start timestamp of the
+    /// cap. The balance that was last updated before it grows without the cap until it's capped
+    /// with `cap`, so the pooled total doesn't change when the growth becomes capped.
     pub fn update(
         &mut self,
         previous_timestamp: TimestampNs,
         current_timestamp: TimestampNs,
         venear_growth_config: &VenearGrowthConfig,
+    ) {
+        self.grow(previous_timestamp, current_timestamp, venear_growth_config);
+        if venear_growth_config.is_capped_at(previous_timestamp) {
+            self.cap(venear_growth_config);
+        }
+    }
+
+    /// Updates the balance that is delegated to another account. The delegated balance is not
+    /// pooled, so with the capped growth the extra veNEAR balance is capped after the start
+    /// timestamp of the cap.
+    pub fn update_delegated(
+        &mut self,
+        previous_timestamp: TimestampNs,
+        current_timestamp: TimestampNs,
+        venear_growth_config: &VenearGrowthConfig,
+    ) {
+        self.grow(previous_timestamp, current_timestamp, venear_growth_config);
+        if venear_growth_config.is_capped_at(current_timestamp) {
+            self.cap(venear_growth_config);
+        }
+    }
+
+    /// Caps the extra veNEAR balance at the maximum for the NEAR balance, if the growth is capped.
+    pub fn cap(&mut self, venear_growth_config: &VenearGrowthConfig) {
+        if let Some(max_extra_venear_balance) =
+            venear_growth_config.max_extra_venear_balance(self.near_balance)
+        {
+            self.extra_venear_balance =
+                std::cmp::min(self.extra_venear_balance, max_extra_venear_balance);
+        }
+    }
+
+    fn grow(
+        &mut self,
+        previous_timestamp: TimestampNs,
+        current_timestamp: TimestampNs,
+        venear_growth_config: &VenearGrowthConfig,
     ) {
         self.extra_venear_balance = near_add(
             self.extra_venear_balance,
@@ -98,6 +139,12 @@ impl SubAssign<Self> for VenearBalance {
 /// truncated to milliNEAR for every added `VenearBalance` to avoid rounding errors
 /// during `extra_venear_balance` growth calculations. The truncated `near_balance` is added to
 /// `extra_venear_balance` to ensure that the total balance remains consistent.
+/// The total veNEAR balance grows without the cap. The balances that don't grow, e.g. the
+/// saturated balances with the capped growth, are pooled entirely in `extra_venear_balance` with
+/// `pooled_add_fixed`, and the growing balances are moved there with `pooled_saturate` once they
+/// reach the cap. So the pooled total matches the sum of the pooled balances.
+/// The balances delegated to an account are pooled in its `delegated_balance`, which grows and is
+/// capped as a single balance with `update_delegated`.
 #[derive(Copy, Clone, Default)]
 #[near(serializers=[borsh, json])]
 pub struct PooledVenearBalance(VenearBalance);
@@ -112,11 +159,35 @@ impl PooledVenearBalance {
         previous_timestamp: TimestampNs,
         current_timestamp: TimestampNs,
         venear_growth_config: &VenearGrowthConfig,
+    ) {
+        self.0
+            .grow(previous_timestamp, current_timestamp, venear_growth_config);
+    }
+
+    /// Updates the pool of the balances delegated to the account. With the capped growth, the pool
+    /// is capped the same way as the balance of the account, see `VenearBalance::update`.
+    pub fn update_delegated(
+        &mut self,
+        previous_timestamp: TimestampNs,
+        current_timestamp: TimestampNs,
+        venear_growth_config: &VenearGrowthConfig,
     ) {
         self.0
             .update(previous_timestamp, current_timestamp, venear_growth_config);
     }
 
+    /// Caps the extra veNEAR balance of the pool of the delegated balances at the maximum for its
+    /// NEAR balance, if the growth is capped.
+    pub fn cap(&mut self, venear_growth_config: &VenearGrowthConfig) {
+        self.0.cap(venear_growth_config);
+    }
+
+    /// Returns the pooled balance, e.g. to pool the delegated balances in the total veNEAR balance.
+    pub fn balance(&self) -> &VenearBalance {
+        &self.0
+    }
+
+    /// Adds the growing balance to the pool.
     pub fn pooled_add(&self, other: &VenearBalance) -> Self {
         let truncated_near_balance = truncate_near_to_millis(other.near_balance);
         let difference = near_sub(other.near_balance, truncated_near_balance);
@@ -129,6 +200,7 @@ impl PooledVenearBalance {
         })
     }
 
+    /// Removes the growing balance from the pool.
     pub fn pooled_sub(&self, other: &VenearBalance) -> Self {
         let truncated_near_balance = truncate_near_to_millis(other.near_balance);
         let difference = near_sub(other.near_balance, truncated_near_balance);
@@ -140,6 +212,49 @@ impl PooledVenearBalance {
             ),
         })
     }
+
+    /// Adds the balance that doesn't grow to the pool.
+    pub fn pooled_add_fixed(&self, other: &VenearBalance) -> Self {
+        Self(VenearBalance {
+            near_balance: self.0.near_balance,
+            extra_venear_balance: near_add(self.0.extra_venear_balance, other.total()),
+        })
+    }
+
+    /// Removes the balance that doesn't grow from the pool.
+    pub fn pooled_sub_fixed(&self, other: &VenearBalance) -> Self {
+        Self(VenearBalance {
+            near_balance: self.0.near_balance,
+            extra_venear_balance: near_sub(self.0.extra_venear_balance, other.total()),
+        })
+    }
+
+    /// Removes the balance from the pool of the delegated balances. With the capped growth, the
+    /// pool is capped as a single balance, so it may have less extra veNEAR than the sum of the
+    /// delegated balances. The extra veNEAR of the pool doesn't go below zero.
+    pub fn pooled_sub_delegated(&self, other: &VenearBalance) -> Self {
+        let truncated_near_balance = truncate_near_to_millis(other.near_balance);
+        let difference = near_sub(other.near_balance, truncated_near_balance);
+        Self(VenearBalance {
+            near_balance: near_sub(self.0.near_balance, truncated_near_balance),
+            extra_venear_balance: self
+                .0
+                .extra_venear_balance
+                .saturating_sub(near_add(other.extra_venear_balance, difference)),
+        })
+    }
+
+    /// Stops the growth of the truncated NEAR balance `saturation.near_balance` and removes the
+    /// excess growth `saturation.extra_venear_balance` from the pool.
+    pub fn pooled_saturate(&self, saturation: &VenearBalance) -> Self {
+        Self(VenearBalance {
+            near_balance: near_sub(self.0.near_balance, saturation.near_balance),
+            extra_venear_balance: near_sub(
+                near_add(self.0.extra_venear_balance, saturation.near_balance),
+                saturation.extra_venear_balance,
+            ),
+        })
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
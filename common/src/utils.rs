@@ -16,6 +16,19 @@ pub fn truncate_near_to_millis(near: NearToken) -> NearToken {
     NearToken::from_millinear(near.as_millinear())
 }
 
+/// Returns the range of indices of the page that starts at the given index. The limit defaults to
+/// and is capped by the given maximum page size, and the range doesn't go past the given length.
+pub fn page_range(
+    from_index: u32,
+    limit: Option<u32>,
+    max_limit: u32,
+    length: u32,
+) -> std::ops::Range<u32> {
+    let limit = limit.unwrap_or(max_limit).min(max_limit);
+    let to_index = std::cmp::min(from_index.saturating_add(limit), length);
+    from_index..std::cmp::max(from_index, to_index)
+}
+
 // Tests for `near_add` and `near_sub`
 #[cfg(test)]
 mod tests {
@@ -36,4 +49,17 @@ mod tests {
         let c = NearToken::from_yoctonear(100);
         assert_eq!(near_sub(a, b), c);
     }
+
+    #[test]
+    fn test_page_range() {
+        assert_eq!(page_range(0, None, 10, 25), 0..10);
+        assert_eq!(page_range(20, None, 10, 25), 20..25);
+        assert_eq!(page_range(5, Some(3), 10, 25), 5..8);
+        assert_eq!(page_range(5, Some(100), 10, 25), 5..15);
+        assert_eq!(page_range(30, None, 10, 25), 30..30);
+        assert_eq!(
+            page_range(u32::MAX, Some(u32::MAX), 10, 25),
+            u32::MAX..u32::MAX
+        );
+    }
 }
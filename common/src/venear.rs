@@ -1,10 +1,13 @@
 use crate::*;
 use near_sdk::require;
 
+const NUM_NS_IN_SEC: u64 = 1_000_000_000;
+
 #[derive(Clone)]
 #[near(serializers=[json, borsh])]
 pub enum VenearGrowthConfig {
     FixedRate(Box<VenearGrowthConfigFixedRate>),
+    CappedFixedRate(Box<VenearGrowthConfigCappedFixedRate>),
 }
 
 /// The fixed annual growth rate of veNEAR tokens.
@@ -19,13 +22,48 @@ pub struct VenearGrowthConfigFixedRate {
     pub annual_growth_rate_ns: Fraction,
 }
 
+/// The fixed annual growth rate of veNEAR tokens, where the extra veNEAR balance saturates at the
+/// given multiple of the NEAR balance after the given timestamp.
+/// The balances delegated to an account are capped as a single balance, the same way as the
+/// balance of the account. The balances that were last updated before the start timestamp grow
+/// without the cap until they are updated, which caps the extra veNEAR accumulated before.
+#[derive(Clone)]
+#[near(serializers=[json, borsh])]
+pub struct VenearGrowthConfigCappedFixedRate {
+    /// The growth rate of veNEAR tokens per nanosecond. E.g. `6 / (100 * NUM_SEC_IN_YEAR * 10**9)`
+    /// means 6% annual growth rate.
+    /// Note, the denominator has to be `10**30` to avoid precision issues.
+    pub annual_growth_rate_ns: Fraction,
+
+    /// The maximum extra veNEAR balance as a multiple of the NEAR balance. E.g. `1 / 1` means the
+    /// total veNEAR balance can never exceed 2x of the NEAR balance.
+    pub max_extra_venear_multiplier: Fraction,
+
+    /// The timestamp in nanoseconds when the growth became capped. The balances updated after it
+    /// are capped.
+    pub start_timestamp: TimestampNs,
+}
+
 impl From<VenearGrowthConfigFixedRate> for VenearGrowthConfig {
     fn from(config: VenearGrowthConfigFixedRate) -> Self {
         Self::FixedRate(Box::new(config))
     }
 }
 
+impl From<VenearGrowthConfigCappedFixedRate> for VenearGrowthConfig {
+    fn from(config: VenearGrowthConfigCappedFixedRate) -> Self {
+        Self::CappedFixedRate(Box::new(config))
+    }
+}
+
 impl VenearGrowthConfig {
+    pub fn annual_growth_rate_ns(&self) -> &Fraction {
+        match self {
+            VenearGrowthConfig::FixedRate(config) => &config.annual_growth_rate_ns,
+            VenearGrowthConfig::CappedFixedRate(config) => &config.annual_growth_rate_ns,
+        }
+    }
+
     pub fn calculate(
         &self,
         previous_timestamp: TimestampNs,
@@ -48,15 +86,190 @@ impl VenearGrowthConfig {
             return NearToken::from_yoctonear(0);
         }
         let truncated_near_balance = truncate_near_to_millis(balance);
+        let growth_period_ns = current_timestamp.0 - previous_timestamp.0;
+        NearToken::from_yoctonear(
+            self.annual_growth_rate_ns()
+                .u384_mul(growth_period_ns as _, truncated_near_balance.as_yoctonear()),
+        )
+    }
+
+    /// Returns whether the balance that was updated at the given timestamp is capped, i.e. the
+    /// growth is capped and the timestamp is after the start timestamp of the cap.
+    pub fn is_capped_at(&self, update_timestamp: TimestampNs) -> bool {
         match self {
-            VenearGrowthConfig::FixedRate(config) => {
-                let growth_period_ns = current_timestamp.0 - previous_timestamp.0;
-                NearToken::from_yoctonear(
-                    config
-                        .annual_growth_rate_ns
-                        .u384_mul(growth_period_ns as _, truncated_near_balance.as_yoctonear()),
-                )
+            VenearGrowthConfig::FixedRate(_) => false,
+            VenearGrowthConfig::CappedFixedRate(config) => {
+                update_timestamp > config.start_timestamp
+            }
+        }
+    }
+
+    /// Returns the maximum extra veNEAR balance for the given NEAR balance, or `None` if the growth
+    /// is not capped.
+    pub fn max_extra_venear_balance(&self, near_balance: NearToken) -> Option<NearToken> {
+        match self {
+            VenearGrowthConfig::FixedRate(_) => None,
+            VenearGrowthConfig::CappedFixedRate(config) => Some(NearToken::from_yoctonear(
+                config.max_extra_venear_multiplier * near_balance.as_yoctonear(),
+            )),
+        }
+    }
+
+    /// Returns the timestamp when the extra veNEAR balance of the given balance growing from the
+    /// given timestamp reaches the cap, together with the saturation of the pooled balance at that
+    /// timestamp. See `PooledVenearBalance::pooled_saturate`.
+    /// Returns `None` if the growth is not capped, or the balance doesn't reach the cap.
+    /// The timestamp and the saturation don't change while the balance grows, so they can be
+    /// recomputed from the balance at any time before the saturation.
+    pub fn saturation(
+        &self,
+        balance: &VenearBalance,
+        timestamp: TimestampNs,
+    ) -> Option<(TimestampNs, VenearBalance)> {
+        let remaining = self
+            .max_extra_venear_balance(balance.near_balance)?
+            .as_yoctonear()
+            .checked_sub(balance.extra_venear_balance.as_yoctonear())
+            .filter(|&remaining| remaining > 0)?;
+        let growth_per_sec = self
+            .calculate(0.into(), NUM_NS_IN_SEC.into(), balance.near_balance)
+            .as_yoctonear();
+        if growth_per_sec == 0 {
+            return None;
+        }
+        let num_sec = remaining.div_ceil(growth_per_sec);
+        let saturation_timestamp = num_sec
+            .checked_mul(NUM_NS_IN_SEC as u128)?
+            .checked_add(timestamp.0 as u128)
+            .and_then(|timestamp| u64::try_from(timestamp).ok())?;
+        let growth = growth_per_sec.checked_mul(num_sec)?;
+        Some((
+            saturation_timestamp.into(),
+            VenearBalance {
+                near_balance: truncate_near_to_millis(balance.near_balance),
+                extra_venear_balance: NearToken::from_yoctonear(growth - remaining),
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_YEAR_SEC: u64 = 365 * 24 * 60 * 60;
+
+    fn capped_config(start_timestamp: TimestampNs) -> VenearGrowthConfig {
+        // 10% annual growth rate, capped at 1x of the NEAR balance.
+        VenearGrowthConfigCappedFixedRate {
+            annual_growth_rate_ns: Fraction {
+                numerator: (10 * 10u128.pow(30) / (100 * ONE_YEAR_SEC as u128 * 10u128.pow(9)))
+                    .into(),
+                denominator: 10u128.pow(30).into(),
+            },
+            max_extra_venear_multiplier: Fraction {
+                numerator: 1.into(),
+                denominator: 1.into(),
+            },
+            start_timestamp,
+        }
+        .into()
+    }
+
+    fn years(num_years: u64) -> TimestampNs {
+        (num_years * ONE_YEAR_SEC * 10u64.pow(9)).into()
+    }
+
+    #[test]
+    fn test_capped_growth_saturates() {
+        let config = capped_config(years(0));
+        let mut balance = VenearBalance::from_near(NearToken::from_near(100));
+        balance.update(years(0), years(5), &config);
+        assert!(balance.extra_venear_balance > NearToken::from_near(49));
+        assert!(balance.extra_venear_balance < NearToken::from_near(51));
+
+        balance.update(years(5), years(20), &config);
+        assert_eq!(balance.extra_venear_balance, NearToken::from_near(100));
+        assert_eq!(balance.total(), NearToken::from_near(200));
+    }
+
+    #[test]
+    fn test_capped_growth_starts_at_timestamp() {
+        let config = capped_config(years(15));
+        let mut balance = VenearBalance::from_near(NearToken::from_near(100));
+        balance.update(years(0), years(12), &config);
+        assert!(balance.extra_venear_balance > NearToken::from_near(100));
+
+        // The balance updated before the start timestamp is not capped until it's updated.
+        balance.update(years(12), years(16), &config);
+        assert!(balance.extra_venear_balance > NearToken::from_near(100));
+        balance.cap(&config);
+        assert_eq!(balance.extra_venear_balance, NearToken::from_near(100));
+        balance.update(years(16), years(20), &config);
+        assert_eq!(balance.extra_venear_balance, NearToken::from_near(100));
+
+        // The delegated balance is capped the same way after the start timestamp.
+        let mut delegated_balance = VenearBalance::from_near(NearToken::from_near(10));
+        delegated_balance.update_delegated(years(0), years(14), &config);
+        assert!(delegated_balance.extra_venear_balance > NearToken::from_near(10));
+        delegated_balance.update_delegated(years(14), years(20), &config);
+        assert_eq!(
+            delegated_balance.extra_venear_balance,
+            NearToken::from_near(10)
+        );
+
+        // The pool of the delegated balances is capped as a single balance.
+        let mut pool = PooledVenearBalance::default()
+            .pooled_add(&VenearBalance::from_near(NearToken::from_near(10)))
+            .pooled_add(&VenearBalance::from_near(NearToken::from_near(30)));
+        pool.update_delegated(years(0), years(16), &config);
+        pool.cap(&config);
+        pool.update_delegated(years(16), years(20), &config);
+        assert_eq!(pool.total(), NearToken::from_near(80));
+        let pool = pool.pooled_sub_delegated(&delegated_balance);
+        assert_eq!(pool.total(), NearToken::from_near(60));
+    }
+
+    #[test]
+    fn test_capped_growth_pooled_matches_sum() {
+        let config = capped_config(years(0));
+        let mut a = VenearBalance::from_near(NearToken::from_yoctonear(10u128.pow(26) + 7));
+        let mut b = VenearBalance::from_near(NearToken::from_near(5));
+        let mut pool = PooledVenearBalance::default().pooled_add(&a);
+        let mut saturations = vec![config.saturation(&a, years(0)).unwrap()];
+
+        a.update(years(0), years(3), &config);
+        pool.update(years(0), years(3), &config);
+        assert_eq!(pool.total(), a.total());
+        // The saturation doesn't change while the balance grows.
+        assert_eq!(config.saturation(&a, years(3)).unwrap().0, saturations[0].0);
+        pool = pool.pooled_add(&b);
+        saturations.push(config.saturation(&b, years(3)).unwrap());
+
+        let mut timestamp = years(3);
+        for next_timestamp in [years(6), years(11), years(20)] {
+            a.update(timestamp, next_timestamp, &config);
+            b.update(timestamp, next_timestamp, &config);
+            saturations.sort_by_key(|(saturation_timestamp, _)| *saturation_timestamp);
+            let previous_timestamp = timestamp;
+            for (saturation_timestamp, saturation) in
+                saturations.iter().filter(|(saturation_timestamp, _)| {
+                    *saturation_timestamp > previous_timestamp
+                        && *saturation_timestamp <= next_timestamp
+                })
+            {
+                pool.update(timestamp, *saturation_timestamp, &config);
+                pool = pool.pooled_saturate(saturation);
+                timestamp = *saturation_timestamp;
             }
+            pool.update(timestamp, next_timestamp, &config);
+            timestamp = next_timestamp;
+            assert_eq!(pool.total(), near_add(a.total(), b.total()));
         }
+        assert!(config.saturation(&a, timestamp).is_none());
+        assert!(config.saturation(&b, timestamp).is_none());
+
+        let pool = pool.pooled_sub_fixed(&a).pooled_sub_fixed(&b);
+        assert_eq!(pool.total(), NearToken::from_yoctonear(0));
     }
 }
@@ -47,6 +47,7 @@ pub struct VenearTestWorkspaceBuilder {
     pub local_deposit: NearToken,
     pub min_lockup_deposit: NearToken,
     pub annual_growth_rate_ns: Fraction,
+    pub max_extra_venear_multiplier: Option<Fraction>,
     pub deploy_voting: bool,
     pub voting_duration_ns: u64,
     pub max_number_of_voting_options: u8,
@@ -68,6 +69,7 @@ impl Default for VenearTestWorkspaceBuilder {
                 numerator: 1902587519026.into(),
                 denominator: 10u128.pow(30).into(),
             },
+            max_extra_venear_multiplier: None,
             deploy_voting: false,
             voting_duration_ns: VOTING_DURATION_SECONDS * 1_000_000_000,
             max_number_of_voting_options: 16,
@@ -181,6 +183,22 @@ impl VenearTestWorkspaceBuilder {
             outcome.outcomes()
         );
 
+        if let Some(max_extra_venear_multiplier) = &self.max_extra_venear_multiplier {
+            let outcome = venear_owner
+                .call(venear.id(), "set_max_extra_venear_multiplier")
+                .args_json(json!({
+                    "max_extra_venear_multiplier": max_extra_venear_multiplier,
+                }))
+                .deposit(NearToken::from_yoctonear(1))
+                .transact()
+                .await?;
+            assert!(
+                outcome.is_success(),
+                "Failed to cap the veNEAR growth: {:#?}",
+                outcome.outcomes()
+            );
+        }
+
         let storage_balance_bounds: serde_json::Value = sandbox
             .view(venear.id(), "storage_balance_bounds")
             .await?
@@ -317,6 +335,11 @@ impl VenearTestWorkspaceBuilder {
         self
     }
 
+    pub fn max_extra_venear_multiplier(mut self, max_extra_venear_multiplier: Fraction) -> Self {
+        self.max_extra_venear_multiplier = Some(max_extra_venear_multiplier);
+        self
+    }
+
     pub fn with_voting(mut self) -> Self {
         self.deploy_voting = true;
         self
@@ -337,6 +360,32 @@ impl VenearTestWorkspace {
             .json()?)
     }
 
+    /// Pages through the list view of the contract until `next_from_index` is `null` and returns
+    /// the items of all pages.
+    pub async fn view_all_pages(
+        &self,
+        contract_id: &AccountId,
+        method_name: &str,
+        items_key: &str,
+        limit: u32,
+    ) -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error>> {
+        let mut items = vec![];
+        let mut from_index = Some(0);
+        while let Some(index) = from_index {
+            let page: serde_json::Value = self
+                .sandbox
+                .view(contract_id, method_name)
+                .args_json(json!({ "from_index": index, "limit": limit }))
+                .await?
+                .json()?;
+            let page_items = page[items_key].as_array().unwrap();
+            assert!(page_items.len() as u32 <= limit);
+            items.extend(page_items.iter().cloned());
+            from_index = page["next_from_index"].as_u64().map(|index| index as u32);
+        }
+        Ok(items)
+    }
+
     pub async fn ft_balance(
         &self,
         account_id: &AccountId,
@@ -349,6 +398,19 @@ impl VenearTestWorkspace {
             .json()?)
     }
 
+    pub async fn ft_balance_at(
+        &self,
+        account_id: &AccountId,
+        timestamp: Timestamp,
+    ) -> Result<NearToken, Box<dyn std::error::Error>> {
+        Ok(self
+            .sandbox
+            .view(self.venear.id(), "ft_balance_of_at")
+            .args_json(json!({ "account_id": account_id, "timestamp": timestamp.to_string() }))
+            .await?
+            .json()?)
+    }
+
     pub async fn create_account_with_lockup(&self) -> Result<Account, Box<dyn std::error::Error>> {
         let user_account = self.sandbox.dev_create_account().await?;
 
@@ -4,6 +4,7 @@ use crate::setup::{
     assert_almost_eq, outcome_check, VenearTestWorkspace, VenearTestWorkspaceBuilder,
     UNLOCK_DURATION_SECONDS,
 };
+use common::lockup_update::FungibleTokenBalance;
 use near_sdk::json_types::U128;
 use near_sdk::Gas;
 use near_workspaces::types::NearToken;
@@ -296,7 +297,7 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
     // Should fail, because the staking pool is not selected
     let outcome = user
         .call(&lockup_id, "deposit_and_stake")
-        .args_json(json!({ "amount": NearToken::from_near(50) }))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id(), "amount": NearToken::from_near(50) }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -321,14 +322,14 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
         "Selecting non-whitelisted staking pool should fail"
     );
 
-    let selected_staking_pool_id: Option<AccountId> = v
+    let selected_staking_pool_ids: Vec<AccountId> = v
         .sandbox
-        .view(&lockup_id, "get_staking_pool_account_id")
+        .view(&lockup_id, "get_staking_pool_account_ids")
         .await?
         .json()?;
 
     assert!(
-        selected_staking_pool_id.is_none(),
+        selected_staking_pool_ids.is_empty(),
         "Staking pool should not be set"
     );
 
@@ -345,15 +346,15 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
         "Selecting whitelisted staking pool should be successful"
     );
 
-    let selected_staking_pool_id: Option<AccountId> = v
+    let selected_staking_pool_ids: Vec<AccountId> = v
         .sandbox
-        .view(&lockup_id, "get_staking_pool_account_id")
+        .view(&lockup_id, "get_staking_pool_account_ids")
         .await?
         .json()?;
 
     assert_eq!(
-        selected_staking_pool_id.as_ref(),
-        Some(v.staking_pool.id()),
+        selected_staking_pool_ids,
+        vec![v.staking_pool.id().clone()],
         "Staking pool should be set correctly"
     );
 
@@ -375,7 +376,7 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
     // Deposit and stake 50 NEAR
     let outcome = user
         .call(&lockup_id, "deposit_and_stake")
-        .args_json(json!({ "amount": NearToken::from_near(50) }))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id(), "amount": NearToken::from_near(50) }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -513,7 +514,7 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
     // Refresh staking pool balance
     let outcome = user
         .call(&lockup_id, "refresh_staking_pool_balance")
-        .args_json(json!({}))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id() }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(100))
         .transact()
@@ -537,7 +538,7 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
     // Start unstaking process of 60 NEAR
     let outcome = user
         .call(&lockup_id, "unstake")
-        .args_json(json!({ "amount": NearToken::from_near(60) }))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id(), "amount": NearToken::from_near(60) }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -576,7 +577,7 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
     // Refresh staking pool balance
     let outcome = user
         .call(&lockup_id, "refresh_staking_pool_balance")
-        .args_json(json!({}))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id() }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(100))
         .transact()
@@ -620,7 +621,7 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
     // Withdraw 25 NEAR
     let outcome = user
         .call(&lockup_id, "withdraw_from_staking_pool")
-        .args_json(json!({ "amount": NearToken::from_near(25) }))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id(), "amount": NearToken::from_near(25) }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -667,7 +668,7 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
     // Withdraw all remaining unstaked NEAR
     let outcome = user
         .call(&lockup_id, "withdraw_all_from_staking_pool")
-        .args_json(json!({}))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id() }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -714,7 +715,7 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
     // Attempt to unselect the staking pool
     let outcome = user
         .call(&lockup_id, "unselect_staking_pool")
-        .args_json(json!({}))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id() }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -727,7 +728,7 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
     // Unstake all NEAR
     let outcome = user
         .call(&lockup_id, "unstake_all")
-        .args_json(json!({}))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id() }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -796,7 +797,7 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
     // Withdraw all NEAR
     let outcome = user
         .call(&lockup_id, "withdraw_all_from_staking_pool")
-        .args_json(json!({}))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id() }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -829,7 +830,7 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
     // Unselect the staking pool
     let outcome = user
         .call(&lockup_id, "unselect_staking_pool")
-        .args_json(json!({}))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id() }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -839,13 +840,13 @@ pub async fn test_lockup_staking() -> Result<(), Box<dyn std::error::Error>> {
         "Unselecting staking pool should be successful"
     );
 
-    let selected_staking_pool_id: Option<AccountId> = v
+    let selected_staking_pool_ids: Vec<AccountId> = v
         .sandbox
-        .view(&lockup_id, "get_staking_pool_account_id")
+        .view(&lockup_id, "get_staking_pool_account_ids")
         .await?
         .json()?;
     assert!(
-        selected_staking_pool_id.is_none(),
+        selected_staking_pool_ids.is_empty(),
         "Staking pool should not be set"
     );
 
@@ -882,22 +883,22 @@ pub async fn test_lockup_delete_after_staking() -> Result<(), Box<dyn std::error
         "Selecting whitelisted staking pool should be successful"
     );
 
-    let selected_staking_pool_id: Option<AccountId> = v
+    let selected_staking_pool_ids: Vec<AccountId> = v
         .sandbox
-        .view(&lockup_id, "get_staking_pool_account_id")
+        .view(&lockup_id, "get_staking_pool_account_ids")
         .await?
         .json()?;
 
     assert_eq!(
-        selected_staking_pool_id.as_ref(),
-        Some(v.staking_pool.id()),
+        selected_staking_pool_ids,
+        vec![v.staking_pool.id().clone()],
         "Staking pool should be set correctly"
     );
 
     // Deposit to the staking pool
     let outcome = user
         .call(&lockup_id, "deposit_to_staking_pool")
-        .args_json(json!({ "amount": NearToken::from_near(50) }))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id(), "amount": NearToken::from_near(50) }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -934,7 +935,7 @@ pub async fn test_lockup_delete_after_staking() -> Result<(), Box<dyn std::error
     // Withdraw all NEAR
     let outcome = user
         .call(&lockup_id, "withdraw_all_from_staking_pool")
-        .args_json(json!({}))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id() }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -958,7 +959,7 @@ pub async fn test_lockup_delete_after_staking() -> Result<(), Box<dyn std::error
     // Unselect the staking pool
     let outcome = user
         .call(&lockup_id, "unselect_staking_pool")
-        .args_json(json!({}))
+        .args_json(json!({ "staking_pool_account_id": v.staking_pool.id() }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
@@ -968,13 +969,13 @@ pub async fn test_lockup_delete_after_staking() -> Result<(), Box<dyn std::error
         "Unselecting staking pool should be successful"
     );
 
-    let selected_staking_pool_id: Option<AccountId> = v
+    let selected_staking_pool_ids: Vec<AccountId> = v
         .sandbox
-        .view(&lockup_id, "get_staking_pool_account_id")
+        .view(&lockup_id, "get_staking_pool_account_ids")
         .await?
         .json()?;
     assert!(
-        selected_staking_pool_id.is_none(),
+        selected_staking_pool_ids.is_empty(),
         "Staking pool should not be set"
     );
 
@@ -1023,37 +1024,36 @@ pub async fn test_ft_on_transfer_success() -> Result<(), Box<dyn std::error::Err
     let root = v.sandbox.root_account().unwrap();
     let lockup_id = v.get_lockup_account_id(user.id()).await?;
 
-    let staking_pool = v.sandbox.dev_create_account().await?;
+    let lst = v.sandbox.dev_create_account().await?;
 
-    // Whitelist the staking pool account
-    let pool_add = v
+    // Whitelist the liquid staking token account
+    let lst_add = v
         .staking_pool_whitelist_account
         .call(v.staking_pool_whitelist_account.id(), "sandbox_whitelist")
         .args_json(json!({
-            "staking_pool_account_id": staking_pool.id(),
+            "staking_pool_account_id": lst.id(),
         }))
         .transact()
         .await?;
     assert!(
-        pool_add.is_success(),
-        "Failed to whitelist staking_pool: {:#?}",
-        pool_add.outcomes()
+        lst_add.is_success(),
+        "Failed to whitelist LST: {:#?}",
+        lst_add.outcomes()
     );
 
-    // Attempt to select non-whitelisted staking pool
-    let select_pool = user
-        .call(&lockup_id, "select_staking_pool")
-        .args_json(json!({ "staking_pool_account_id": staking_pool.id() }))
+    let select_lst = user
+        .call(&lockup_id, "select_liquid_staking_token")
+        .args_json(json!({ "token_account_id": lst.id(), "price_method": "FtPrice" }))
         .deposit(NearToken::from_yoctonear(1))
         .gas(Gas::from_tgas(200))
         .transact()
         .await?;
     assert!(
-        select_pool.is_success(),
-        "Selecting whitelisted staking pool should succeed"
+        select_lst.is_success(),
+        "Selecting whitelisted LST should succeed"
     );
 
-    let outcome = staking_pool
+    let outcome = lst
         .call(&lockup_id, "ft_on_transfer")
         .args_json(json!({ "sender_id": lockup_id, "amount": "1".to_string(), "msg": "Lorem ipsum".to_string() }))
         .gas(Gas::from_tgas(100))
@@ -1065,5 +1065,17 @@ pub async fn test_ft_on_transfer_success() -> Result<(), Box<dyn std::error::Err
     let amt: Option<U128> = outcome.json()?;
     assert_eq!(amt.unwrap(), 0u128.into());
 
+    let fungible_tokens: Vec<FungibleTokenBalance> = user
+        .view(&lockup_id, "get_liquid_staking_tokens")
+        .args_json(json!({}))
+        .await?
+        .json()?;
+    assert_eq!(fungible_tokens.len(), 1);
+    assert_eq!(
+        fungible_tokens[0].token_account_id.as_str(),
+        lst.id().as_str()
+    );
+    assert_eq!(fungible_tokens[0].balance, 1u128.into());
+
     Ok(())
 }
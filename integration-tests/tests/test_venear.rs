@@ -3,7 +3,12 @@ mod setup;
 use crate::setup::{
     assert_almost_eq, VenearTestWorkspace, VenearTestWorkspaceBuilder, VENEAR_WASM_FILEPATH,
 };
-use common::{near_add, Fraction, TimestampNs};
+use common::account::{Account, LockBoost, VAccount};
+use common::{near_add, Fraction, TimestampNs, VenearBalance};
+use merkle_tree::{
+    MerkleMultiProof, MerkleNonMembershipProof, MerkleProof, MerkleTreeFormat, MerkleTreeSnapshot,
+    OffchainMerkleTree,
+};
 use near_sdk::json_types::Base58CryptoHash;
 use near_sdk::{CryptoHash, Gas};
 use near_workspaces::types::NearToken;
@@ -294,6 +299,471 @@ async fn attempt_venear_upgrade(
     Ok(())
 }
 
+#[tokio::test]
+async fn test_ft_balance_of_at() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let user_a = v.create_account_with_lockup().await?;
+    let user_b = v.create_account_with_lockup().await?;
+    v.transfer_and_lock(&user_a, NearToken::from_near(1000))
+        .await?;
+
+    let lock_timestamp = v.sandbox.view_block().await?.timestamp();
+    // 1000 from lockup + 0.1 from local storage
+    let expected_balance = NearToken::from_millinear(1000100);
+
+    // Move to the next second, so the delegation is recorded as a new checkpoint.
+    v.fast_forward(lock_timestamp + 2 * 10u64.pow(9), 5, 10)
+        .await?;
+
+    let outcome = user_a
+        .call(v.venear.id(), "delegate_all")
+        .args_json(json!({
+            "receiver_id": user_b.id()
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(
+        outcome.is_success(),
+        "Failed to delegate NEAR: {:#?}",
+        outcome.outcomes()
+    );
+    let timestamp = v.sandbox.view_block().await?.timestamp();
+    assert!(v.ft_balance(user_a.id()).await?.is_zero());
+    assert!(v.ft_balance_at(user_a.id(), timestamp).await?.is_zero());
+
+    // Every update of the account stores the previous value as a checkpoint.
+    let num_checkpoints: u32 = v
+        .sandbox
+        .view(v.venear.id(), "get_num_account_checkpoints")
+        .args_json(json!({ "account_id": user_a.id() }))
+        .await?
+        .json()?;
+    assert!(num_checkpoints >= 1);
+
+    // The balances before the delegation are preserved, including the timestamps between the
+    // updates.
+    for past_timestamp in [lock_timestamp, lock_timestamp + 10u64.pow(9)] {
+        let balance_a = v.ft_balance_at(user_a.id(), past_timestamp).await?;
+        assert_almost_eq(balance_a, expected_balance, NearToken::from_millinear(10));
+        let balance_b = v.ft_balance_at(user_b.id(), past_timestamp).await?;
+        assert_almost_eq(
+            balance_b,
+            NearToken::from_millinear(100),
+            NearToken::from_millinear(10),
+        );
+    }
+    let balance_b = v.ft_balance_at(user_b.id(), timestamp).await?;
+    assert_almost_eq(
+        balance_b,
+        near_add(expected_balance, NearToken::from_millinear(100)),
+        NearToken::from_millinear(10),
+    );
+
+    // The account was not registered yet.
+    assert!(v.ft_balance_at(user_a.id(), 0).await?.is_zero());
+
+    // The future timestamps are not allowed.
+    assert!(v
+        .ft_balance_at(user_a.id(), timestamp + 3600 * 10u64.pow(9))
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_projected_balance() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let user = v.create_account_with_lockup().await?;
+    v.transfer_and_lock(&user, NearToken::from_near(1000))
+        .await?;
+
+    let timestamp = v.sandbox.view_block().await?.timestamp();
+    // One year after the current block.
+    let projected_timestamp = timestamp + 365 * 24 * 60 * 60 * 10u64.pow(9);
+
+    let projected_balance: NearToken = v
+        .sandbox
+        .view(v.venear.id(), "ft_balance_of_projected")
+        .args_json(json!({
+            "account_id": user.id(),
+            "timestamp": projected_timestamp.to_string(),
+        }))
+        .await?
+        .json()?;
+    // 1000 from lockup + 0.1 from local storage with 6% annual growth
+    assert_almost_eq(
+        projected_balance,
+        NearToken::from_millinear(1060106),
+        NearToken::from_millinear(10),
+    );
+
+    let projected_total_supply: NearToken = v
+        .sandbox
+        .view(v.venear.id(), "ft_total_supply_projected")
+        .args_json(json!({
+            "timestamp": projected_timestamp.to_string(),
+        }))
+        .await?
+        .json()?;
+    assert_eq!(projected_total_supply, projected_balance);
+
+    // The past timestamps are not allowed.
+    assert!(v
+        .sandbox
+        .view(v.venear.id(), "ft_total_supply_projected")
+        .args_json(json!({
+            "timestamp": "0",
+        }))
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_snapshot_at() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let user_a = v.create_account_with_lockup().await?;
+    v.transfer_and_lock(&user_a, NearToken::from_near(100))
+        .await?;
+
+    let (snapshot, _): (serde_json::Value, serde_json::Value) = v
+        .sandbox
+        .view(v.venear.id(), "get_snapshot")
+        .await?
+        .json()?;
+    let block_height = snapshot["block_height"].as_u64().unwrap();
+    let proof: (serde_json::Value, serde_json::Value) = v
+        .sandbox
+        .view(v.venear.id(), "get_proof")
+        .args_json(json!({ "account_id": user_a.id() }))
+        .await?
+        .json()?;
+
+    let user_b = v.create_account_with_lockup().await?;
+    v.transfer_and_lock(&user_b, NearToken::from_near(100))
+        .await?;
+
+    let (new_snapshot, _): (serde_json::Value, serde_json::Value) = v
+        .sandbox
+        .view(v.venear.id(), "get_snapshot")
+        .await?
+        .json()?;
+    assert_ne!(new_snapshot["root"], snapshot["root"]);
+
+    let (old_snapshot, _): (serde_json::Value, serde_json::Value) = v
+        .sandbox
+        .view(v.venear.id(), "get_snapshot_at")
+        .args_json(json!({ "block_height": block_height }))
+        .await?
+        .json()?;
+    assert_eq!(old_snapshot, snapshot);
+
+    // The proof against the old snapshot matches the proof that was returned before the update.
+    let old_proof: (serde_json::Value, serde_json::Value) = v
+        .sandbox
+        .view(v.venear.id(), "get_proof_at")
+        .args_json(json!({ "account_id": user_a.id(), "block_height": block_height }))
+        .await?
+        .json()?;
+    assert_eq!(old_proof, proof);
+
+    // The account didn't exist at the old snapshot.
+    assert!(v
+        .sandbox
+        .view(v.venear.id(), "get_proof_at")
+        .args_json(json!({ "account_id": user_b.id(), "block_height": block_height }))
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_offchain_merkle_tree() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    for _ in 0..3 {
+        let user = v.create_account_with_lockup().await?;
+        v.transfer_and_lock(&user, NearToken::from_near(10)).await?;
+    }
+
+    let (snapshot, _): (MerkleTreeSnapshot, serde_json::Value) = v
+        .sandbox
+        .view(v.venear.id(), "get_snapshot")
+        .await?
+        .json()?;
+    let accounts: Vec<VAccount> = v
+        .view_all_pages(v.venear.id(), "get_accounts_raw", "accounts", 500)
+        .await?
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<Result<_, _>>()?;
+
+    let format: MerkleTreeFormat = v
+        .sandbox
+        .view(v.venear.id(), "get_tree_format")
+        .await?
+        .json()?;
+    assert_eq!(format, MerkleTreeFormat::DomainSeparated);
+
+    let tree = OffchainMerkleTree::from_values(format, &accounts);
+    assert_eq!(tree.len(), snapshot.length);
+    assert_eq!(tree.root(), CryptoHash::from(snapshot.root));
+
+    for (index, v_account) in accounts.iter().enumerate() {
+        let proof = tree.get_proof(index as u32).unwrap();
+        assert!(proof.is_valid(
+            snapshot.format,
+            snapshot.root.into(),
+            snapshot.length,
+            v_account
+        ));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_multi_proof() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let mut users = vec![];
+    for _ in 0..5 {
+        let user = v.create_account_with_lockup().await?;
+        v.transfer_and_lock(&user, NearToken::from_near(10)).await?;
+        users.push(user);
+    }
+
+    let (snapshot, _): (MerkleTreeSnapshot, serde_json::Value) = v
+        .sandbox
+        .view(v.venear.id(), "get_snapshot")
+        .await?
+        .json()?;
+    let account_ids = vec![users[3].id(), users[0].id(), users[4].id(), users[3].id()];
+    let (proof, accounts): (MerkleMultiProof, Vec<VAccount>) = v
+        .sandbox
+        .view(v.venear.id(), "get_multi_proof")
+        .args_json(json!({ "account_ids": account_ids }))
+        .await?
+        .json()?;
+    assert_eq!(proof.indices.len(), 3);
+    assert_eq!(accounts.len(), 3);
+    assert!(proof.is_valid(
+        snapshot.format,
+        snapshot.root.into(),
+        snapshot.length,
+        &accounts
+    ));
+
+    let unknown_account_id: AccountId = "unknown.near".parse().unwrap();
+    assert!(v
+        .sandbox
+        .view(v.venear.id(), "get_multi_proof")
+        .args_json(json!({ "account_ids": [users[0].id(), unknown_account_id] }))
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_non_membership_proof() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let mut users = vec![];
+    for _ in 0..3 {
+        let user = v.create_account_with_lockup().await?;
+        v.transfer_and_lock(&user, NearToken::from_near(10)).await?;
+        users.push(user);
+    }
+
+    let (snapshot, _): (MerkleTreeSnapshot, serde_json::Value) = v
+        .sandbox
+        .view(v.venear.id(), "get_snapshot")
+        .await?
+        .json()?;
+    let keys_root: Base58CryptoHash = v
+        .sandbox
+        .view(v.venear.id(), "get_keys_root")
+        .await?
+        .json()?;
+    assert_eq!(snapshot.keys_root, Some(keys_root));
+    let unknown_account_id: AccountId = "unknown.near".parse().unwrap();
+    let proof: MerkleNonMembershipProof = v
+        .sandbox
+        .view(v.venear.id(), "get_non_membership_proof")
+        .args_json(json!({ "account_id": unknown_account_id }))
+        .await?
+        .json()?;
+    let unknown_account_id: near_sdk::AccountId = unknown_account_id.as_str().parse().unwrap();
+    assert!(proof.is_valid(&unknown_account_id, keys_root.into(), snapshot.length));
+
+    // The registered account doesn't have a non-membership proof.
+    assert!(v
+        .sandbox
+        .view(v.venear.id(), "get_non_membership_proof")
+        .args_json(json!({ "account_id": users[0].id() }))
+        .await
+        .is_err());
+
+    // The account registered after the snapshot is not a member of the snapshot.
+    let new_user = v.create_account_with_lockup().await?;
+    let proof: MerkleNonMembershipProof = v
+        .sandbox
+        .view(v.venear.id(), "get_non_membership_proof_at")
+        .args_json(json!({ "account_id": new_user.id(), "block_height": snapshot.block_height }))
+        .await?
+        .json()?;
+    let new_account_id: near_sdk::AccountId = new_user.id().as_str().parse().unwrap();
+    assert!(proof.is_valid(&new_account_id, keys_root.into(), snapshot.length));
+    assert!(v
+        .sandbox
+        .view(v.venear.id(), "get_non_membership_proof")
+        .args_json(json!({ "account_id": new_user.id() }))
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+/// Measures the gas burnt by veNEAR for the lockup update and the delegation in a tree with
+/// multiple levels.
+#[tokio::test]
+async fn test_gas_benchmarks() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let mut users = vec![];
+    for _ in 0..20 {
+        let user = v.create_account_with_lockup().await?;
+        v.transfer_and_lock(&user, NearToken::from_near(10)).await?;
+        users.push(user);
+    }
+    let venear_gas_burnt = |outcome: &near_workspaces::result::ExecutionFinalResult| {
+        outcome
+            .receipt_outcomes()
+            .iter()
+            .filter(|receipt| &receipt.executor_id == v.venear.id())
+            .map(|receipt| receipt.gas_burnt.as_gas())
+            .sum::<u64>()
+    };
+
+    let outcome = users[0]
+        .call(v.venear.id(), "delegate_all")
+        .args_json(json!({ "receiver_id": users[1].id() }))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(outcome.is_success());
+    let delegate_all_gas = venear_gas_burnt(&outcome);
+
+    // The lockup update of the delegating account also updates the delegation account.
+    let lockup_id = v.get_lockup_account_id(users[0].id()).await?;
+    v.sandbox
+        .root_account()?
+        .transfer_near(&lockup_id, NearToken::from_near(5))
+        .await?
+        .into_result()?;
+    let outcome = users[0]
+        .call(&lockup_id, "lock_near")
+        .args_json(json!({}))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(200))
+        .transact()
+        .await?;
+    assert!(outcome.is_success());
+    let lockup_update_gas = venear_gas_burnt(&outcome);
+
+    // The lockup contract attaches 20 Tgas to `on_lockup_update`. The gas of the Merkle tree
+    // updates is compared with the tree before the retained snapshots in the `merkle-tree` tests.
+    assert!(lockup_update_gas < Gas::from_tgas(20).as_gas());
+    assert!(delegate_all_gas < Gas::from_tgas(20).as_gas());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_storage_unregister() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let user_a = v.create_account_with_lockup().await?;
+    let user_b = v.sandbox.dev_create_account().await?;
+    let local_deposit: NearToken = v
+        .sandbox
+        .view(v.venear.id(), "storage_balance_bounds")
+        .await?
+        .json::<serde_json::Value>()?["min"]
+        .as_str()
+        .unwrap()
+        .parse::<u128>()
+        .map(NearToken::from_yoctonear)?;
+    user_b
+        .call(v.venear.id(), "storage_deposit")
+        .args_json(json!({}))
+        .deposit(local_deposit)
+        .transact()
+        .await?
+        .into_result()?;
+    let num_accounts: u32 = v
+        .sandbox
+        .view(v.venear.id(), "get_num_accounts")
+        .await?
+        .json()?;
+
+    // The account with the lockup can't be unregistered.
+    let outcome = user_a
+        .call(v.venear.id(), "storage_unregister")
+        .args_json(json!({}))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+
+    let total_supply_before: NearToken = v
+        .sandbox
+        .view(v.venear.id(), "ft_total_supply")
+        .await?
+        .json()?;
+    let balance_before = user_b.view_account().await?.balance;
+    let outcome = user_b
+        .call(v.venear.id(), "storage_unregister")
+        .args_json(json!({}))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_success());
+    assert!(outcome.json::<bool>()?);
+    let balance_after = user_b.view_account().await?.balance;
+    assert!(balance_after > balance_before);
+
+    assert!(v.account_info(user_b.id()).await?.is_null());
+    assert_eq!(v.ft_balance(user_b.id()).await?, NearToken::from_near(0));
+    // The veNEAR balance of the unregistered account is burned.
+    let total_supply_after: NearToken = v
+        .sandbox
+        .view(v.venear.id(), "ft_total_supply")
+        .await?
+        .json()?;
+    assert!(total_supply_after < total_supply_before);
+    let free_indices: Vec<u32> = v
+        .sandbox
+        .view(v.venear.id(), "get_free_indices")
+        .await?
+        .json()?;
+    assert_eq!(free_indices, vec![num_accounts - 1]);
+
+    // The new account reuses the index of the unregistered account.
+    let user_c = v.create_account_with_lockup().await?;
+    let new_num_accounts: u32 = v
+        .sandbox
+        .view(v.venear.id(), "get_num_accounts")
+        .await?
+        .json()?;
+    assert_eq!(new_num_accounts, num_accounts);
+    assert!(!v.account_info(user_c.id()).await?.is_null());
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_upgrade_venear() -> Result<(), Box<dyn std::error::Error>> {
     let v = VenearTestWorkspaceBuilder::default().build().await?;
@@ -371,29 +841,255 @@ async fn test_venear_growth() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 #[tokio::test]
-async fn test_ft_events() -> Result<(), Box<dyn std::error::Error>> {
+async fn test_venear_growth_capped() -> Result<(), Box<dyn std::error::Error>> {
+    // 10 minutes in nanoseconds
+    let period = 600 * 10u128.pow(9);
+    // Configure the annual growth rate to be 10% per selected period, capped at 2% of the NEAR
     let v = VenearTestWorkspaceBuilder::default()
         .annual_growth_rate_ns(Fraction {
-            numerator: 0.into(),
+            numerator: (10 * 10u128.pow(30) / (100 * period)).into(),
             denominator: 10u128.pow(30).into(),
         })
+        .max_extra_venear_multiplier(Fraction {
+            numerator: 2.into(),
+            denominator: 100.into(),
+        })
         .build()
         .await?;
-    let user_a = v.sandbox.dev_create_account().await?;
+    let user = v.create_account_with_lockup().await?;
+    v.transfer_and_lock(&user, NearToken::from_near(1000))
+        .await?;
 
-    let storage_balance_bounds: serde_json::Value = v
+    let expected_balance = NearToken::from_millinear(1000100);
+    let start_timestamp = v.sandbox.view_block().await?.timestamp();
+
+    // The uncapped growth would be 10% after the period.
+    v.fast_forward(
+        start_timestamp + period as u64,
+        (period / 10u128.pow(9)) as u64 / 5,
+        30,
+    )
+    .await?;
+
+    let max_balance = NearToken::from_yoctonear(expected_balance.as_yoctonear() / 100 * 102);
+    let balance = v.ft_balance(user.id()).await?;
+    assert_eq!(balance, max_balance);
+
+    let total_supply: NearToken = v
         .sandbox
-        .view(v.venear.id(), "storage_balance_bounds")
+        .view(v.venear.id(), "ft_total_supply")
+        .args_json(json!({}))
         .await?
         .json()?;
+    assert_eq!(total_supply, balance);
 
-    let storage_balance_bounds_min: NearToken =
-        serde_json::from_value(storage_balance_bounds["min"].clone())?;
+    Ok(())
+}
 
-    let outcome = user_a
-        .call(v.venear.id(), "storage_deposit")
-        .deposit(storage_balance_bounds_min)
-        .args_json(json!({}))
+#[tokio::test]
+async fn test_venear_growth_cap_migration() -> Result<(), Box<dyn std::error::Error>> {
+    // 10 minutes in nanoseconds
+    let period = 600 * 10u128.pow(9);
+    // Configure the annual growth rate to be 10% per selected period
+    let v = VenearTestWorkspaceBuilder::default()
+        .annual_growth_rate_ns(Fraction {
+            numerator: (10 * 10u128.pow(30) / (100 * period)).into(),
+            denominator: 10u128.pow(30).into(),
+        })
+        .build()
+        .await?;
+    let user_a = v.create_account_with_lockup().await?;
+    v.transfer_and_lock(&user_a, NearToken::from_near(1000))
+        .await?;
+    let user_b = v.create_account_with_lockup().await?;
+    v.transfer_and_lock(&user_b, NearToken::from_near(10))
+        .await?;
+    let outcome = user_b
+        .call(v.venear.id(), "delegate_all")
+        .args_json(json!({
+            "receiver_id": user_a.id()
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "Failed to delegate");
+
+    let start_timestamp = v.sandbox.view_block().await?.timestamp();
+    v.fast_forward(
+        start_timestamp + period as u64 / 2,
+        (period / 10u128.pow(9)) as u64 / 10,
+        30,
+    )
+    .await?;
+
+    // Capping the growth at 2% of the NEAR, which is below the accumulated extra veNEAR.
+    let outcome = v
+        .venear_owner
+        .call(v.venear.id(), "set_max_extra_venear_multiplier")
+        .args_json(json!({
+            "max_extra_venear_multiplier": Fraction {
+                numerator: 2.into(),
+                denominator: 100.into(),
+            },
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "Failed to cap the growth");
+
+    // The total supply matches the sum of the balances before the accounts are capped.
+    let total_supply: NearToken = v
+        .sandbox
+        .view(v.venear.id(), "ft_total_supply")
+        .args_json(json!({}))
+        .await?
+        .json()?;
+    assert_eq!(
+        total_supply,
+        v.ft_balance(user_a.id())
+            .await?
+            .checked_add(v.ft_balance(user_b.id()).await?)
+            .unwrap()
+    );
+
+    let outcome = v
+        .venear_owner
+        .call(v.venear.id(), "migrate_venear_growth_cap")
+        .args_json(json!({ "limit": 100 }))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(300))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "Failed to migrate the accounts");
+    assert!(outcome.json::<bool>()?, "The migration is not complete");
+
+    // The delegated balance is saturated at 2% extra veNEAR once the account is capped.
+    let delegated_balance = v.ft_balance(user_a.id()).await?;
+    let start_timestamp = v.sandbox.view_block().await?.timestamp();
+    v.fast_forward(
+        start_timestamp + period as u64,
+        (period / 10u128.pow(9)) as u64 / 5,
+        30,
+    )
+    .await?;
+    assert_eq!(v.ft_balance(user_a.id()).await?, delegated_balance);
+
+    let outcome = user_b
+        .call(v.venear.id(), "undelegate")
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "Failed to undelegate");
+
+    // 1000 from lockup + 0.1 from local storage, saturated at 2% extra veNEAR
+    let balance_a = v.ft_balance(user_a.id()).await?;
+    assert_eq!(balance_a, NearToken::from_millinear(1020102));
+    // 10 from lockup + 0.1 from local storage, saturated at 2% extra veNEAR
+    let balance_b = v.ft_balance(user_b.id()).await?;
+    assert_eq!(balance_b, NearToken::from_millinear(10302));
+
+    let total_supply: NearToken = v
+        .sandbox
+        .view(v.venear.id(), "ft_total_supply")
+        .args_json(json!({}))
+        .await?
+        .json()?;
+    assert_eq!(total_supply, balance_a.checked_add(balance_b).unwrap());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_venear_proportional_forfeiture() -> Result<(), Box<dyn std::error::Error>> {
+    // 10 minutes in nanoseconds
+    let period = 600 * 10u128.pow(9);
+    // Configure the annual growth rate to be 10% per selected period, capped at 2% of the NEAR
+    let v = VenearTestWorkspaceBuilder::default()
+        .annual_growth_rate_ns(Fraction {
+            numerator: (10 * 10u128.pow(30) / (100 * period)).into(),
+            denominator: 10u128.pow(30).into(),
+        })
+        .max_extra_venear_multiplier(Fraction {
+            numerator: 2.into(),
+            denominator: 100.into(),
+        })
+        .build()
+        .await?;
+
+    let outcome = v
+        .venear_owner
+        .call(v.venear.id(), "set_unlock_forfeiture_policy")
+        .args_json(json!({
+            "unlock_forfeiture_policy": "Proportional"
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(
+        outcome.is_success(),
+        "Owner should be able to set unlock forfeiture policy",
+    );
+
+    let user = v.create_account_with_lockup().await?;
+    let lockup_account_id = v.get_lockup_account_id(user.id()).await?;
+    v.transfer_and_lock(&user, NearToken::from_near(1000))
+        .await?;
+
+    let start_timestamp = v.sandbox.view_block().await?.timestamp();
+    v.fast_forward(
+        start_timestamp + period as u64,
+        (period / 10u128.pow(9)) as u64 / 5,
+        30,
+    )
+    .await?;
+
+    // 1000 from lockup + 0.1 from local storage, saturated at 2% extra veNEAR
+    let balance = v.ft_balance(user.id()).await?;
+    assert_eq!(balance, NearToken::from_millinear(1020102));
+
+    let outcome = user
+        .call(&lockup_account_id, "begin_unlock_near")
+        .args_json(json!({ "amount": NearToken::from_near(500) }))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "Failed to begin unlock");
+
+    // The remaining 500.1 NEAR keep their part of the extra veNEAR
+    let balance = v.ft_balance(user.id()).await?;
+    assert_eq!(balance, NearToken::from_millinear(510102));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ft_events() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default()
+        .annual_growth_rate_ns(Fraction {
+            numerator: 0.into(),
+            denominator: 10u128.pow(30).into(),
+        })
+        .build()
+        .await?;
+    let user_a = v.sandbox.dev_create_account().await?;
+
+    let storage_balance_bounds: serde_json::Value = v
+        .sandbox
+        .view(v.venear.id(), "storage_balance_bounds")
+        .await?
+        .json()?;
+
+    let storage_balance_bounds_min: NearToken =
+        serde_json::from_value(storage_balance_bounds["min"].clone())?;
+
+    let outcome = user_a
+        .call(v.venear.id(), "storage_deposit")
+        .deposit(storage_balance_bounds_min)
+        .args_json(json!({}))
         .transact()
         .await?;
     assert!(outcome.is_success());
@@ -1277,3 +1973,566 @@ async fn test_venear_pause() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_account_summaries() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let mut users = vec![];
+    for _ in 0..3 {
+        let user = v.create_account_with_lockup().await?;
+        v.transfer_and_lock(&user, NearToken::from_near(10)).await?;
+        users.push(user);
+    }
+    let num_accounts: u32 = v
+        .sandbox
+        .view(v.venear.id(), "get_num_accounts")
+        .await?
+        .json()?;
+
+    let summaries = v
+        .view_all_pages(v.venear.id(), "get_account_summaries", "accounts", 2)
+        .await?;
+    assert_eq!(summaries.len() as u32, num_accounts);
+
+    // The other account list views page through the same accounts.
+    for method_name in ["get_accounts", "get_accounts_raw"] {
+        let accounts = v
+            .view_all_pages(v.venear.id(), method_name, "accounts", 2)
+            .await?;
+        assert_eq!(accounts.len() as u32, num_accounts);
+    }
+
+    for user in &users {
+        let summary = summaries
+            .iter()
+            .find(|summary| summary["account_id"].as_str() == Some(user.id().as_str()))
+            .unwrap();
+        let balance: NearToken = serde_json::from_value(summary["balance"].clone())?;
+        assert_almost_eq(
+            balance,
+            v.ft_balance(user.id()).await?,
+            NearToken::from_millinear(10),
+        );
+    }
+
+    // The page size is capped.
+    let page: serde_json::Value = v
+        .sandbox
+        .view(v.venear.id(), "get_accounts")
+        .args_json(json!({"limit": u32::MAX}))
+        .await?
+        .json()?;
+    assert_eq!(
+        page["accounts"].as_array().unwrap().len() as u32,
+        num_accounts
+    );
+    assert!(page["next_from_index"].is_null());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_top_accounts() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let mut users = vec![];
+    for amount in [15, 20, 10] {
+        let user = v.create_account_with_lockup().await?;
+        v.transfer_and_lock(&user, NearToken::from_near(amount))
+            .await?;
+        users.push(user);
+    }
+
+    let indexed: bool = v
+        .sandbox
+        .view(v.venear.id(), "are_top_accounts_indexed")
+        .await?
+        .json()?;
+    assert!(indexed);
+
+    let top_accounts: Vec<serde_json::Value> = v
+        .sandbox
+        .view(v.venear.id(), "get_top_accounts")
+        .args_json(json!({"limit": 2}))
+        .await?
+        .json()?;
+    assert_eq!(top_accounts.len(), 2);
+    assert_eq!(top_accounts[0]["account_id"], users[1].id().as_str());
+    assert_eq!(top_accounts[1]["account_id"], users[0].id().as_str());
+
+    for (user, expected_rank) in users.iter().zip([2, 1, 3]) {
+        let rank: Option<u32> = v
+            .sandbox
+            .view(v.venear.id(), "get_account_rank")
+            .args_json(json!({"account_id": user.id()}))
+            .await?
+            .json()?;
+        assert_eq!(rank, Some(expected_rank));
+    }
+
+    // The delegated balance moves to the delegate.
+    users[0]
+        .call(v.venear.id(), "delegate_all")
+        .args_json(json!({"receiver_id": users[2].id()}))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?
+        .into_result()?;
+    let rank: Option<u32> = v
+        .sandbox
+        .view(v.venear.id(), "get_account_rank")
+        .args_json(json!({"account_id": users[0].id()}))
+        .await?
+        .json()?;
+    assert_eq!(rank, None);
+    let top_accounts: Vec<serde_json::Value> = v
+        .sandbox
+        .view(v.venear.id(), "get_top_accounts")
+        .args_json(json!({}))
+        .await?
+        .json()?;
+    assert_eq!(top_accounts.len(), 2);
+    assert_eq!(top_accounts[0]["account_id"], users[2].id().as_str());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_epoch_snapshot() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let user = v.create_account_with_lockup().await?;
+    v.transfer_and_lock(&user, NearToken::from_near(10)).await?;
+
+    let at_timestamp = v.sandbox.view_block().await?.timestamp() + 3 * 10u64.pow(9);
+
+    // Only the owner and the snapshot schedulers can schedule snapshots.
+    let outcome = user
+        .call(v.venear.id(), "schedule_snapshot")
+        .args_json(json!({"at_timestamp": at_timestamp.to_string()}))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+
+    let epoch_id: u32 = v
+        .venear_owner
+        .call(v.venear.id(), "schedule_snapshot")
+        .args_json(json!({"at_timestamp": at_timestamp.to_string()}))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?
+        .json()?;
+    assert_eq!(epoch_id, 0);
+
+    let epoch_snapshot: serde_json::Value = v
+        .sandbox
+        .view(v.venear.id(), "get_epoch_snapshot")
+        .args_json(json!({"epoch_id": epoch_id}))
+        .await?
+        .json()?;
+    assert!(epoch_snapshot["snapshot"].is_null());
+
+    v.fast_forward(at_timestamp, 5, 10).await?;
+    // The first update after the timestamp takes the snapshot before the update.
+    v.transfer_and_lock(&user, NearToken::from_near(10)).await?;
+
+    let epoch_snapshot: serde_json::Value = v
+        .sandbox
+        .view(v.venear.id(), "get_epoch_snapshot")
+        .args_json(json!({"epoch_id": epoch_id}))
+        .await?
+        .json()?;
+    let (snapshot, _): (MerkleTreeSnapshot, serde_json::Value) =
+        serde_json::from_value(epoch_snapshot["snapshot"].clone())?;
+    let (proof, v_account): (MerkleProof, VAccount) = v
+        .sandbox
+        .view(v.venear.id(), "get_proof_at")
+        .args_json(json!({
+            "account_id": user.id(),
+            "block_height": snapshot.block_height,
+        }))
+        .await?
+        .json()?;
+    assert!(proof.is_valid(
+        snapshot.format,
+        snapshot.root.into(),
+        snapshot.length,
+        &v_account
+    ));
+    let account: Account = v_account.into();
+    // 10 from lockup + 0.1 from local storage
+    assert_eq!(
+        account.balance.near_balance,
+        NearToken::from_millinear(10100)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fungible_token_weights() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let user = v.create_account_with_lockup().await?;
+    let weight = json!({"numerator": "9", "denominator": "10"});
+
+    // Only the owner can set the weights.
+    let outcome = user
+        .call(v.venear.id(), "set_fungible_token_weight")
+        .args_json(json!({"token_account_id": "lst.near", "weight": weight}))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+
+    // The weight can't exceed 1.
+    let outcome = v
+        .venear_owner
+        .call(v.venear.id(), "set_fungible_token_weight")
+        .args_json(json!({
+            "token_account_id": "lst.near",
+            "weight": {"numerator": "11", "denominator": "10"},
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+
+    let outcome = v
+        .venear_owner
+        .call(v.venear.id(), "set_fungible_token_weight")
+        .args_json(json!({"token_account_id": "lst.near", "weight": weight}))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:#?}", outcome.outcomes());
+
+    let config: serde_json::Value = v.sandbox.view(v.venear.id(), "get_config").await?.json()?;
+    assert_eq!(
+        config["fungible_token_weights"],
+        json!({"lst.near": weight})
+    );
+
+    let outcome = v
+        .venear_owner
+        .call(v.venear.id(), "set_fungible_token_weight")
+        .args_json(json!({"token_account_id": "lst.near", "weight": null}))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:#?}", outcome.outcomes());
+
+    let config: serde_json::Value = v.sandbox.view(v.venear.id(), "get_config").await?.json()?;
+    assert_eq!(config["fungible_token_weights"], json!({}));
+
+    // The lockup updates without fungible tokens are not affected.
+    v.transfer_and_lock(&user, NearToken::from_near(10)).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lock_duration_boost() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let user = v.create_account_with_lockup().await?;
+    let lockup_account_id = v.get_lockup_account_id(user.id()).await?;
+    const DAY_NS: u64 = 24 * 60 * 60 * 10u64.pow(9);
+
+    // Doubling the locked balance for the 4 years commitment.
+    let outcome = v
+        .venear_owner
+        .call(v.venear.id(), "set_lock_duration_boost")
+        .args_json(json!({
+            "lock_duration_boost": {
+                "max_lock_duration_ns": (4 * 365 * DAY_NS).to_string(),
+                "max_boost": {"numerator": "1", "denominator": "1"},
+            },
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:#?}", outcome.outcomes());
+
+    let account_info = v.account_info(user.id()).await?;
+    let default_balance: NearToken =
+        serde_json::from_value(account_info["account"]["balance"]["near_balance"].clone())?;
+
+    let locked_balance = NearToken::from_near(10);
+    v.transfer_and_lock(&user, locked_balance).await?;
+
+    // The commitment shorter than the minimum duration is rejected.
+    let outcome = user
+        .call(&lockup_account_id, "commit_lock")
+        .args_json(json!({"lock_duration_ns": (30 * DAY_NS).to_string()}))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+
+    // The 2 years commitment receives half of the maximum boost.
+    let outcome = user
+        .call(&lockup_account_id, "commit_lock")
+        .args_json(json!({"lock_duration_ns": (2 * 365 * DAY_NS).to_string()}))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:#?}", outcome.outcomes());
+
+    let boost = NearToken::from_near(5);
+    let account_info = v.account_info(user.id()).await?;
+    let balance: NearToken =
+        serde_json::from_value(account_info["account"]["balance"]["near_balance"].clone())?;
+    assert_eq!(balance, near_add(default_balance, locked_balance));
+    // The boost is computed from the remaining duration and decreases to zero at the end.
+    let lock_boost: LockBoost =
+        serde_json::from_value(account_info["account"]["lock_boost"].clone())?;
+    assert_eq!(
+        lock_boost.end_timestamp.0 - lock_boost.start_timestamp.0,
+        2 * 365 * DAY_NS
+    );
+    // The boost is rounded down to a multiple of the number of seconds of the commitment.
+    let num_seconds = (2 * 365 * DAY_NS / 10u64.pow(9)) as u128;
+    assert_eq!(
+        lock_boost.near_balance.as_yoctonear(),
+        boost.as_yoctonear() / num_seconds * num_seconds
+    );
+    assert_eq!(
+        lock_boost.balance(lock_boost.end_timestamp),
+        NearToken::from_near(0)
+    );
+
+    // The boost is included in the total supply.
+    let total_supply: NearToken = v
+        .sandbox
+        .view(v.venear.id(), "ft_total_supply")
+        .args_json(json!({}))
+        .await?
+        .json()?;
+    assert_eq!(total_supply, v.ft_balance(user.id()).await?);
+
+    // The locked NEAR can't be unlocked during the commitment.
+    let outcome = user
+        .call(&lockup_account_id, "begin_unlock_near")
+        .args_json(json!({}))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lockup_owner_transfer() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let user = v.create_account_with_lockup().await?;
+    let lockup_account_id = v.get_lockup_account_id(user.id()).await?;
+    let locked_balance = NearToken::from_near(10);
+    v.transfer_and_lock(&user, locked_balance).await?;
+
+    // The new owner has to be registered without a lockup.
+    let new_owner = v.sandbox.dev_create_account().await?;
+    let outcome = new_owner
+        .call(v.venear.id(), "storage_deposit")
+        .deposit(
+            v.get_config().await?["local_deposit"]
+                .as_str()
+                .unwrap()
+                .parse()?,
+        )
+        .args_json(json!({}))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:#?}", outcome.outcomes());
+    let account_info = v.account_info(new_owner.id()).await?;
+    let new_owner_balance: NearToken =
+        serde_json::from_value(account_info["account"]["balance"]["near_balance"].clone())?;
+
+    let outcome = user
+        .call(&lockup_account_id, "propose_owner")
+        .args_json(json!({"new_owner_account_id": new_owner.id()}))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:#?}", outcome.outcomes());
+
+    // Only the proposed owner can accept the ownership.
+    let outcome = user
+        .call(&lockup_account_id, "accept_owner")
+        .args_json(json!({}))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+
+    let outcome = new_owner
+        .call(&lockup_account_id, "accept_owner")
+        .args_json(json!({}))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:#?}", outcome.outcomes());
+    let transferred: bool = outcome.json()?;
+    assert!(transferred);
+
+    let owner_account_id: AccountId = v
+        .sandbox
+        .view(&lockup_account_id, "get_owner_account_id")
+        .await?
+        .json()?;
+    assert_eq!(owner_account_id, *new_owner.id());
+    assert_eq!(
+        v.get_lockup_account_id(new_owner.id()).await?,
+        lockup_account_id
+    );
+
+    // The locked balance moved to the new owner.
+    let account_info = v.account_info(new_owner.id()).await?;
+    let balance: NearToken =
+        serde_json::from_value(account_info["account"]["balance"]["near_balance"].clone())?;
+    assert_eq!(balance, near_add(new_owner_balance, locked_balance));
+    assert_eq!(account_info["internal"]["lockup_version"].as_u64(), Some(1));
+
+    let account_info = v.account_info(user.id()).await?;
+    assert!(account_info["internal"]["lockup_version"].is_null());
+    let balance: NearToken =
+        serde_json::from_value(account_info["account"]["balance"]["near_balance"].clone())?;
+    let deposit: NearToken = serde_json::from_value(account_info["internal"]["deposit"].clone())?;
+    assert_eq!(balance, deposit);
+
+    // The lockup updates from the transferred lockup are accepted for the new owner.
+    v.transfer_and_lock(&new_owner, NearToken::from_near(5))
+        .await?;
+    let account_info = v.account_info(new_owner.id()).await?;
+    let balance: NearToken =
+        serde_json::from_value(account_info["account"]["balance"]["near_balance"].clone())?;
+    assert_eq!(
+        balance,
+        near_add(
+            near_add(new_owner_balance, locked_balance),
+            NearToken::from_near(5)
+        )
+    );
+
+    // The previous owner can deploy a new lockup that doesn't collide with the transferred one.
+    let new_lockup_account_id = v.get_lockup_account_id(user.id()).await?;
+    assert_ne!(new_lockup_account_id, lockup_account_id);
+    let lockup_cost: NearToken = v
+        .sandbox
+        .view(v.venear.id(), "get_lockup_deployment_cost")
+        .await?
+        .json()?;
+    let outcome = user
+        .call(v.venear.id(), "deploy_lockup")
+        .deposit(lockup_cost)
+        .args_json(json!({}))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:#?}", outcome.outcomes());
+    let deployed_lockup_account_id: Option<AccountId> = outcome.json()?;
+    assert_eq!(deployed_lockup_account_id, Some(new_lockup_account_id));
+    let account_info = v.account_info(user.id()).await?;
+    assert_eq!(account_info["internal"]["lockup_version"].as_u64(), Some(1));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lockup_migration() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default().build().await?;
+    let user = v.create_account_with_lockup().await?;
+    let lockup_account_id = v.get_lockup_account_id(user.id()).await?;
+    let locked_balance = NearToken::from_near(1000);
+    v.transfer_and_lock(&user, locked_balance).await?;
+
+    let lock_timestamp = v.sandbox.view_block().await?.timestamp();
+    v.fast_forward(lock_timestamp + 10 * 10u64.pow(9), 10, 20)
+        .await?;
+
+    // The same lockup code is set again as the new version of the lockup contract.
+    let config = v.get_config().await?;
+    let outcome = v
+        .venear_owner
+        .call(v.venear.id(), "set_lockup_contract")
+        .args_json(json!({
+            "contract_hash": config["lockup_contract_config"]["contract_hash"],
+            "min_lockup_deposit": config["min_lockup_deposit"],
+        }))
+        .deposit(NearToken::from_yoctonear(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:#?}", outcome.outcomes());
+
+    let lockup_cost: NearToken = v
+        .sandbox
+        .view(v.venear.id(), "get_lockup_deployment_cost")
+        .await?
+        .json()?;
+    let outcome = user
+        .call(v.venear.id(), "deploy_migration_lockup")
+        .args_json(json!({}))
+        .deposit(lockup_cost)
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:#?}", outcome.outcomes());
+    let new_lockup_account_id: Option<AccountId> = outcome.json()?;
+    let new_lockup_account_id = new_lockup_account_id.unwrap();
+    assert_ne!(new_lockup_account_id, lockup_account_id);
+
+    // The lockup contract doesn't change until the migration.
+    assert_eq!(v.get_lockup_account_id(user.id()).await?, lockup_account_id);
+    let account_info = v.account_info(user.id()).await?;
+    assert_eq!(
+        account_info["internal"]["lockup_migration"]["lockup_version"].as_u64(),
+        Some(2)
+    );
+    let balance_before: VenearBalance =
+        serde_json::from_value(account_info["account"]["balance"].clone())?;
+    assert!(!balance_before.extra_venear_balance.is_zero());
+
+    let outcome = user
+        .call(&lockup_account_id, "migrate_lockup")
+        .args_json(json!({"new_lockup_account_id": new_lockup_account_id}))
+        .deposit(NearToken::from_yoctonear(1))
+        .gas(Gas::from_tgas(200))
+        .transact()
+        .await?;
+    assert!(outcome.is_success(), "{:#?}", outcome.outcomes());
+    let migrated: bool = outcome.json()?;
+    assert!(migrated);
+
+    assert_eq!(
+        v.get_lockup_account_id(user.id()).await?,
+        new_lockup_account_id
+    );
+    let new_locked_balance: NearToken = v
+        .sandbox
+        .view(&new_lockup_account_id, "get_venear_locked_balance")
+        .await?
+        .json()?;
+    assert_eq!(new_locked_balance, locked_balance);
+
+    // The extra veNEAR is preserved by the migration.
+    let account_info = v.account_info(user.id()).await?;
+    assert_eq!(account_info["internal"]["lockup_version"].as_u64(), Some(2));
+    assert!(account_info["internal"]["lockup_migration"].is_null());
+    let balance: VenearBalance =
+        serde_json::from_value(account_info["account"]["balance"].clone())?;
+    assert_eq!(balance.near_balance, balance_before.near_balance);
+    assert!(balance.extra_venear_balance >= balance_before.extra_venear_balance);
+
+    let migration_lockup_account_id: Option<AccountId> = v
+        .sandbox
+        .view(&lockup_account_id, "get_migration_lockup_account_id")
+        .await?
+        .json()?;
+    assert_eq!(migration_lockup_account_id, Some(new_lockup_account_id));
+
+    Ok(())
+}
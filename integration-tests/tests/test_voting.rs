@@ -394,6 +394,53 @@ async fn test_voting_reject_proposal() -> Result<(), Box<dyn std::error::Error>>
     Ok(())
 }
 
+#[tokio::test]
+async fn test_proposals_pagination() -> Result<(), Box<dyn std::error::Error>> {
+    let v = VenearTestWorkspaceBuilder::default()
+        .with_voting()
+        .build()
+        .await?;
+    let user_a = v.create_account_with_lockup().await?;
+
+    let mut proposal_ids = vec![];
+    for _ in 0..5 {
+        proposal_ids.push(create_proposal(&v, &user_a).await?);
+    }
+    for &proposal_id in &proposal_ids[..3] {
+        approve_proposal(&v, &v.voting.as_ref().unwrap().reviewer, proposal_id).await?;
+    }
+
+    let proposals = v
+        .view_all_pages(v.voting_id(), "get_proposals", "proposals", 2)
+        .await?;
+    let ids: Vec<u64> = proposals
+        .iter()
+        .map(|proposal| proposal["id"].as_u64().unwrap())
+        .collect();
+    assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+
+    let approved_proposals = v
+        .view_all_pages(v.voting_id(), "get_approved_proposals", "proposals", 2)
+        .await?;
+    let ids: Vec<u64> = approved_proposals
+        .iter()
+        .map(|proposal| proposal["id"].as_u64().unwrap())
+        .collect();
+    assert_eq!(ids, vec![0, 1, 2]);
+
+    // The page past the end is empty and has no next page.
+    let page: serde_json::Value = v
+        .sandbox
+        .view(v.voting_id(), "get_proposals")
+        .args_json(json!({"from_index": 5}))
+        .await?
+        .json()?;
+    assert!(page["proposals"].as_array().unwrap().is_empty());
+    assert!(page["next_from_index"].is_null());
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_voting_governance() -> Result<(), Box<dyn std::error::Error>> {
     let v = VenearTestWorkspaceBuilder::default()
@@ -54,6 +54,28 @@ pub mod staking_pool {
     pub const GET_ACCOUNT_TOTAL_BALANCE: Gas = super::BASE_GAS;
 }
 
+pub mod liquid_staking_token {
+    use near_sdk::Gas;
+
+    /// The amount of gas required to get the current price of the liquid staking token.
+    /// Requires BASE for local processing.
+    pub const GET_PRICE: Gas = super::BASE_GAS;
+
+    /// Gas attached to the fungible token transfer call on the liquid staking token contract.
+    /// Requires BASE for local updates.
+    pub const FT_TRANSFER: Gas = super::BASE_GAS;
+}
+
+pub mod lockup {
+    use near_sdk::Gas;
+
+    /// Gas attached to the call on the new lockup contract to accept the migrated locked NEAR.
+    /// Requires BASE for local updates + gas for the veNEAR lockup update.
+    pub const ACCEPT_LOCKUP_MIGRATION: Gas = Gas::from_gas(
+        super::BASE_GAS.as_gas() + crate::venear_ext::GAS_FOR_VENEAR_LOCKUP_UPDATE.as_gas(),
+    );
+}
+
 pub mod owner_callbacks {
     use near_sdk::Gas;
 
@@ -100,6 +122,40 @@ pub mod owner_callbacks {
     /// Requires BASE for local updates.
     pub const ON_GET_ACCOUNT_TOTAL_BALANCE: Gas = super::BASE_GAS;
 
+    /// Gas attached to the inner callback for processing result of the call to get the current
+    /// price of the liquid staking token.
+    /// Requires BASE for local updates + gas for the veNEAR lockup update.
+    pub const ON_GET_LIQUID_STAKING_TOKEN_PRICE: Gas = Gas::from_gas(
+        super::BASE_GAS.as_gas() + crate::venear_ext::GAS_FOR_VENEAR_LOCKUP_UPDATE.as_gas(),
+    );
+
+    /// Gas attached to the inner callback for processing result of the fungible token transfer
+    /// call to the liquid staking token.
+    /// Requires BASE for local updates.
+    pub const ON_LIQUID_STAKING_TOKEN_TRANSFER: Gas = super::BASE_GAS;
+
+    /// Gas attached to the inner callback for processing result of the ownership transfer call
+    /// to the veNEAR contract.
+    /// Requires BASE for local updates.
+    pub const ON_LOCKUP_OWNER_TRANSFER: Gas = super::BASE_GAS;
+
+    /// Gas attached to the inner callback for processing result of the migration call to the
+    /// veNEAR contract.
+    /// Requires BASE for local updates + gas for the call to the new lockup contract + gas for
+    /// another callback.
+    pub const ON_LOCKUP_MIGRATION: Gas = Gas::from_gas(
+        super::BASE_GAS.as_gas()
+            + super::lockup::ACCEPT_LOCKUP_MIGRATION.as_gas()
+            + ON_ACCEPT_LOCKUP_MIGRATION.as_gas(),
+    );
+
+    /// Gas attached to the inner callback for processing result of the call to the new lockup
+    /// contract to accept the migration.
+    /// Requires BASE for local updates + gas for the veNEAR rollback call.
+    pub const ON_ACCEPT_LOCKUP_MIGRATION: Gas = Gas::from_gas(
+        super::BASE_GAS.as_gas() + crate::venear_ext::GAS_FOR_VENEAR_LOCKUP_MIGRATION.as_gas(),
+    );
+
     /// Gas attached to the inner callback for processing result of the call to get the current
     /// unstaked balance from the staking pool.
     /// The callback might proceed with withdrawing this amount.
@@ -109,4 +165,23 @@ pub mod owner_callbacks {
             + super::staking_pool::WITHDRAW.as_gas()
             + ON_STAKING_POOL_WITHDRAW.as_gas(),
     );
+
+    /// Gas attached to the inner callback for processing result of the call to get the current
+    /// total balance from the staking pool before locking the NEAR.
+    /// Requires BASE for local updates + gas for the veNEAR lockup update.
+    pub const ON_GET_ACCOUNT_TOTAL_BALANCE_TO_LOCK: Gas = Gas::from_gas(
+        super::BASE_GAS.as_gas() + crate::venear_ext::GAS_FOR_VENEAR_LOCKUP_UPDATE.as_gas(),
+    );
+
+    /// Gas attached to the inner callback for processing result of the call to get the current
+    /// total balance from the staking pool before unlocking the NEAR.
+    /// Requires BASE for local updates + gas for the veNEAR lockup update.
+    pub const ON_GET_ACCOUNT_TOTAL_BALANCE_TO_BEGIN_UNLOCK: Gas =
+        ON_GET_ACCOUNT_TOTAL_BALANCE_TO_LOCK;
+
+    /// Gas attached to the inner callback for processing result of the call to get the current
+    /// total balance from the staking pool to lock the staking rewards.
+    /// Requires BASE for local updates + gas for the veNEAR lockup update.
+    pub const ON_GET_ACCOUNT_TOTAL_BALANCE_TO_SYNC_REWARDS: Gas =
+        ON_GET_ACCOUNT_TOTAL_BALANCE_TO_LOCK;
 }
@@ -1,4 +1,7 @@
 use crate::*;
+use common::lockup_update::FungibleTokenBalance;
+use common::TimestampNs;
+use near_sdk::json_types::U64;
 use near_sdk::near;
 
 #[near]
@@ -8,22 +11,73 @@ impl LockupContract {
         self.owner_account_id.clone()
     }
 
-    /// Returns the account ID of the selected staking pool.
-    pub fn get_staking_pool_account_id(&self) -> Option<AccountId> {
-        self.staking_information
-            .as_ref()
-            .map(|info| info.staking_pool_account_id.clone())
+    /// Returns the account ID of the proposed new owner.
+    pub fn get_proposed_owner_account_id(&self) -> Option<AccountId> {
+        self.proposed_owner_account_id.clone()
     }
 
-    /// Returns the amount of tokens that were deposited to the staking pool.
+    /// Returns the timestamp in nanoseconds when the proposed new owner can accept the
+    /// ownership, or 0 if there is no proposed new owner.
+    pub fn get_owner_transfer_timestamp(&self) -> TimestampNs {
+        self.owner_transfer_timestamp.into()
+    }
+
+    /// Returns the time in nanoseconds between proposing the new owner and accepting the
+    /// ownership.
+    pub fn get_owner_transfer_delay_ns(&self) -> U64 {
+        self.owner_transfer_delay_ns.into()
+    }
+
+    /// Returns the account ID of the new lockup contract this lockup contract migrates or
+    /// migrated to.
+    pub fn get_migration_lockup_account_id(&self) -> Option<AccountId> {
+        self.migration_lockup_account_id.clone()
+    }
+
+    /// Returns the account IDs of the selected staking pools.
+    pub fn get_staking_pool_account_ids(&self) -> Vec<AccountId> {
+        self.staking_pools.keys().cloned().collect()
+    }
+
+    /// Returns the amount of tokens that were deposited to the given staking pool, or `None` if
+    /// the staking pool is not selected.
+    pub fn get_staking_pool_deposited_balance(
+        &self,
+        staking_pool_account_id: AccountId,
+    ) -> Option<NearToken> {
+        self.staking_pools
+            .get(&staking_pool_account_id)
+            .map(|info| info.deposit_amount)
+    }
+
+    /// Returns the amount of tokens that were deposited to all staking pools.
     /// NOTE: The actual balance can be larger than this known deposit balance due to staking
-    /// rewards acquired on the staking pool.
+    /// rewards acquired on the staking pools.
     /// To refresh the amount the owner can call `refresh_staking_pool_balance`.
     pub fn get_known_deposited_balance(&self) -> NearToken {
-        self.staking_information
-            .as_ref()
-            .map(|info| info.deposit_amount)
-            .unwrap_or(NearToken::from_yoctonear(0))
+        NearToken::from_yoctonear(
+            self.staking_pools
+                .values()
+                .map(|info| info.deposit_amount.as_yoctonear())
+                .sum(),
+        )
+    }
+
+    /// Returns the balances of the selected liquid staking tokens with their NEAR value at the
+    /// last known price.
+    pub fn get_liquid_staking_tokens(&self) -> Vec<FungibleTokenBalance> {
+        self.internal_get_fungible_token_balances()
+    }
+
+    /// Returns the NEAR value of all liquid staking tokens at the last known prices.
+    /// To refresh the price of a token call `refresh_liquid_staking_token_price`.
+    pub fn get_liquid_staking_tokens_near_balance(&self) -> NearToken {
+        NearToken::from_yoctonear(
+            self.liquid_staking_tokens
+                .values()
+                .map(|lst_information| lst_information.near_value(lst_information.balance))
+                .sum(),
+        )
     }
 
     /// Returns the balance of the account owner.
@@ -32,7 +86,7 @@ impl LockupContract {
         self.get_balance()
     }
 
-    /// Returns total balance of the account including tokens deposited to the staking pool.
+    /// Returns total balance of the account including tokens deposited to the staking pools.
     pub fn get_balance(&self) -> NearToken {
         NearToken::from_yoctonear(
             env::account_balance().as_yoctonear()
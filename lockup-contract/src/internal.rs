@@ -1,5 +1,6 @@
 use crate::*;
-use near_sdk::NearToken;
+use common::lockup_update::FungibleTokenBalance;
+use near_sdk::{NearToken, Promise};
 
 /********************/
 /* Internal methods */
@@ -16,15 +17,47 @@ impl LockupContract {
         )
     }
 
-    pub fn set_staking_pool_status(&mut self, status: TransactionStatus) {
-        self.staking_information
-            .as_mut()
+    pub fn internal_get_staking_pool_mut(
+        &mut self,
+        staking_pool_account_id: &AccountId,
+    ) -> &mut StakingInformation {
+        self.staking_pools
+            .get_mut(staking_pool_account_id)
             .expect("Staking pool should be selected")
+    }
+
+    pub fn set_staking_pool_status(
+        &mut self,
+        staking_pool_account_id: &AccountId,
+        status: TransactionStatus,
+    ) {
+        self.internal_get_staking_pool_mut(staking_pool_account_id)
             .status = status;
     }
 
+    /// Requests the total balance of this account from the given staking pool. The staking pool
+    /// is busy until the callback.
+    pub fn internal_get_staking_pool_total_balance(
+        &mut self,
+        staking_pool_account_id: &AccountId,
+    ) -> Promise {
+        self.assert_staking_pool_is_idle(staking_pool_account_id);
+
+        env::log_str(&format!(
+            "Fetching total balance from the staking pool @{}",
+            staking_pool_account_id
+        ));
+
+        self.set_staking_pool_status(staking_pool_account_id, TransactionStatus::Busy);
+
+        ext_staking_pool::ext(staking_pool_account_id.clone())
+            .with_static_gas(gas::staking_pool::GET_ACCOUNT_TOTAL_BALANCE)
+            .get_account_total_balance(env::current_account_id())
+    }
+
+    /// Asserts that there are no transactions in progress with any of the staking pools.
     pub fn assert_no_staking_or_idle(&self) {
-        if let Some(staking_information) = &self.staking_information {
+        for staking_information in self.staking_pools.values() {
             match staking_information.status {
                 TransactionStatus::Idle => (),
                 TransactionStatus::Busy => {
@@ -34,12 +67,57 @@ impl LockupContract {
         }
     }
 
-    pub fn assert_staking_pool_is_idle(&self) {
+    pub fn assert_staking_pool_is_idle(&self, staking_pool_account_id: &AccountId) {
+        let staking_information = self
+            .staking_pools
+            .get(staking_pool_account_id)
+            .expect("Staking pool is not selected");
+        match staking_information.status {
+            TransactionStatus::Idle => (),
+            TransactionStatus::Busy => {
+                env::panic_str("Contract is currently busy with another operation")
+            }
+        };
+    }
+
+    pub fn assert_staking_pool_is_not_selected(&self, staking_pool_account_id: &AccountId) {
+        assert!(
+            !self.staking_pools.contains_key(staking_pool_account_id),
+            "Staking pool is already selected"
+        );
+    }
+
+    pub fn assert_can_select_staking_pool(&self) {
         assert!(
-            self.staking_information.is_some(),
-            "Staking pool is not selected"
+            self.staking_pools.len() < MAX_STAKING_POOLS,
+            "Too many staking pools are selected"
         );
-        match self.staking_information.as_ref().unwrap().status {
+    }
+
+    pub fn internal_get_liquid_staking_token_mut(
+        &mut self,
+        token_account_id: &AccountId,
+    ) -> &mut LiquidStakingTokenInformation {
+        self.liquid_staking_tokens
+            .get_mut(token_account_id)
+            .expect("Liquid staking token is not selected")
+    }
+
+    pub fn set_liquid_staking_token_status(
+        &mut self,
+        token_account_id: &AccountId,
+        status: TransactionStatus,
+    ) {
+        self.internal_get_liquid_staking_token_mut(token_account_id)
+            .status = status;
+    }
+
+    pub fn assert_liquid_staking_token_is_idle(&self, token_account_id: &AccountId) {
+        let lst_information = self
+            .liquid_staking_tokens
+            .get(token_account_id)
+            .expect("Liquid staking token is not selected");
+        match lst_information.status {
             TransactionStatus::Idle => (),
             TransactionStatus::Busy => {
                 env::panic_str("Contract is currently busy with another operation")
@@ -47,10 +125,38 @@ impl LockupContract {
         };
     }
 
-    pub fn assert_staking_pool_is_not_selected(&self) {
+    pub fn assert_liquid_staking_token_is_not_selected(&self, token_account_id: &AccountId) {
         assert!(
-            self.staking_information.is_none(),
-            "Staking pool is already selected"
+            !self.liquid_staking_tokens.contains_key(token_account_id),
+            "Liquid staking token is already selected"
+        );
+    }
+
+    pub fn assert_can_select_liquid_staking_token(&self) {
+        assert!(
+            self.liquid_staking_tokens.len() < MAX_LIQUID_STAKING_TOKENS,
+            "Too many liquid staking tokens are selected"
+        );
+    }
+
+    /// Returns the balances of the selected liquid staking tokens with their NEAR value.
+    pub fn internal_get_fungible_token_balances(&self) -> Vec<FungibleTokenBalance> {
+        self.liquid_staking_tokens
+            .iter()
+            .map(|(token_account_id, lst_information)| FungibleTokenBalance {
+                token_account_id: token_account_id.clone(),
+                balance: lst_information.balance.into(),
+                near_balance: NearToken::from_yoctonear(
+                    lst_information.near_value(lst_information.balance),
+                ),
+            })
+            .collect()
+    }
+
+    pub fn assert_not_migrated(&self) {
+        assert!(
+            self.migration_lockup_account_id.is_none(),
+            "The lockup contract is migrated"
         );
     }
 
@@ -62,3 +168,10 @@ impl LockupContract {
         )
     }
 }
+
+impl LiquidStakingTokenInformation {
+    /// Returns the value of the given amount of tokens in yoctoNEAR at the last known price.
+    pub fn near_value(&self, amount: Balance) -> Balance {
+        (U256::from(amount) * U256::from(self.price) / U256::from(LST_PRICE_DENOMINATOR)).as_u128()
+    }
+}
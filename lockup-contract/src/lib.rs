@@ -1,9 +1,10 @@
 //! A smart contract that allows tokens to be locked up.
 
 pub use crate::types::*;
-use near_sdk::json_types::U64;
+use near_sdk::json_types::{U128, U64};
 use near_sdk::{env, ext_contract, near, require, AccountId, PanicOnDefault};
 use near_sdk::{Gas, NearToken};
+use std::collections::BTreeMap;
 
 pub mod gas;
 pub mod owner_callbacks;
@@ -18,6 +19,24 @@ pub mod venear_ext;
 
 pub type Version = u64;
 
+/// The maximum number of staking pools that can be selected at the same time.
+pub const MAX_STAKING_POOLS: usize = 8;
+
+/// The maximum number of liquid staking tokens that can be selected at the same time.
+pub const MAX_LIQUID_STAKING_TOKENS: usize = 4;
+
+/// The maximum number of the pending unlock tranches.
+pub const MAX_UNLOCK_TRANCHES: usize = 32;
+
+/// The minimum duration in nanoseconds of the lock commitment (90 days).
+pub const MIN_LOCK_DURATION_NS: Duration = 90 * 24 * 60 * 60 * 10u64.pow(9);
+
+/// The maximum duration in nanoseconds of the lock commitment (4 years).
+pub const MAX_LOCK_DURATION_NS: Duration = 4 * 365 * 24 * 60 * 60 * 10u64.pow(9);
+
+/// The denominator of the liquid staking token price, i.e. the price is given for 10^24 units.
+pub const LST_PRICE_DENOMINATOR: Balance = 10u128.pow(24);
+
 #[ext_contract(ext_staking_pool)]
 pub trait ExtStakingPool {
     fn get_account_staked_balance(&self, account_id: AccountId) -> NearToken;
@@ -39,6 +58,26 @@ pub trait ExtStakingPool {
     fn unstake_all(&mut self);
 }
 
+#[ext_contract(ext_liquid_staking_token)]
+pub trait ExtLiquidStakingToken {
+    fn ft_price(&self) -> U128;
+
+    fn get_st_near_price(&self) -> U128;
+
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+#[ext_contract(ext_lockup)]
+pub trait ExtLockupContract {
+    fn accept_lockup_migration(
+        &mut self,
+        locked_balance: NearToken,
+        unlock_tranches: Vec<UnlockTranche>,
+        lock_duration_ns: U64,
+        lock_end_timestamp: U64,
+    );
+}
+
 #[ext_contract(ext_whitelist)]
 pub trait ExtStakingPoolWhitelist {
     fn is_whitelisted(&self, staking_pool_account_id: AccountId) -> bool;
@@ -52,24 +91,99 @@ pub trait ExtLockupContractOwner {
         staking_pool_account_id: AccountId,
     ) -> bool;
 
-    fn on_staking_pool_deposit(&mut self, amount: NearToken) -> bool;
+    fn on_staking_pool_deposit(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> bool;
 
-    fn on_staking_pool_deposit_and_stake(&mut self, amount: NearToken) -> bool;
+    fn on_staking_pool_deposit_and_stake(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> bool;
 
-    fn on_staking_pool_withdraw(&mut self, amount: NearToken) -> bool;
+    fn on_staking_pool_withdraw(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> bool;
 
-    fn on_staking_pool_stake(&mut self, amount: NearToken) -> bool;
+    fn on_staking_pool_stake(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> bool;
 
-    fn on_staking_pool_unstake(&mut self, amount: NearToken) -> bool;
+    fn on_staking_pool_unstake(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> bool;
 
-    fn on_staking_pool_unstake_all(&mut self) -> bool;
+    fn on_staking_pool_unstake_all(&mut self, staking_pool_account_id: AccountId) -> bool;
 
-    fn on_get_account_total_balance(&mut self, #[callback] total_balance: NearToken);
+    fn on_get_account_total_balance(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        #[callback] total_balance: NearToken,
+    );
 
     fn on_get_account_unstaked_balance_to_withdraw_by_owner(
         &mut self,
+        staking_pool_account_id: AccountId,
         #[callback] unstaked_balance: NearToken,
     );
+
+    fn on_get_account_total_balance_to_lock(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: Option<NearToken>,
+    ) -> bool;
+
+    fn on_get_account_total_balance_to_begin_unlock(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: Option<NearToken>,
+    ) -> bool;
+
+    fn on_get_account_total_balance_to_sync_rewards(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        deposit_amount: NearToken,
+    ) -> NearToken;
+
+    fn on_whitelist_is_whitelisted_lst(
+        &mut self,
+        #[callback] is_whitelisted: bool,
+        token_account_id: AccountId,
+        price_method: LstPriceMethod,
+    ) -> bool;
+
+    fn on_get_liquid_staking_token_price(
+        &mut self,
+        token_account_id: AccountId,
+        #[callback] price: U128,
+    );
+
+    fn on_liquid_staking_token_transfer(
+        &mut self,
+        token_account_id: AccountId,
+        amount: U128,
+    ) -> bool;
+
+    fn on_lockup_owner_transfer(&mut self, new_owner_account_id: AccountId) -> bool;
+
+    fn on_lockup_migration(&mut self, new_lockup_account_id: AccountId) -> bool;
+
+    fn on_accept_lockup_migration(
+        &mut self,
+        new_lockup_account_id: AccountId,
+        locked_balance: NearToken,
+        unlock_tranches: Vec<UnlockTranche>,
+        lock_duration_ns: U64,
+        lock_end_timestamp: U64,
+    ) -> bool;
 }
 
 #[near(contract_state)]
@@ -78,16 +192,27 @@ pub struct LockupContract {
     /// The account ID of the owner.
     pub owner_account_id: AccountId,
 
+    /// The account ID of the proposed new owner. The account has to accept the ownership after
+    /// the transfer delay.
+    pub proposed_owner_account_id: Option<AccountId>,
+
+    /// The timestamp in nanoseconds when the proposed new owner can accept the ownership.
+    pub owner_transfer_timestamp: Timestamp,
+
+    /// The time in nanoseconds between proposing the new owner and accepting the ownership.
+    pub owner_transfer_delay_ns: Duration,
+
     /// Account Id of VeNEAR Contract
     pub venear_account_id: AccountId,
 
     /// Account ID of the staking pool whitelist contract.
     pub staking_pool_whitelist_account_id: AccountId,
 
-    /// Information about staking and delegation.
-    /// `Some` means the staking information is available and the staking pool contract is selected.
-    /// `None` means there is no staking pool selected.
-    pub staking_information: Option<StakingInformation>,
+    /// Information about staking per selected staking pool contract.
+    pub staking_pools: BTreeMap<AccountId, StakingInformation>,
+
+    /// Information about the balances per selected liquid staking token contract.
+    pub liquid_staking_tokens: BTreeMap<AccountId, LiquidStakingTokenInformation>,
 
     /// The time in nanoseconds for unlocking the lockup amount.
     pub unlock_duration_ns: u64,
@@ -95,11 +220,15 @@ pub struct LockupContract {
     /// Locked amount
     pub venear_locked_balance: Balance,
 
-    /// Timestamp to unlock
-    pub venear_unlock_timestamp: Timestamp,
+    /// Pending unlocking amounts ordered by the unlock timestamp
+    pub unlock_tranches: Vec<UnlockTranche>,
+
+    /// The duration in nanoseconds of the lock commitment, or 0 if there is no commitment.
+    pub lock_duration_ns: Duration,
 
-    /// Pending unlocking amount
-    pub venear_pending_balance: Balance,
+    /// The timestamp in nanoseconds when the lock commitment ends. The locked NEAR can't be
+    /// unlocked before it.
+    pub lock_end_timestamp: Timestamp,
 
     /// The nonce of the lockup update. It should be incremented for every new update by the lockup
     /// contract.
@@ -110,6 +239,16 @@ pub struct LockupContract {
 
     /// The minimum amount in NEAR required for lockup deployment.
     pub min_lockup_deposit: NearToken,
+
+    /// Whether the staking rewards are locked automatically by `sync_rewards`.
+    pub auto_lock_rewards: bool,
+
+    /// The account ID of the lockup contract that migrates to this lockup contract. It can
+    /// transfer its locked NEAR to this lockup contract once.
+    pub previous_lockup_account_id: Option<AccountId>,
+
+    /// The account ID of the new lockup contract this lockup contract migrates or migrated to.
+    pub migration_lockup_account_id: Option<AccountId>,
 }
 
 #[near]
@@ -127,6 +266,10 @@ impl LockupContract {
     /// - `lockup_update_nonce` - The nonce of the lockup update. It should be incremented for every
     ///   new update by the lockup contract.
     /// - `min_lockup_deposit` - The minimum amount in NEAR required for lockup deployment.
+    /// - `owner_transfer_delay_ns` - The time in nanoseconds between proposing the new owner and
+    ///   accepting the ownership.
+    /// - `previous_lockup_account_id` - The account ID of the lockup contract that migrates to
+    ///   this lockup contract, if it's deployed for the migration.
     #[payable]
     #[init]
     pub fn new(
@@ -137,6 +280,8 @@ impl LockupContract {
         version: Version,
         lockup_update_nonce: U64,
         min_lockup_deposit: NearToken,
+        owner_transfer_delay_ns: U64,
+        previous_lockup_account_id: Option<AccountId>,
     ) -> Self {
         require!(
             env::account_balance() >= min_lockup_deposit,
@@ -144,16 +289,24 @@ impl LockupContract {
         );
         Self {
             owner_account_id,
+            proposed_owner_account_id: None,
+            owner_transfer_timestamp: 0,
+            owner_transfer_delay_ns: owner_transfer_delay_ns.into(),
             venear_account_id,
-            staking_information: None,
+            staking_pools: BTreeMap::new(),
+            liquid_staking_tokens: BTreeMap::new(),
             staking_pool_whitelist_account_id,
             unlock_duration_ns: unlock_duration_ns.into(),
             venear_locked_balance: 0,
-            venear_unlock_timestamp: 0u64,
-            venear_pending_balance: 0,
+            unlock_tranches: vec![],
+            lock_duration_ns: 0,
+            lock_end_timestamp: 0,
             lockup_update_nonce: lockup_update_nonce.into(),
             version,
             min_lockup_deposit,
+            auto_lock_rewards: false,
+            previous_lockup_account_id,
+            migration_lockup_account_id: None,
         }
     }
 }
@@ -161,7 +314,7 @@ impl LockupContract {
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(test)]
 mod tests {
-    use near_sdk::{testing_env, AccountId, NearToken, VMContext};
+    use near_sdk::{testing_env, AccountId, NearToken, PromiseError, VMContext};
     use std::convert::TryInto;
     use std::str::FromStr;
     use test_utils::*;
@@ -175,6 +328,7 @@ mod tests {
     const LOCKUP_VERSION: Version = 1;
     const UNLOCK_DURATION_NS: u64 = 90u64 * 24 * 60 * 60 * 10u64.pow(9);
     const MIN_LOCKUP_DEPOSIT: NearToken = NearToken::from_millinear(2000);
+    const OWNER_TRANSFER_DELAY_NS: u64 = 7u64 * 24 * 60 * 60 * 10u64.pow(9);
 
     fn basic_context() -> VMContext {
         get_context(
@@ -197,6 +351,8 @@ mod tests {
             LOCKUP_VERSION,
             0.into(),
             MIN_LOCKUP_DEPOSIT,
+            OWNER_TRANSFER_DELAY_NS.into(),
+            None,
         );
 
         (context, contract)
@@ -267,9 +423,10 @@ mod tests {
         context.signer_account_pk = public_key(2).try_into().unwrap();
         context.attached_deposit = NearToken::from_yoctonear(1);
 
+        let staking_pool: AccountId = AccountId::from_str("staking_pool").unwrap();
         let amount = to_yocto(LOCKUP_NEAR - 100);
         testing_env!(context.clone());
-        contract.deposit_to_staking_pool(NearToken::from_yoctonear(amount));
+        contract.deposit_to_staking_pool(staking_pool.clone(), NearToken::from_yoctonear(amount));
     }
 
     #[test]
@@ -290,7 +447,10 @@ mod tests {
 
         // context = clone_context(context.clone(), true);
         testing_env!(context.clone());
-        assert_eq!(contract.get_staking_pool_account_id(), Some(staking_pool));
+        assert_eq!(
+            contract.get_staking_pool_account_ids(),
+            vec![staking_pool.clone()]
+        );
         assert_eq!(contract.get_known_deposited_balance().as_yoctonear(), 0);
         // context = clone_context(context.clone(), false);
 
@@ -299,7 +459,7 @@ mod tests {
         context.account_balance = env::account_balance();
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.deposit_to_staking_pool(NearToken::from_yoctonear(amount));
+        contract.deposit_to_staking_pool(staking_pool.clone(), NearToken::from_yoctonear(amount));
         context.account_balance = env::account_balance();
         assert_almost_eq(
             context.account_balance.as_yoctonear(),
@@ -307,7 +467,11 @@ mod tests {
         );
 
         context.predecessor_account_id = lockup_account();
-        contract.on_staking_pool_deposit_inner(NearToken::from_yoctonear(amount), true);
+        contract.on_staking_pool_deposit_inner(
+            &staking_pool,
+            NearToken::from_yoctonear(amount),
+            true,
+        );
         // context = clone_context(context.clone(), true);
         testing_env!(context.clone());
         assert_eq!(
@@ -318,37 +482,55 @@ mod tests {
         // Staking on the staking pool
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.stake(NearToken::from_yoctonear(amount));
+        contract.stake(staking_pool.clone(), NearToken::from_yoctonear(amount));
 
         context.predecessor_account_id = lockup_account();
-        contract.on_staking_pool_stake_inner(NearToken::from_yoctonear(amount), true);
+        contract.on_staking_pool_stake_inner(
+            &staking_pool,
+            NearToken::from_yoctonear(amount),
+            true,
+        );
 
         // Assuming there are 20 NEAR tokens in rewards. Unstaking.
         let unstake_amount = amount + to_yocto(20);
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.unstake(NearToken::from_yoctonear(unstake_amount));
+        contract.unstake(
+            staking_pool.clone(),
+            NearToken::from_yoctonear(unstake_amount),
+        );
 
         context.predecessor_account_id = lockup_account();
-        contract.on_staking_pool_unstake_inner(NearToken::from_yoctonear(unstake_amount), true);
+        contract.on_staking_pool_unstake_inner(
+            &staking_pool,
+            NearToken::from_yoctonear(unstake_amount),
+            true,
+        );
 
         // Withdrawing
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.withdraw_from_staking_pool(NearToken::from_yoctonear(unstake_amount));
+        contract.withdraw_from_staking_pool(
+            staking_pool.clone(),
+            NearToken::from_yoctonear(unstake_amount),
+        );
         context.account_balance =
             NearToken::from_yoctonear(context.account_balance.as_yoctonear() + unstake_amount);
 
         context.predecessor_account_id = lockup_account();
-        contract.on_staking_pool_withdraw_inner(NearToken::from_yoctonear(unstake_amount), true);
+        contract.on_staking_pool_withdraw_inner(
+            &staking_pool,
+            NearToken::from_yoctonear(unstake_amount),
+            true,
+        );
         testing_env!(context.clone());
         assert_eq!(contract.get_known_deposited_balance().as_yoctonear(), 0);
 
         // Unselecting staking pool
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.unselect_staking_pool();
-        assert_eq!(contract.get_staking_pool_account_id(), None);
+        contract.unselect_staking_pool(staking_pool.clone());
+        assert!(contract.get_staking_pool_account_ids().is_empty());
     }
 
     #[test]
@@ -371,7 +553,7 @@ mod tests {
         let amount = to_yocto(LOCKUP_NEAR - 100);
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.deposit_to_staking_pool(NearToken::from_yoctonear(amount));
+        contract.deposit_to_staking_pool(staking_pool.clone(), NearToken::from_yoctonear(amount));
         context.account_balance = env::account_balance();
         assert_almost_eq(
             context.account_balance.as_yoctonear(),
@@ -379,15 +561,23 @@ mod tests {
         );
 
         context.predecessor_account_id = lockup_account();
-        contract.on_staking_pool_deposit_inner(NearToken::from_yoctonear(amount), true);
+        contract.on_staking_pool_deposit_inner(
+            &staking_pool,
+            NearToken::from_yoctonear(amount),
+            true,
+        );
 
         // Staking on the staking pool
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.stake(NearToken::from_yoctonear(amount));
+        contract.stake(staking_pool.clone(), NearToken::from_yoctonear(amount));
 
         context.predecessor_account_id = lockup_account();
-        contract.on_staking_pool_stake_inner(NearToken::from_yoctonear(amount), true);
+        contract.on_staking_pool_stake_inner(
+            &staking_pool,
+            NearToken::from_yoctonear(amount),
+            true,
+        );
 
         testing_env!(context.clone());
         assert_almost_eq(contract.get_owners_balance().as_yoctonear(), to_yocto(1000));
@@ -404,11 +594,14 @@ mod tests {
         let total_balance = amount + to_yocto(20);
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.refresh_staking_pool_balance();
+        contract.refresh_staking_pool_balance(staking_pool.clone());
 
         // In unit tests, the following call ignores the promise value, because it's passed directly.
         context.predecessor_account_id = lockup_account();
-        contract.on_get_account_total_balance(NearToken::from_yoctonear(total_balance));
+        contract.on_get_account_total_balance(
+            staking_pool.clone(),
+            NearToken::from_yoctonear(total_balance),
+        );
 
         testing_env!(context.clone());
         assert_eq!(
@@ -457,10 +650,131 @@ mod tests {
         context.predecessor_account_id = lockup_account();
         contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
 
-        // Selecting another staking pool
+        // Selecting the same staking pool again
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.select_staking_pool(staking_pool.clone());
+    }
+
+    #[test]
+    fn test_multiple_staking_pools() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+
+        // Selecting two staking pools
+        let staking_pool_1: AccountId = AccountId::from_str("staking_pool_1").unwrap();
+        let staking_pool_2: AccountId = AccountId::from_str("staking_pool_2").unwrap();
+        for staking_pool in [&staking_pool_1, &staking_pool_2] {
+            context.predecessor_account_id = account_owner();
+            testing_env!(context.clone());
+            contract.select_staking_pool(staking_pool.clone());
+
+            context.predecessor_account_id = lockup_account();
+            contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+        }
+        testing_env!(context.clone());
+        assert_eq!(
+            contract.get_staking_pool_account_ids(),
+            vec![staking_pool_1.clone(), staking_pool_2.clone()]
+        );
+
+        // Depositing to both staking pools
+        let amount_1 = to_yocto(100);
+        let amount_2 = to_yocto(200);
+        for (staking_pool, amount) in [(&staking_pool_1, amount_1), (&staking_pool_2, amount_2)] {
+            context.predecessor_account_id = account_owner();
+            testing_env!(context.clone());
+            contract
+                .deposit_to_staking_pool(staking_pool.clone(), NearToken::from_yoctonear(amount));
+            context.account_balance = env::account_balance();
+
+            context.predecessor_account_id = lockup_account();
+            contract.on_staking_pool_deposit_inner(
+                staking_pool,
+                NearToken::from_yoctonear(amount),
+                true,
+            );
+        }
+        testing_env!(context.clone());
+        assert_eq!(
+            contract.get_staking_pool_deposited_balance(staking_pool_1.clone()),
+            Some(NearToken::from_yoctonear(amount_1))
+        );
+        assert_eq!(
+            contract.get_known_deposited_balance().as_yoctonear(),
+            amount_1 + amount_2
+        );
+        assert_almost_eq(contract.get_owners_balance().as_yoctonear(), to_yocto(1000));
+        assert_almost_eq(contract.venear_liquid_balance(), to_yocto(1000));
+
+        // The busy staking pool doesn't block the other one.
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.stake(staking_pool_1.clone(), NearToken::from_yoctonear(amount_1));
+        contract.stake(staking_pool_2.clone(), NearToken::from_yoctonear(amount_2));
+
+        context.predecessor_account_id = lockup_account();
+        contract.on_staking_pool_stake_inner(
+            &staking_pool_1,
+            NearToken::from_yoctonear(amount_1),
+            true,
+        );
+        contract.on_staking_pool_stake_inner(
+            &staking_pool_2,
+            NearToken::from_yoctonear(amount_2),
+            true,
+        );
+
+        // Withdrawing from the first staking pool and unselecting it
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.withdraw_from_staking_pool(
+            staking_pool_1.clone(),
+            NearToken::from_yoctonear(amount_1),
+        );
+        context.account_balance =
+            NearToken::from_yoctonear(context.account_balance.as_yoctonear() + amount_1);
+
+        context.predecessor_account_id = lockup_account();
+        contract.on_staking_pool_withdraw_inner(
+            &staking_pool_1,
+            NearToken::from_yoctonear(amount_1),
+            true,
+        );
+
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.select_staking_pool(AccountId::from_str("staking_pool_2").unwrap());
+        contract.unselect_staking_pool(staking_pool_1.clone());
+        assert_eq!(
+            contract.get_staking_pool_account_ids(),
+            vec![staking_pool_2]
+        );
+        assert_eq!(
+            contract.get_known_deposited_balance().as_yoctonear(),
+            amount_2
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many staking pools are selected")]
+    fn test_too_many_staking_pools() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+
+        for i in 0..=MAX_STAKING_POOLS {
+            let staking_pool = AccountId::from_str(&format!("staking_pool_{}", i)).unwrap();
+            context.predecessor_account_id = account_owner();
+            testing_env!(context.clone());
+            contract.select_staking_pool(staking_pool.clone());
+
+            context.predecessor_account_id = lockup_account();
+            contract.on_whitelist_is_whitelisted(true, staking_pool);
+        }
     }
 
     #[test]
@@ -492,8 +806,9 @@ mod tests {
         context.attached_deposit = NearToken::from_yoctonear(1);
 
         // Unselecting staking pool
+        let staking_pool: AccountId = AccountId::from_str("staking_pool").unwrap();
         testing_env!(context.clone());
-        contract.unselect_staking_pool();
+        contract.unselect_staking_pool(staking_pool.clone());
     }
 
     #[test]
@@ -517,16 +832,20 @@ mod tests {
         let amount = to_yocto(LOCKUP_NEAR - 100);
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.deposit_to_staking_pool(NearToken::from_yoctonear(amount));
+        contract.deposit_to_staking_pool(staking_pool.clone(), NearToken::from_yoctonear(amount));
         context.account_balance = env::account_balance();
 
         context.predecessor_account_id = lockup_account();
-        contract.on_staking_pool_deposit_inner(NearToken::from_yoctonear(amount), true);
+        contract.on_staking_pool_deposit_inner(
+            &staking_pool,
+            NearToken::from_yoctonear(amount),
+            true,
+        );
 
         // Unselecting staking pool
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.unselect_staking_pool();
+        contract.unselect_staking_pool(staking_pool.clone());
     }
 
     #[test]
@@ -558,7 +877,8 @@ mod tests {
             total_amount += amount;
             context.predecessor_account_id = account_owner();
             testing_env!(context.clone());
-            contract.deposit_to_staking_pool(NearToken::from_yoctonear(amount));
+            contract
+                .deposit_to_staking_pool(staking_pool.clone(), NearToken::from_yoctonear(amount));
             context.account_balance = env::account_balance();
             assert_almost_eq(
                 context.account_balance.as_yoctonear(),
@@ -566,7 +886,11 @@ mod tests {
             );
 
             context.predecessor_account_id = lockup_account();
-            contract.on_staking_pool_deposit_inner(NearToken::from_yoctonear(amount), true);
+            contract.on_staking_pool_deposit_inner(
+                &staking_pool,
+                NearToken::from_yoctonear(amount),
+                true,
+            );
             testing_env!(context.clone());
             assert_eq!(
                 contract.get_known_deposited_balance().as_yoctonear(),
@@ -585,7 +909,10 @@ mod tests {
             total_withdrawn_amount += amount;
             context.predecessor_account_id = account_owner();
             testing_env!(context.clone());
-            contract.withdraw_from_staking_pool(NearToken::from_yoctonear(amount));
+            contract.withdraw_from_staking_pool(
+                staking_pool.clone(),
+                NearToken::from_yoctonear(amount),
+            );
             context.account_balance =
                 NearToken::from_yoctonear(context.account_balance.as_yoctonear() + amount);
             assert_almost_eq(
@@ -594,7 +921,11 @@ mod tests {
             );
 
             context.predecessor_account_id = lockup_account();
-            contract.on_staking_pool_withdraw_inner(NearToken::from_yoctonear(amount), true);
+            contract.on_staking_pool_withdraw_inner(
+                &staking_pool,
+                NearToken::from_yoctonear(amount),
+                true,
+            );
             testing_env!(context.clone());
             assert_eq!(
                 contract.get_known_deposited_balance().as_yoctonear(),
@@ -610,12 +941,17 @@ mod tests {
         // Withdrawing from the staking_pool one extra time as a reward
         context.predecessor_account_id = account_owner();
         testing_env!(context.clone());
-        contract.withdraw_from_staking_pool(NearToken::from_yoctonear(amount));
+        contract
+            .withdraw_from_staking_pool(staking_pool.clone(), NearToken::from_yoctonear(amount));
         context.account_balance =
             NearToken::from_yoctonear(context.account_balance.as_yoctonear() + amount);
 
         context.predecessor_account_id = lockup_account();
-        contract.on_staking_pool_withdraw_inner(NearToken::from_yoctonear(amount), true);
+        contract.on_staking_pool_withdraw_inner(
+            &staking_pool,
+            NearToken::from_yoctonear(amount),
+            true,
+        );
         testing_env!(context.clone());
         assert_eq!(
             contract.get_known_deposited_balance().as_yoctonear(),
@@ -630,4 +966,742 @@ mod tests {
             to_yocto(1000) + amount - MIN_LOCKUP_DEPOSIT.as_yoctonear(),
         );
     }
+
+    #[test]
+    fn test_liquid_staking_tokens() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+
+        // Selecting liquid staking token
+        let lst: AccountId = AccountId::from_str("lst").unwrap();
+        contract.select_liquid_staking_token(lst.clone(), LstPriceMethod::FtPrice);
+        context.predecessor_account_id = lockup_account();
+        testing_env!(context.clone());
+        contract.on_whitelist_is_whitelisted_lst(true, lst.clone(), LstPriceMethod::FtPrice);
+
+        // Receiving tokens, the value is unknown until the price is refreshed
+        context.predecessor_account_id = lst.clone();
+        context.attached_deposit = NearToken::from_yoctonear(0);
+        testing_env!(context.clone());
+        contract.ft_on_transfer(account_owner(), to_yocto(100).into(), "".to_string());
+        assert_eq!(
+            contract
+                .get_liquid_staking_tokens_near_balance()
+                .as_yoctonear(),
+            0
+        );
+        assert_almost_eq(contract.venear_liquid_balance(), to_yocto(1000));
+
+        // Anyone can refresh the price
+        context.predecessor_account_id = non_owner();
+        testing_env!(context.clone());
+        contract.refresh_liquid_staking_token_price(lst.clone());
+        context.predecessor_account_id = lockup_account();
+        testing_env!(context.clone());
+        contract.on_get_liquid_staking_token_price(lst.clone(), (to_yocto(12) / 10).into());
+        let fungible_tokens = contract.get_liquid_staking_tokens();
+        assert_eq!(fungible_tokens.len(), 1);
+        assert_eq!(fungible_tokens[0].balance.0, to_yocto(100));
+        assert_eq!(
+            fungible_tokens[0].near_balance.as_yoctonear(),
+            to_yocto(120)
+        );
+        assert_almost_eq(contract.venear_liquid_balance(), to_yocto(1120));
+
+        // Locking NEAR covered by the liquid staking token
+        context.predecessor_account_id = account_owner();
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(1100)), None);
+        assert_almost_eq(contract.venear_liquid_balance(), to_yocto(20));
+        let (locked_near_balance, locked_fungible_tokens) = contract.internal_get_locked_balances();
+        assert_almost_eq(locked_near_balance, to_yocto(1000));
+        assert_eq!(locked_fungible_tokens.len(), 1);
+        assert_almost_eq(
+            locked_fungible_tokens[0].near_balance.as_yoctonear(),
+            to_yocto(100),
+        );
+        assert_almost_eq(locked_fungible_tokens[0].balance.0, to_yocto(100) * 5 / 6);
+
+        // Transferring tokens out within the liquid balance
+        contract.transfer_liquid_staking_token(lst.clone(), to_yocto(10).into(), non_owner());
+        assert_eq!(
+            contract.get_liquid_staking_tokens()[0].balance.0,
+            to_yocto(90)
+        );
+        assert_almost_eq(contract.venear_liquid_balance(), to_yocto(8));
+
+        // The price drop doesn't break the liquid balance
+        context.predecessor_account_id = lockup_account();
+        testing_env!(context.clone());
+        contract.on_liquid_staking_token_transfer_inner(&lst, to_yocto(10).into(), true);
+        let lockup_update_nonce = contract.lockup_update_nonce;
+        contract.on_get_liquid_staking_token_price(lst.clone(), to_yocto(1).into());
+        assert_eq!(contract.venear_liquid_balance(), 0);
+        // veNEAR is updated with the new value of the locked tokens
+        assert_eq!(contract.lockup_update_nonce, lockup_update_nonce + 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only currently selected LST is accepted")]
+    fn test_liquid_staking_token_is_not_selected() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = AccountId::from_str("lst").unwrap();
+        testing_env!(context.clone());
+        contract.ft_on_transfer(account_owner(), to_yocto(100).into(), "".to_string());
+    }
+
+    #[test]
+    fn test_lock_near_with_staking_pool_refresh() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+
+        // Selecting staking pool and depositing
+        let staking_pool: AccountId = AccountId::from_str("staking_pool").unwrap();
+        contract.select_staking_pool(staking_pool.clone());
+        context.predecessor_account_id = lockup_account();
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        let amount = to_yocto(500);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.deposit_to_staking_pool(staking_pool.clone(), NearToken::from_yoctonear(amount));
+        context.account_balance = env::account_balance();
+        context.predecessor_account_id = lockup_account();
+        testing_env!(context.clone());
+        contract.on_staking_pool_deposit_inner(
+            &staking_pool,
+            NearToken::from_yoctonear(amount),
+            true,
+        );
+        assert_almost_eq(contract.venear_liquid_balance(), to_yocto(1000));
+
+        // Locking all NEAR including the staking rewards
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.lock_near(None, Some(staking_pool.clone()));
+        assert_eq!(contract.venear_locked_balance, 0);
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(context.clone());
+        assert!(contract.on_get_account_total_balance_to_lock(
+            staking_pool.clone(),
+            None,
+            Ok(NearToken::from_near(510)),
+        ));
+        assert_almost_eq(contract.venear_locked_balance, to_yocto(1010));
+        assert_eq!(
+            contract.get_known_deposited_balance(),
+            NearToken::from_near(510)
+        );
+
+        // The invalid amount is skipped and the staking pool is not busy
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(10)), Some(staking_pool.clone()));
+        context.predecessor_account_id = lockup_account();
+        testing_env!(context.clone());
+        assert!(!contract.on_get_account_total_balance_to_lock(
+            staking_pool.clone(),
+            Some(NearToken::from_near(10)),
+            Err(PromiseError::Failed),
+        ));
+        assert_almost_eq(contract.venear_locked_balance, to_yocto(1010));
+        contract.assert_staking_pool_is_idle(&staking_pool);
+    }
+
+    #[test]
+    fn test_sync_rewards() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+
+        // Selecting staking pool, depositing and locking everything
+        let staking_pool: AccountId = AccountId::from_str("staking_pool").unwrap();
+        contract.select_staking_pool(staking_pool.clone());
+        context.predecessor_account_id = lockup_account();
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+
+        let amount = to_yocto(500);
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.deposit_to_staking_pool(staking_pool.clone(), NearToken::from_yoctonear(amount));
+        context.account_balance = env::account_balance();
+        context.predecessor_account_id = lockup_account();
+        testing_env!(context.clone());
+        contract.on_staking_pool_deposit_inner(
+            &staking_pool,
+            NearToken::from_yoctonear(amount),
+            true,
+        );
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.lock_near(None, None);
+        contract.set_auto_lock_rewards(true);
+        assert!(contract.get_auto_lock_rewards());
+        let locked_balance = contract.venear_locked_balance;
+
+        // Anyone can sync the rewards
+        context.predecessor_account_id = non_owner();
+        context.attached_deposit = NearToken::from_yoctonear(0);
+        testing_env!(context.clone());
+        contract.sync_rewards(staking_pool.clone());
+
+        context.predecessor_account_id = lockup_account();
+        testing_env!(context.clone());
+        let locked_rewards = contract.on_get_account_total_balance_to_sync_rewards(
+            staking_pool.clone(),
+            NearToken::from_yoctonear(amount),
+            Ok(NearToken::from_near(505)),
+        );
+        assert_almost_eq(locked_rewards.as_yoctonear(), to_yocto(5));
+        assert_eq!(
+            contract.venear_locked_balance,
+            locked_balance + locked_rewards.as_yoctonear()
+        );
+        assert_eq!(contract.venear_liquid_balance(), 0);
+
+        // No rewards since the last refresh
+        context.predecessor_account_id = non_owner();
+        testing_env!(context.clone());
+        contract.sync_rewards(staking_pool.clone());
+        // The sync doesn't block the owner
+        contract.assert_staking_pool_is_idle(&staking_pool);
+        context.predecessor_account_id = lockup_account();
+        testing_env!(context.clone());
+        assert_eq!(
+            contract.on_get_account_total_balance_to_sync_rewards(
+                staking_pool.clone(),
+                NearToken::from_near(505),
+                Ok(NearToken::from_near(505)),
+            ),
+            NearToken::from_yoctonear(0)
+        );
+        assert_eq!(
+            contract.venear_locked_balance,
+            locked_balance + locked_rewards.as_yoctonear()
+        );
+    }
+
+    #[test]
+    fn test_sync_rewards_after_staking_pool_update() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        let staking_pool: AccountId = AccountId::from_str("staking_pool").unwrap();
+        contract.select_staking_pool(staking_pool.clone());
+        context.predecessor_account_id = lockup_account();
+        contract.on_whitelist_is_whitelisted(true, staking_pool.clone());
+        context.predecessor_account_id = account_owner();
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(100)), None);
+        contract.set_auto_lock_rewards(true);
+        let locked_balance = contract.venear_locked_balance;
+
+        context.predecessor_account_id = non_owner();
+        context.attached_deposit = NearToken::from_yoctonear(0);
+        testing_env!(context.clone());
+        contract.sync_rewards(staking_pool.clone());
+
+        // The owner deposits to the staking pool before the sync completes
+        context.predecessor_account_id = account_owner();
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.deposit_to_staking_pool(staking_pool.clone(), NearToken::from_near(500));
+        context.account_balance = env::account_balance();
+        context.predecessor_account_id = lockup_account();
+        testing_env!(context.clone());
+        contract.on_staking_pool_deposit_inner(&staking_pool, NearToken::from_near(500), true);
+
+        // The stale balance is ignored
+        assert_eq!(
+            contract.on_get_account_total_balance_to_sync_rewards(
+                staking_pool.clone(),
+                NearToken::from_yoctonear(0),
+                Ok(NearToken::from_near(1)),
+            ),
+            NearToken::from_yoctonear(0)
+        );
+        assert_eq!(contract.venear_locked_balance, locked_balance);
+        assert_eq!(
+            contract.staking_pools[&staking_pool].deposit_amount,
+            NearToken::from_near(500)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Auto-locking of the staking rewards is disabled")]
+    fn test_sync_rewards_disabled() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = non_owner();
+        testing_env!(context.clone());
+        contract.sync_rewards(AccountId::from_str("staking_pool").unwrap());
+    }
+
+    #[test]
+    fn test_unlock_tranches() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+
+        // Unlocking in two tranches a day apart
+        contract.begin_unlock_near(Some(NearToken::from_near(100)), None);
+        let first_unlock_timestamp = context.block_timestamp + UNLOCK_DURATION_NS;
+        context.block_timestamp += to_nanos(1);
+        testing_env!(context.clone());
+        contract.begin_unlock_near(Some(NearToken::from_near(50)), None);
+        let second_unlock_timestamp = context.block_timestamp + UNLOCK_DURATION_NS;
+
+        let tranches = contract.get_unlock_tranches();
+        assert_eq!(tranches.len(), 2);
+        assert_eq!(tranches[0].unlock_timestamp.0, first_unlock_timestamp);
+        assert_eq!(tranches[1].unlock_timestamp.0, second_unlock_timestamp);
+        assert_eq!(
+            contract.get_venear_unlock_timestamp().0,
+            second_unlock_timestamp
+        );
+        assert_eq!(
+            contract.get_venear_pending_balance(),
+            NearToken::from_near(150)
+        );
+        assert_eq!(
+            contract.get_venear_locked_balance(),
+            NearToken::from_near(350)
+        );
+
+        // Only the first tranche can be unlocked
+        context.block_timestamp = first_unlock_timestamp;
+        testing_env!(context.clone());
+        assert_eq!(
+            contract.get_venear_unlockable_balance(),
+            NearToken::from_near(100)
+        );
+        contract.end_unlock_near(None);
+        assert_eq!(
+            contract.get_venear_pending_balance(),
+            NearToken::from_near(50)
+        );
+        assert_eq!(contract.get_unlock_tranches().len(), 1);
+
+        // Locking the pending NEAR back
+        contract.lock_pending_near(Some(NearToken::from_near(20)));
+        assert_eq!(
+            contract.get_venear_pending_balance(),
+            NearToken::from_near(30)
+        );
+        assert_eq!(
+            contract.get_venear_locked_balance(),
+            NearToken::from_near(370)
+        );
+        assert_eq!(
+            contract.get_unlock_tranches()[0].unlock_timestamp.0,
+            second_unlock_timestamp
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid amount")]
+    fn test_begin_unlock_zero_amount() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+        contract.begin_unlock_near(Some(NearToken::from_near(0)), None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The amount exceeds the pending NEAR that reached the unlock timestamp"
+    )]
+    fn test_end_unlock_more_than_reached() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+        contract.begin_unlock_near(Some(NearToken::from_near(100)), None);
+
+        context.block_timestamp += UNLOCK_DURATION_NS;
+        testing_env!(context.clone());
+        contract.begin_unlock_near(Some(NearToken::from_near(100)), None);
+        contract.end_unlock_near(Some(NearToken::from_near(150)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Nothing to unlock")]
+    fn test_end_unlock_nothing_reached() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+        contract.begin_unlock_near(Some(NearToken::from_near(100)), None);
+        contract.end_unlock_near(None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The amount exceeds the pending NEAR that reached the unlock timestamp"
+    )]
+    fn test_end_unlock_before_unlock_time() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+        contract.begin_unlock_near(Some(NearToken::from_near(100)), None);
+
+        context.block_timestamp += UNLOCK_DURATION_NS - 1;
+        testing_env!(context.clone());
+        contract.end_unlock_near(Some(NearToken::from_near(100)));
+    }
+
+    #[test]
+    fn test_finalize_unlock() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+        contract.begin_unlock_near(Some(NearToken::from_near(100)), None);
+        let liquid_balance = contract.get_venear_liquid_balance();
+
+        // Anyone can finalize the matured unlock without a deposit
+        context.predecessor_account_id = non_owner();
+        context.attached_deposit = NearToken::from_yoctonear(0);
+        context.block_timestamp += UNLOCK_DURATION_NS;
+        testing_env!(context.clone());
+        contract.finalize_unlock();
+        assert_eq!(contract.get_venear_pending_balance().as_yoctonear(), 0);
+        assert_almost_eq(
+            contract.get_venear_liquid_balance().as_yoctonear(),
+            liquid_balance.as_yoctonear() + NearToken::from_near(100).as_yoctonear(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Nothing to unlock")]
+    fn test_finalize_unlock_before_unlock_time() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+        contract.begin_unlock_near(Some(NearToken::from_near(100)), None);
+
+        context.predecessor_account_id = non_owner();
+        context.attached_deposit = NearToken::from_yoctonear(0);
+        context.block_timestamp += UNLOCK_DURATION_NS - 1;
+        testing_env!(context.clone());
+        contract.finalize_unlock();
+    }
+
+    #[test]
+    fn test_owner_transfer() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.propose_owner(Some(non_owner()));
+        assert_eq!(contract.get_proposed_owner_account_id(), Some(non_owner()));
+        assert_eq!(
+            contract.get_owner_transfer_timestamp().0,
+            context.block_timestamp + OWNER_TRANSFER_DELAY_NS
+        );
+
+        context.predecessor_account_id = non_owner();
+        context.block_timestamp += OWNER_TRANSFER_DELAY_NS;
+        testing_env!(context.clone());
+        contract.accept_owner();
+
+        // The ownership is not transferred if veNEAR fails to move the account
+        assert!(!contract.on_lockup_owner_transfer_inner(non_owner(), false));
+        assert_eq!(contract.get_owner_account_id(), account_owner());
+
+        assert!(contract.on_lockup_owner_transfer_inner(non_owner(), true));
+        assert_eq!(contract.get_owner_account_id(), non_owner());
+        assert_eq!(contract.get_proposed_owner_account_id(), None);
+        assert_eq!(contract.get_owner_transfer_timestamp().0, 0);
+
+        // The new owner can call owner's methods
+        contract.lock_near(Some(NearToken::from_near(100)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "The owner transfer delay has not passed")]
+    fn test_accept_owner_before_delay() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.propose_owner(Some(non_owner()));
+
+        context.predecessor_account_id = non_owner();
+        context.block_timestamp += OWNER_TRANSFER_DELAY_NS - 1;
+        testing_env!(context.clone());
+        contract.accept_owner();
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the proposed owner")]
+    fn test_accept_owner_after_cancel() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.propose_owner(Some(non_owner()));
+        contract.propose_owner(None);
+
+        context.predecessor_account_id = non_owner();
+        context.block_timestamp += OWNER_TRANSFER_DELAY_NS;
+        testing_env!(context.clone());
+        contract.accept_owner();
+    }
+
+    #[test]
+    fn test_lockup_migration() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+        contract.begin_unlock_near(Some(NearToken::from_near(100)), None);
+        contract.commit_lock(MIN_LOCK_DURATION_NS.into());
+        let unlock_tranches = contract.get_unlock_tranches();
+
+        let new_lockup_account_id = AccountId::from_str("new_lockup").unwrap();
+        contract.migrate_lockup(new_lockup_account_id.clone());
+        assert_eq!(
+            contract.get_migration_lockup_account_id(),
+            Some(new_lockup_account_id.clone())
+        );
+
+        // The lockup contract stays unchanged if veNEAR rejects the migration
+        assert!(!contract.on_lockup_migration_inner(new_lockup_account_id.clone(), false));
+        assert_eq!(contract.get_migration_lockup_account_id(), None);
+        assert_eq!(
+            contract.get_venear_locked_balance(),
+            NearToken::from_near(400)
+        );
+
+        contract.migrate_lockup(new_lockup_account_id.clone());
+        assert!(contract.on_lockup_migration_inner(new_lockup_account_id.clone(), true));
+        assert_eq!(contract.get_venear_locked_balance().as_yoctonear(), 0);
+        assert_eq!(contract.get_venear_pending_balance().as_yoctonear(), 0);
+        assert_eq!(contract.get_lock_end_timestamp().0, 0);
+
+        // The new lockup contract accepts the migration from the previous lockup contract
+        let (mut context, mut new_contract) = lockup_only_setup();
+        new_contract.previous_lockup_account_id = Some(lockup_account());
+        context.predecessor_account_id = lockup_account();
+        context.attached_deposit = NearToken::from_near(500);
+        testing_env!(context.clone());
+        new_contract.accept_lockup_migration(
+            NearToken::from_near(400),
+            unlock_tranches,
+            MIN_LOCK_DURATION_NS.into(),
+            (context.block_timestamp + MIN_LOCK_DURATION_NS).into(),
+        );
+        assert_eq!(
+            new_contract.get_venear_locked_balance(),
+            NearToken::from_near(400)
+        );
+        assert_eq!(
+            new_contract.get_venear_pending_balance(),
+            NearToken::from_near(100)
+        );
+        assert_eq!(new_contract.get_lock_duration_ns().0, MIN_LOCK_DURATION_NS);
+        assert_eq!(new_contract.previous_lockup_account_id, None);
+    }
+
+    #[test]
+    fn test_lockup_migration_rollback() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+        contract.begin_unlock_near(Some(NearToken::from_near(100)), None);
+        contract.commit_lock(MIN_LOCK_DURATION_NS.into());
+        let unlock_tranches = contract.get_unlock_tranches();
+        let lock_end_timestamp = contract.get_lock_end_timestamp();
+
+        let new_lockup_account_id = AccountId::from_str("new_lockup").unwrap();
+        contract.migrate_lockup(new_lockup_account_id.clone());
+        assert!(contract.on_lockup_migration_inner(new_lockup_account_id.clone(), true));
+        assert_eq!(contract.get_venear_locked_balance().as_yoctonear(), 0);
+
+        // The lockup contract is restored if the new lockup contract fails to accept the migration
+        assert!(!contract.on_accept_lockup_migration_inner(
+            new_lockup_account_id.clone(),
+            NearToken::from_near(400),
+            unlock_tranches.clone(),
+            MIN_LOCK_DURATION_NS.into(),
+            lock_end_timestamp,
+            false,
+        ));
+        assert_eq!(contract.get_migration_lockup_account_id(), None);
+        assert_eq!(
+            contract.get_venear_locked_balance(),
+            NearToken::from_near(400)
+        );
+        assert_eq!(
+            contract.get_venear_pending_balance(),
+            NearToken::from_near(100)
+        );
+        assert_eq!(contract.get_unlock_tranches().len(), unlock_tranches.len());
+        assert_eq!(contract.get_lock_end_timestamp().0, lock_end_timestamp.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough balance to migrate the locked NEAR")]
+    fn test_lockup_migration_without_balance() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+        context.account_balance = NearToken::from_near(100);
+        testing_env!(context.clone());
+        contract.migrate_lockup(AccountId::from_str("new_lockup").unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can only be called by the previous lockup contract")]
+    fn test_accept_lockup_migration_by_non_previous_lockup() {
+        let (mut context, mut contract) = lockup_only_setup();
+        contract.previous_lockup_account_id = Some(lockup_account());
+        context.predecessor_account_id = non_owner();
+        context.attached_deposit = NearToken::from_near(500);
+        testing_env!(context.clone());
+        contract.accept_lockup_migration(NearToken::from_near(500), vec![], 0.into(), 0.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "The lockup contract is migrated")]
+    fn test_select_staking_pool_during_migration() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.migrate_lockup(AccountId::from_str("new_lockup").unwrap());
+        contract.select_staking_pool(AccountId::from_str("staking_pool").unwrap());
+    }
+
+    #[test]
+    fn test_lock_commitment() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+
+        contract.commit_lock(MIN_LOCK_DURATION_NS.into());
+        let lock_end_timestamp = context.block_timestamp + MIN_LOCK_DURATION_NS;
+        assert_eq!(contract.get_lock_duration_ns().0, MIN_LOCK_DURATION_NS);
+        assert_eq!(contract.get_lock_end_timestamp().0, lock_end_timestamp);
+        // The unlock amount is not available during the commitment
+        assert!(contract.internal_get_unlock_amount(None).is_none());
+
+        // The commitment can be extended
+        context.block_timestamp += to_nanos(1);
+        testing_env!(context.clone());
+        contract.commit_lock(MIN_LOCK_DURATION_NS.into());
+        let lock_end_timestamp = context.block_timestamp + MIN_LOCK_DURATION_NS;
+        assert_eq!(contract.get_lock_end_timestamp().0, lock_end_timestamp);
+
+        // The commitment ends and the NEAR can be unlocked
+        context.block_timestamp = lock_end_timestamp;
+        testing_env!(context.clone());
+        contract.begin_unlock_near(Some(NearToken::from_near(100)), None);
+        assert_eq!(
+            contract.get_venear_pending_balance(),
+            NearToken::from_near(100)
+        );
+
+        // Anyone can clear the ended commitment
+        context.predecessor_account_id = non_owner();
+        context.attached_deposit = NearToken::from_yoctonear(0);
+        testing_env!(context.clone());
+        contract.end_lock_commitment();
+        assert_eq!(contract.get_lock_duration_ns().0, 0);
+        assert_eq!(contract.get_lock_end_timestamp().0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "The lock commitment has not ended")]
+    fn test_begin_unlock_during_lock_commitment() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.lock_near(Some(NearToken::from_near(500)), None);
+        contract.commit_lock(MAX_LOCK_DURATION_NS.into());
+
+        context.block_timestamp += MAX_LOCK_DURATION_NS - 1;
+        testing_env!(context.clone());
+        contract.begin_unlock_near(Some(NearToken::from_near(100)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "The lock commitment can't be shortened")]
+    fn test_shorten_lock_commitment() {
+        let (mut context, mut contract) = lockup_only_setup();
+        context.predecessor_account_id = account_owner();
+        context.signer_account_id = account_owner();
+        context.signer_account_pk = public_key(2);
+        context.attached_deposit = NearToken::from_yoctonear(1);
+        testing_env!(context.clone());
+        contract.commit_lock(MAX_LOCK_DURATION_NS.into());
+        contract.commit_lock(MIN_LOCK_DURATION_NS.into());
+    }
 }
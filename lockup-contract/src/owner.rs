@@ -1,5 +1,10 @@
+use crate::venear_ext::{
+    ext_venear, GAS_FOR_VENEAR_LOCKUP_MIGRATION, GAS_FOR_VENEAR_LOCKUP_OWNER_TRANSFER,
+};
 use crate::*;
 use common::events;
+use common::lockup_update::LockupMigrationUpdate;
+use near_sdk::json_types::U128;
 use near_sdk::{assert_one_yocto, near, AccountId, NearToken, Promise};
 
 #[near]
@@ -10,7 +15,8 @@ impl LockupContract {
     /// Requires 1 yoctoNEAR attached
     ///
     /// Selects staking pool contract at the given account ID. The staking pool first has to be
-    /// checked against the staking pool whitelist contract.
+    /// checked against the staking pool whitelist contract. Multiple staking pools can be selected
+    /// at the same time, up to `MAX_STAKING_POOLS`.
     #[payable]
     pub fn select_staking_pool(&mut self, staking_pool_account_id: AccountId) -> Promise {
         self.assert_owner();
@@ -19,7 +25,9 @@ impl LockupContract {
             env::is_valid_account_id(staking_pool_account_id.as_bytes()),
             "The staking pool account ID is invalid"
         );
-        self.assert_staking_pool_is_not_selected();
+        self.assert_staking_pool_is_not_selected(&staking_pool_account_id);
+        self.assert_can_select_staking_pool();
+        self.assert_not_migrated();
 
         env::log_str(&format!(
             "Selecting staking pool @{}. Going to check whitelist first.",
@@ -41,20 +49,18 @@ impl LockupContract {
     /// Requires 25 TGas (1 * BASE_GAS)
     /// Requires 1 yoctoNEAR attached
     ///
-    /// Unselects the current staking pool.
-    /// It requires that there are no known deposits left on the currently selected staking pool.
+    /// Unselects the given staking pool.
+    /// It requires that there are no known deposits left on the staking pool.
     #[payable]
-    pub fn unselect_staking_pool(&mut self) {
+    pub fn unselect_staking_pool(&mut self, staking_pool_account_id: AccountId) {
         self.assert_owner();
         assert_one_yocto();
-        self.assert_staking_pool_is_idle();
+        self.assert_staking_pool_is_idle(&staking_pool_account_id);
         // NOTE: This is best effort checks. There is still some balance might be left on the
         // staking pool, but it's up to the owner whether to unselect the staking pool.
         // The contract doesn't care about leftovers.
         assert_eq!(
-            self.staking_information
-                .as_ref()
-                .unwrap()
+            self.staking_pools[&staking_pool_account_id]
                 .deposit_amount
                 .as_yoctonear(),
             0,
@@ -62,14 +68,11 @@ impl LockupContract {
         );
 
         env::log_str(&format!(
-            "Unselected current staking pool @{}.",
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
+            "Unselected staking pool @{}.",
+            staking_pool_account_id
         ));
 
-        self.staking_information = None;
+        self.staking_pools.remove(&staking_pool_account_id);
     }
 
     /// OWNER'S METHOD
@@ -77,13 +80,17 @@ impl LockupContract {
     /// Requires 100 TGas (4 * BASE_GAS)
     /// Requires 1 yoctoNEAR attached
     ///
-    /// Deposits the given extra amount to the staking pool
+    /// Deposits the given extra amount to the given staking pool
     #[payable]
-    pub fn deposit_to_staking_pool(&mut self, amount: NearToken) -> Promise {
+    pub fn deposit_to_staking_pool(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> Promise {
         self.assert_owner();
         assert_one_yocto();
         assert!(amount.as_yoctonear() > 0, "Amount should be positive");
-        self.assert_staking_pool_is_idle();
+        self.assert_staking_pool_is_idle(&staking_pool_account_id);
         assert!(
             self.get_account_balance() >= amount,
             "The balance that can be deposited to the staking pool is lower than the extra amount"
@@ -91,30 +98,20 @@ impl LockupContract {
 
         env::log_str(&format!(
             "Depositing {} to the staking pool @{}",
-            amount,
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
+            amount, staking_pool_account_id
         ));
 
-        self.set_staking_pool_status(TransactionStatus::Busy);
-
-        ext_staking_pool::ext(
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
-                .clone(),
-        )
-        .with_static_gas(gas::staking_pool::DEPOSIT)
-        .with_attached_deposit(amount)
-        .deposit()
-        .then(
-            ext_self_owner::ext(env::current_account_id())
-                .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_DEPOSIT)
-                .on_staking_pool_deposit(amount),
-        )
+        self.set_staking_pool_status(&staking_pool_account_id, TransactionStatus::Busy);
+
+        ext_staking_pool::ext(staking_pool_account_id.clone())
+            .with_static_gas(gas::staking_pool::DEPOSIT)
+            .with_attached_deposit(amount)
+            .deposit()
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_DEPOSIT)
+                    .on_staking_pool_deposit(staking_pool_account_id, amount),
+            )
     }
 
     /// OWNER'S METHOD
@@ -122,13 +119,17 @@ impl LockupContract {
     /// Requires 125 TGas (5 * BASE_GAS)
     /// Requires 1 yoctoNEAR attached
     ///
-    /// Deposits and stakes the given extra amount to the selected staking pool
+    /// Deposits and stakes the given extra amount to the given staking pool
     #[payable]
-    pub fn deposit_and_stake(&mut self, amount: NearToken) -> Promise {
+    pub fn deposit_and_stake(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> Promise {
         self.assert_owner();
         assert_one_yocto();
         assert!(amount.as_yoctonear() > 0, "Amount should be positive");
-        self.assert_staking_pool_is_idle();
+        self.assert_staking_pool_is_idle(&staking_pool_account_id);
         assert!(
             self.get_account_balance() >= amount,
             "The balance that can be deposited to the staking pool is lower than the extra amount"
@@ -136,30 +137,20 @@ impl LockupContract {
 
         env::log_str(&format!(
             "Depositing and staking {} to the staking pool @{}",
-            amount,
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
+            amount, staking_pool_account_id
         ));
 
-        self.set_staking_pool_status(TransactionStatus::Busy);
-
-        ext_staking_pool::ext(
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
-                .clone(),
-        )
-        .with_static_gas(gas::staking_pool::DEPOSIT_AND_STAKE)
-        .with_attached_deposit(amount)
-        .deposit_and_stake()
-        .then(
-            ext_self_owner::ext(env::current_account_id())
-                .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_DEPOSIT_AND_STAKE)
-                .on_staking_pool_deposit_and_stake(amount),
-        )
+        self.set_staking_pool_status(&staking_pool_account_id, TransactionStatus::Busy);
+
+        ext_staking_pool::ext(staking_pool_account_id.clone())
+            .with_static_gas(gas::staking_pool::DEPOSIT_AND_STAKE)
+            .with_attached_deposit(amount)
+            .deposit_and_stake()
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_DEPOSIT_AND_STAKE)
+                    .on_staking_pool_deposit_and_stake(staking_pool_account_id, amount),
+            )
     }
 
     /// OWNER'S METHOD
@@ -167,40 +158,21 @@ impl LockupContract {
     /// Requires 75 TGas (3 * BASE_GAS)
     /// Requires 1 yoctoNEAR attached
     ///
-    /// Retrieves total balance from the staking pool and remembers it internally.
+    /// Retrieves total balance from the given staking pool and remembers it internally.
     /// This method is helpful when the owner received some rewards for staking and wants to
     /// transfer them back to this account for withdrawal. In order to know the actual liquid
     /// balance on the account, this contract needs to query the staking pool.
     #[payable]
-    pub fn refresh_staking_pool_balance(&mut self) -> Promise {
+    pub fn refresh_staking_pool_balance(&mut self, staking_pool_account_id: AccountId) -> Promise {
         self.assert_owner();
         assert_one_yocto();
-        self.assert_staking_pool_is_idle();
 
-        env::log_str(&format!(
-            "Fetching total balance from the staking pool @{}",
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
-        ));
-
-        self.set_staking_pool_status(TransactionStatus::Busy);
-
-        ext_staking_pool::ext(
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
-                .clone(),
-        )
-        .with_static_gas(gas::staking_pool::GET_ACCOUNT_TOTAL_BALANCE)
-        .get_account_total_balance(env::current_account_id())
-        .then(
-            ext_self_owner::ext(env::current_account_id())
-                .with_static_gas(gas::owner_callbacks::ON_GET_ACCOUNT_TOTAL_BALANCE)
-                .on_get_account_total_balance(),
-        )
+        self.internal_get_staking_pool_total_balance(&staking_pool_account_id)
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(gas::owner_callbacks::ON_GET_ACCOUNT_TOTAL_BALANCE)
+                    .on_get_account_total_balance(staking_pool_account_id),
+            )
     }
 
     /// OWNER'S METHOD
@@ -208,39 +180,33 @@ impl LockupContract {
     /// Requires 125 TGas (5 * BASE_GAS)
     /// Requires 1 yoctoNEAR attached
     ///
-    /// Withdraws the given amount from the staking pool
+    /// Withdraws the given amount from the given staking pool
     #[payable]
-    pub fn withdraw_from_staking_pool(&mut self, amount: NearToken) -> Promise {
+    pub fn withdraw_from_staking_pool(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> Promise {
         self.assert_owner();
         assert_one_yocto();
         assert!(amount.as_yoctonear() > 0, "Amount should be positive");
-        self.assert_staking_pool_is_idle();
+        self.assert_staking_pool_is_idle(&staking_pool_account_id);
 
         env::log_str(&format!(
             "Withdrawing {} from the staking pool @{}",
-            amount,
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
+            amount, staking_pool_account_id
         ));
 
-        self.set_staking_pool_status(TransactionStatus::Busy);
-
-        ext_staking_pool::ext(
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
-                .clone(),
-        )
-        .with_static_gas(gas::staking_pool::WITHDRAW)
-        .withdraw(amount)
-        .then(
-            ext_self_owner::ext(env::current_account_id())
-                .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_WITHDRAW)
-                .on_staking_pool_withdraw(amount),
-        )
+        self.set_staking_pool_status(&staking_pool_account_id, TransactionStatus::Busy);
+
+        ext_staking_pool::ext(staking_pool_account_id.clone())
+            .with_static_gas(gas::staking_pool::WITHDRAW)
+            .withdraw(amount)
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_WITHDRAW)
+                    .on_staking_pool_withdraw(staking_pool_account_id, amount),
+            )
     }
 
     /// OWNER'S METHOD
@@ -248,39 +214,33 @@ impl LockupContract {
     /// Requires 175 TGas (7 * BASE_GAS)
     /// Requires 1 yoctoNEAR attached
     ///
-    /// Tries to withdraw all unstaked balance from the staking pool
+    /// Tries to withdraw all unstaked balance from the given staking pool
     #[payable]
-    pub fn withdraw_all_from_staking_pool(&mut self) -> Promise {
+    pub fn withdraw_all_from_staking_pool(
+        &mut self,
+        staking_pool_account_id: AccountId,
+    ) -> Promise {
         self.assert_owner();
         assert_one_yocto();
-        self.assert_staking_pool_is_idle();
+        self.assert_staking_pool_is_idle(&staking_pool_account_id);
 
         env::log_str(&format!(
             "Going to query the unstaked balance at the staking pool @{}",
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
+            staking_pool_account_id
         ));
 
-        self.set_staking_pool_status(TransactionStatus::Busy);
-
-        ext_staking_pool::ext(
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
-                .clone(),
-        )
-        .with_static_gas(gas::staking_pool::GET_ACCOUNT_UNSTAKED_BALANCE)
-        .get_account_unstaked_balance(env::current_account_id())
-        .then(
-            ext_self_owner::ext(env::current_account_id())
-                .with_static_gas(
-                    gas::owner_callbacks::ON_GET_ACCOUNT_UNSTAKED_BALANCE_TO_WITHDRAW_BY_OWNER,
-                )
-                .on_get_account_unstaked_balance_to_withdraw_by_owner(),
-        )
+        self.set_staking_pool_status(&staking_pool_account_id, TransactionStatus::Busy);
+
+        ext_staking_pool::ext(staking_pool_account_id.clone())
+            .with_static_gas(gas::staking_pool::GET_ACCOUNT_UNSTAKED_BALANCE)
+            .get_account_unstaked_balance(env::current_account_id())
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(
+                        gas::owner_callbacks::ON_GET_ACCOUNT_UNSTAKED_BALANCE_TO_WITHDRAW_BY_OWNER,
+                    )
+                    .on_get_account_unstaked_balance_to_withdraw_by_owner(staking_pool_account_id),
+            )
     }
 
     /// OWNER'S METHOD
@@ -288,39 +248,29 @@ impl LockupContract {
     /// Requires 125 TGas (5 * BASE_GAS)
     /// Requires 1 yoctoNEAR attached
     ///
-    /// Stakes the given extra amount at the staking pool
+    /// Stakes the given extra amount at the given staking pool
     #[payable]
-    pub fn stake(&mut self, amount: NearToken) -> Promise {
+    pub fn stake(&mut self, staking_pool_account_id: AccountId, amount: NearToken) -> Promise {
         self.assert_owner();
         assert_one_yocto();
         assert!(amount.as_yoctonear() > 0, "Amount should be positive");
-        self.assert_staking_pool_is_idle();
+        self.assert_staking_pool_is_idle(&staking_pool_account_id);
 
         env::log_str(&format!(
             "Staking {} at the staking pool @{}",
-            amount,
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
+            amount, staking_pool_account_id
         ));
 
-        self.set_staking_pool_status(TransactionStatus::Busy);
-
-        ext_staking_pool::ext(
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
-                .clone(),
-        )
-        .with_static_gas(gas::staking_pool::STAKE)
-        .stake(amount)
-        .then(
-            ext_self_owner::ext(env::current_account_id())
-                .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_STAKE)
-                .on_staking_pool_stake(amount),
-        )
+        self.set_staking_pool_status(&staking_pool_account_id, TransactionStatus::Busy);
+
+        ext_staking_pool::ext(staking_pool_account_id.clone())
+            .with_static_gas(gas::staking_pool::STAKE)
+            .stake(amount)
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_STAKE)
+                    .on_staking_pool_stake(staking_pool_account_id, amount),
+            )
     }
 
     /// OWNER'S METHOD
@@ -328,39 +278,29 @@ impl LockupContract {
     /// Requires 125 TGas (5 * BASE_GAS)
     /// Requires 1 yoctoNEAR attached
     ///
-    /// Unstakes the given amount at the staking pool
+    /// Unstakes the given amount at the given staking pool
     #[payable]
-    pub fn unstake(&mut self, amount: NearToken) -> Promise {
+    pub fn unstake(&mut self, staking_pool_account_id: AccountId, amount: NearToken) -> Promise {
         self.assert_owner();
         assert_one_yocto();
         assert!(amount.as_yoctonear() > 0, "Amount should be positive");
-        self.assert_staking_pool_is_idle();
+        self.assert_staking_pool_is_idle(&staking_pool_account_id);
 
         env::log_str(&format!(
             "Unstaking {} from the staking pool @{}",
-            amount,
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
+            amount, staking_pool_account_id
         ));
 
-        self.set_staking_pool_status(TransactionStatus::Busy);
-
-        ext_staking_pool::ext(
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
-                .clone(),
-        )
-        .with_static_gas(gas::staking_pool::UNSTAKE)
-        .unstake(amount)
-        .then(
-            ext_self_owner::ext(env::current_account_id())
-                .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_UNSTAKE)
-                .on_staking_pool_unstake(amount),
-        )
+        self.set_staking_pool_status(&staking_pool_account_id, TransactionStatus::Busy);
+
+        ext_staking_pool::ext(staking_pool_account_id.clone())
+            .with_static_gas(gas::staking_pool::UNSTAKE)
+            .unstake(amount)
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_UNSTAKE)
+                    .on_staking_pool_unstake(staking_pool_account_id, amount),
+            )
     }
 
     /// OWNER'S METHOD
@@ -368,37 +308,143 @@ impl LockupContract {
     /// Requires 125 TGas (5 * BASE_GAS)
     /// Requires 1 yoctoNEAR attached
     ///
-    /// Unstakes all tokens from the staking pool
+    /// Unstakes all tokens from the given staking pool
     #[payable]
-    pub fn unstake_all(&mut self) -> Promise {
+    pub fn unstake_all(&mut self, staking_pool_account_id: AccountId) -> Promise {
         self.assert_owner();
         assert_one_yocto();
-        self.assert_staking_pool_is_idle();
+        self.assert_staking_pool_is_idle(&staking_pool_account_id);
 
         env::log_str(&format!(
             "Unstaking all tokens from the staking pool @{}",
-            self.staking_information
-                .as_ref()
-                .unwrap()
-                .staking_pool_account_id
+            staking_pool_account_id
         ));
 
-        self.set_staking_pool_status(TransactionStatus::Busy);
-
-        let staking_pool_account_id: AccountId = self
-            .staking_information
-            .as_ref()
-            .unwrap()
-            .staking_pool_account_id
-            .clone();
+        self.set_staking_pool_status(&staking_pool_account_id, TransactionStatus::Busy);
 
-        ext_staking_pool::ext(staking_pool_account_id)
+        ext_staking_pool::ext(staking_pool_account_id.clone())
             .with_static_gas(gas::staking_pool::UNSTAKE_ALL)
             .unstake_all()
             .then(
                 ext_self_owner::ext(env::current_account_id())
                     .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_UNSTAKE_ALL)
-                    .on_staking_pool_unstake_all(),
+                    .on_staking_pool_unstake_all(staking_pool_account_id),
+            )
+    }
+
+    /// OWNER'S METHOD
+    ///
+    /// Requires 50 TGas (2 * BASE_GAS)
+    /// Requires 1 yoctoNEAR attached
+    ///
+    /// Selects liquid staking token contract at the given account ID. The token first has to be
+    /// checked against the staking pool whitelist contract. The `price_method` is the view method
+    /// of the token contract that returns the price of the token in NEAR.
+    /// The lockup account has to be registered with the token contract to receive the tokens.
+    #[payable]
+    pub fn select_liquid_staking_token(
+        &mut self,
+        token_account_id: AccountId,
+        price_method: LstPriceMethod,
+    ) -> Promise {
+        self.assert_owner();
+        assert_one_yocto();
+        self.assert_liquid_staking_token_is_not_selected(&token_account_id);
+        self.assert_can_select_liquid_staking_token();
+        self.assert_not_migrated();
+
+        env::log_str(&format!(
+            "Selecting liquid staking token @{}. Going to check whitelist first.",
+            token_account_id
+        ));
+
+        ext_whitelist::ext(self.staking_pool_whitelist_account_id.clone())
+            .with_static_gas(gas::whitelist::IS_WHITELISTED)
+            .is_whitelisted(token_account_id.clone())
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(gas::owner_callbacks::ON_WHITELIST_IS_WHITELISTED)
+                    .on_whitelist_is_whitelisted_lst(token_account_id, price_method),
+            )
+    }
+
+    /// OWNER'S METHOD
+    ///
+    /// Requires 25 TGas (1 * BASE_GAS)
+    /// Requires 1 yoctoNEAR attached
+    ///
+    /// Unselects the given liquid staking token.
+    /// It requires that there is no balance of the token left.
+    #[payable]
+    pub fn unselect_liquid_staking_token(&mut self, token_account_id: AccountId) {
+        self.assert_owner();
+        assert_one_yocto();
+        self.assert_liquid_staking_token_is_idle(&token_account_id);
+        assert_eq!(
+            self.liquid_staking_tokens[&token_account_id].balance, 0,
+            "There is still a balance of the liquid staking token"
+        );
+
+        env::log_str(&format!(
+            "Unselected liquid staking token @{}",
+            token_account_id
+        ));
+
+        self.liquid_staking_tokens.remove(&token_account_id);
+    }
+
+    /// OWNER'S METHOD
+    ///
+    /// Requires 50 TGas (2 * BASE_GAS)
+    /// Requires 1 yoctoNEAR attached
+    ///
+    /// Transfers the given amount of the liquid staking token to the given receiver account ID.
+    /// The NEAR value of the amount can't exceed the liquid balance, so the locked NEAR remains
+    /// covered.
+    #[payable]
+    pub fn transfer_liquid_staking_token(
+        &mut self,
+        token_account_id: AccountId,
+        amount: U128,
+        receiver_id: AccountId,
+    ) -> Promise {
+        self.assert_owner();
+        assert_one_yocto();
+        assert!(amount.0 > 0, "Amount should be positive");
+        self.assert_liquid_staking_token_is_idle(&token_account_id);
+        let lst_information = &self.liquid_staking_tokens[&token_account_id];
+        assert!(
+            lst_information.balance >= amount.0,
+            "The balance {} is smaller than the requested transfer amount {}",
+            lst_information.balance,
+            amount.0
+        );
+        let near_value = lst_information.near_value(amount.0);
+        assert!(
+            self.venear_liquid_balance() >= near_value,
+            "The available liquid balance {} is smaller than the NEAR value {} of the requested \
+            transfer amount",
+            self.venear_liquid_balance(),
+            near_value
+        );
+
+        env::log_str(&format!(
+            "Transferring {} of @{} to account @{}",
+            amount.0, token_account_id, receiver_id
+        ));
+
+        let lst_information = self.internal_get_liquid_staking_token_mut(&token_account_id);
+        lst_information.balance -= amount.0;
+        lst_information.status = TransactionStatus::Busy;
+
+        ext_liquid_staking_token::ext(token_account_id.clone())
+            .with_attached_deposit(NearToken::from_yoctonear(1))
+            .with_static_gas(gas::liquid_staking_token::FT_TRANSFER)
+            .ft_transfer(receiver_id, amount, None)
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(gas::owner_callbacks::ON_LIQUID_STAKING_TOKEN_TRANSFER)
+                    .on_liquid_staking_token_transfer(token_account_id, amount),
             )
     }
 
@@ -443,7 +489,7 @@ impl LockupContract {
     /// OWNER'S METHOD
     ///
     /// Requires 1 yoctoNEAR attached
-    /// Requires no locked balances or staking pool deposits.
+    /// Requires no locked balances, staking pool deposits or liquid staking tokens.
     ///
     /// Removes the lockup contract and transfers all NEAR to the initial owner.
     #[payable]
@@ -456,13 +502,17 @@ impl LockupContract {
             0,
             "Can't delete account with non-zero staked NEAR balance"
         );
+        assert!(
+            self.liquid_staking_tokens.is_empty(),
+            "Can't delete account with selected liquid staking tokens"
+        );
 
         assert_eq!(
             self.venear_locked_balance, 0,
             "Can't delete account with non-zero locked venear balance"
         );
-        assert_eq!(
-            self.venear_pending_balance, 0,
+        assert!(
+            self.unlock_tranches.is_empty(),
             "Can't delete account with non-zero pending venear balance"
         );
 
@@ -477,4 +527,118 @@ impl LockupContract {
 
         Promise::new(env::current_account_id()).delete_account(self.owner_account_id.clone())
     }
+
+    /// OWNER'S METHOD
+    ///
+    /// Requires 1 yoctoNEAR attached
+    ///
+    /// Proposes the new owner of the lockup contract, or cancels the proposal if the new owner is
+    /// not given. The proposed owner can accept the ownership after the owner transfer delay.
+    #[payable]
+    pub fn propose_owner(&mut self, new_owner_account_id: Option<AccountId>) {
+        self.assert_owner();
+        assert_one_yocto();
+        if let Some(new_owner_account_id) = &new_owner_account_id {
+            assert_ne!(
+                new_owner_account_id, &self.owner_account_id,
+                "The new owner should be different from the current owner"
+            );
+            self.owner_transfer_timestamp = env::block_timestamp() + self.owner_transfer_delay_ns;
+        } else {
+            self.owner_transfer_timestamp = 0;
+        }
+        self.proposed_owner_account_id = new_owner_account_id;
+    }
+
+    /// Requires 1 yoctoNEAR attached
+    /// Requires 55 TGas (BASE_GAS + 30 TGas for veNEAR)
+    /// Requires the owner transfer delay to pass
+    ///
+    /// Accepts the ownership of the lockup contract by the proposed new owner. The veNEAR
+    /// contract moves the account of the current owner to the new owner first, and the ownership
+    /// is transferred if it succeeds. The new owner has to be registered in the veNEAR contract
+    /// without a lockup.
+    #[payable]
+    pub fn accept_owner(&mut self) -> Promise {
+        assert_one_yocto();
+        let new_owner_account_id = env::predecessor_account_id();
+        assert_eq!(
+            self.proposed_owner_account_id.as_ref(),
+            Some(&new_owner_account_id),
+            "Can only be called by the proposed owner"
+        );
+        assert!(
+            env::block_timestamp() >= self.owner_transfer_timestamp,
+            "The owner transfer delay has not passed"
+        );
+
+        ext_venear::ext(self.venear_account_id.clone())
+            .with_static_gas(GAS_FOR_VENEAR_LOCKUP_OWNER_TRANSFER)
+            .on_lockup_owner_transfer(
+                self.version,
+                self.owner_account_id.clone(),
+                new_owner_account_id.clone(),
+            )
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(gas::owner_callbacks::ON_LOCKUP_OWNER_TRANSFER)
+                    .on_lockup_owner_transfer(new_owner_account_id),
+            )
+    }
+
+    /// OWNER'S METHOD
+    ///
+    /// Requires 1 yoctoNEAR attached
+    /// Requires 135 TGas (3 * BASE_GAS + 3 * 20 TGas for veNEAR)
+    /// Requires no selected staking pools or liquid staking tokens.
+    ///
+    /// Migrates the lockup contract to the new lockup contract deployed by the veNEAR contract
+    /// with `deploy_migration_lockup`, without unlocking. The veNEAR contract switches to the new
+    /// lockup contract first and keeps the veNEAR balance, including the extra veNEAR balance.
+    /// Then all NEAR, except the storage deposit, is transferred to the new lockup contract
+    /// together with the locked balance, the pending unlock tranches and the lock commitment.
+    /// If the new lockup contract fails to accept the migration, the lockup contract and the
+    /// veNEAR contract roll back to the state before the migration.
+    /// The staking positions are not migrated. The stake has to be withdrawn from the staking
+    /// pools and the liquid staking tokens have to be unselected first.
+    #[payable]
+    pub fn migrate_lockup(&mut self, new_lockup_account_id: AccountId) -> Promise {
+        self.assert_owner();
+        assert_one_yocto();
+        self.assert_not_migrated();
+        self.assert_no_staking_or_idle();
+        assert!(
+            self.staking_pools.is_empty(),
+            "Can't migrate with selected staking pools"
+        );
+        assert!(
+            self.liquid_staking_tokens.is_empty(),
+            "Can't migrate with selected liquid staking tokens"
+        );
+        assert!(
+            self.get_account_balance().as_yoctonear() >= self.venear_locked_balance,
+            "Not enough balance to migrate the locked NEAR"
+        );
+
+        self.migration_lockup_account_id = Some(new_lockup_account_id.clone());
+        self.lockup_update_nonce += 1;
+
+        ext_venear::ext(self.venear_account_id.clone())
+            .with_static_gas(GAS_FOR_VENEAR_LOCKUP_MIGRATION)
+            .on_lockup_migration(
+                self.version,
+                self.owner_account_id.clone(),
+                LockupMigrationUpdate {
+                    locked_near_balance: NearToken::from_yoctonear(self.venear_locked_balance),
+                    timestamp: env::block_timestamp().into(),
+                    lockup_update_nonce: U64::from(self.lockup_update_nonce),
+                    new_lockup_account_id: new_lockup_account_id.clone(),
+                },
+            )
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(gas::owner_callbacks::ON_LOCKUP_MIGRATION)
+                    .on_lockup_migration(new_lockup_account_id),
+            )
+    }
 }
@@ -1,5 +1,8 @@
+use crate::venear_ext::{ext_venear, GAS_FOR_VENEAR_LOCKUP_MIGRATION};
 use crate::*;
-use near_sdk::{is_promise_success, near, PromiseOrValue};
+use common::events;
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{is_promise_success, near, PromiseError, PromiseOrValue};
 
 #[near]
 impl LockupContract {
@@ -14,57 +17,57 @@ impl LockupContract {
             is_whitelisted,
             "The given staking pool account ID is not whitelisted"
         );
-        self.assert_staking_pool_is_not_selected();
-        self.staking_information = Some(StakingInformation {
+        self.assert_staking_pool_is_not_selected(&staking_pool_account_id);
+        self.assert_can_select_staking_pool();
+        self.assert_not_migrated();
+        self.staking_pools.insert(
             staking_pool_account_id,
-            status: TransactionStatus::Idle,
-            deposit_amount: NearToken::from_yoctonear(0),
-        });
+            StakingInformation {
+                status: TransactionStatus::Idle,
+                deposit_amount: NearToken::from_yoctonear(0),
+            },
+        );
         true
     }
 
     /// Called after a deposit amount was transferred out of this account to the staking pool.
     /// This method needs to update staking pool status.
     #[private]
-    pub fn on_staking_pool_deposit(&mut self, amount: NearToken) -> bool {
+    pub fn on_staking_pool_deposit(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> bool {
         let deposit_succeeded = is_promise_success();
-        self.on_staking_pool_deposit_inner(amount, deposit_succeeded)
+        self.on_staking_pool_deposit_inner(&staking_pool_account_id, amount, deposit_succeeded)
     }
 
     /// Called after a deposit amount was transferred out of this account to the staking pool and it
     /// was staked on the staking pool.
     /// This method needs to update staking pool status.
     #[private]
-    pub fn on_staking_pool_deposit_and_stake(&mut self, amount: NearToken) -> bool {
+    pub fn on_staking_pool_deposit_and_stake(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> bool {
         let deposit_and_stake_succeeded = is_promise_success();
-        self.set_staking_pool_status(TransactionStatus::Idle);
+        self.set_staking_pool_status(&staking_pool_account_id, TransactionStatus::Idle);
 
         if deposit_and_stake_succeeded {
-            self.staking_information.as_mut().unwrap().deposit_amount = NearToken::from_yoctonear(
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .deposit_amount
-                    .as_yoctonear()
-                    + amount.as_yoctonear(),
+            let staking_information = self.internal_get_staking_pool_mut(&staking_pool_account_id);
+            staking_information.deposit_amount = NearToken::from_yoctonear(
+                staking_information.deposit_amount.as_yoctonear() + amount.as_yoctonear(),
             );
 
             env::log_str(&format!(
                 "The deposit and stake of {} to @{} succeeded",
-                amount,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                amount, staking_pool_account_id
             ));
         } else {
             env::log_str(&format!(
                 "The deposit and stake of {} to @{} has failed",
-                amount,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                amount, staking_pool_account_id
             ));
         }
         deposit_and_stake_succeeded
@@ -74,49 +77,55 @@ impl LockupContract {
     /// account.
     /// This method needs to update staking pool status.
     #[private]
-    pub fn on_staking_pool_withdraw(&mut self, amount: NearToken) -> bool {
+    pub fn on_staking_pool_withdraw(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> bool {
         let withdraw_succeeded = is_promise_success();
-        self.on_staking_pool_withdraw_inner(amount, withdraw_succeeded)
+        self.on_staking_pool_withdraw_inner(&staking_pool_account_id, amount, withdraw_succeeded)
     }
 
     /// Called after the extra amount stake was staked in the staking pool contract.
     /// This method needs to update staking pool status.
     #[private]
-    pub fn on_staking_pool_stake(&mut self, amount: NearToken) -> bool {
+    pub fn on_staking_pool_stake(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> bool {
         let stake_succeeded = is_promise_success();
-        self.on_staking_pool_stake_inner(amount, stake_succeeded)
+        self.on_staking_pool_stake_inner(&staking_pool_account_id, amount, stake_succeeded)
     }
 
     /// Called after the given amount was unstaked at the staking pool contract.
     /// This method needs to update staking pool status.
     #[private]
-    pub fn on_staking_pool_unstake(&mut self, amount: NearToken) -> bool {
+    pub fn on_staking_pool_unstake(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: NearToken,
+    ) -> bool {
         let unstake_succeeded = is_promise_success();
-        self.on_staking_pool_unstake_inner(amount, unstake_succeeded)
+        self.on_staking_pool_unstake_inner(&staking_pool_account_id, amount, unstake_succeeded)
     }
 
     /// Called after all tokens were unstaked at the staking pool contract
     /// This method needs to update staking pool status.
     #[private]
-    pub fn on_staking_pool_unstake_all(&mut self) -> bool {
+    pub fn on_staking_pool_unstake_all(&mut self, staking_pool_account_id: AccountId) -> bool {
         let unstake_all_succeeded = is_promise_success();
-        self.set_staking_pool_status(TransactionStatus::Idle);
+        self.set_staking_pool_status(&staking_pool_account_id, TransactionStatus::Idle);
 
         if unstake_all_succeeded {
             env::log_str(&format!(
                 "Unstaking all at @{} succeeded",
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                staking_pool_account_id
             ));
         } else {
             env::log_str(&format!(
                 "Unstaking all at @{} has failed",
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                staking_pool_account_id
             ));
         }
         unstake_all_succeeded
@@ -124,15 +133,50 @@ impl LockupContract {
 
     /// Called after the request to get the current total balance from the staking pool.
     #[private]
-    pub fn on_get_account_total_balance(&mut self, #[callback] total_balance: NearToken) {
-        self.set_staking_pool_status(TransactionStatus::Idle);
+    pub fn on_get_account_total_balance(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        #[callback] total_balance: NearToken,
+    ) {
+        self.on_get_account_total_balance_inner(&staking_pool_account_id, Ok(total_balance));
+    }
 
-        env::log_str(&format!(
-            "The current total balance on the staking pool is {}",
-            total_balance
-        ));
+    /// Called after the request to get the current total balance from the staking pool to lock
+    /// the NEAR. The lock is skipped if the amount is invalid after the refresh.
+    #[private]
+    pub fn on_get_account_total_balance_to_lock(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: Option<NearToken>,
+        #[callback_result] total_balance: Result<NearToken, PromiseError>,
+    ) -> bool {
+        self.on_get_account_total_balance_inner(&staking_pool_account_id, total_balance);
 
-        self.staking_information.as_mut().unwrap().deposit_amount = total_balance;
+        let Some(amount) = self.internal_get_lock_amount(amount) else {
+            env::log_str("The amount to lock exceeds the liquid balance");
+            return false;
+        };
+        self.internal_lock_near(amount);
+        true
+    }
+
+    /// Called after the request to get the current total balance from the staking pool to begin
+    /// unlocking the NEAR. The unlock is skipped if the amount is invalid.
+    #[private]
+    pub fn on_get_account_total_balance_to_begin_unlock(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        amount: Option<NearToken>,
+        #[callback_result] total_balance: Result<NearToken, PromiseError>,
+    ) -> bool {
+        self.on_get_account_total_balance_inner(&staking_pool_account_id, total_balance);
+
+        let Some(amount) = self.internal_get_unlock_amount(amount) else {
+            env::log_str("The amount to unlock is invalid");
+            return false;
+        };
+        self.internal_begin_unlock_near(amount);
+        true
     }
 
     /// Called after the request to get the current unstaked balance to withdraw everything by the
@@ -140,75 +184,357 @@ impl LockupContract {
     #[private]
     pub fn on_get_account_unstaked_balance_to_withdraw_by_owner(
         &mut self,
+        staking_pool_account_id: AccountId,
         #[callback] unstaked_balance: NearToken,
     ) -> PromiseOrValue<bool> {
         if unstaked_balance.as_yoctonear() > 0 {
             // Need to withdraw
             env::log_str(&format!(
                 "Withdrawing {} from the staking pool @{}",
-                unstaked_balance,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                unstaked_balance, staking_pool_account_id
             ));
 
-            ext_staking_pool::ext(
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
-                    .clone(),
-            )
-            .with_static_gas(gas::staking_pool::WITHDRAW)
-            .withdraw(unstaked_balance)
-            .then(
-                ext_self_owner::ext(env::current_account_id())
-                    .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_WITHDRAW)
-                    .on_staking_pool_withdraw(unstaked_balance),
-            )
-            .into()
+            ext_staking_pool::ext(staking_pool_account_id.clone())
+                .with_static_gas(gas::staking_pool::WITHDRAW)
+                .withdraw(unstaked_balance)
+                .then(
+                    ext_self_owner::ext(env::current_account_id())
+                        .with_static_gas(gas::owner_callbacks::ON_STAKING_POOL_WITHDRAW)
+                        .on_staking_pool_withdraw(staking_pool_account_id, unstaked_balance),
+                )
+                .into()
         } else {
             env::log_str("No unstaked balance on the staking pool to withdraw");
-            self.set_staking_pool_status(TransactionStatus::Idle);
+            self.set_staking_pool_status(&staking_pool_account_id, TransactionStatus::Idle);
             PromiseOrValue::Value(true)
         }
     }
+
+    /// Called after a given liquid staking `token_account_id` was checked in the whitelist.
+    #[private]
+    pub fn on_whitelist_is_whitelisted_lst(
+        &mut self,
+        #[callback] is_whitelisted: bool,
+        token_account_id: AccountId,
+        price_method: LstPriceMethod,
+    ) -> bool {
+        assert!(
+            is_whitelisted,
+            "The given liquid staking token account ID is not whitelisted"
+        );
+        self.assert_liquid_staking_token_is_not_selected(&token_account_id);
+        self.assert_can_select_liquid_staking_token();
+        self.assert_not_migrated();
+        self.liquid_staking_tokens.insert(
+            token_account_id,
+            LiquidStakingTokenInformation {
+                status: TransactionStatus::Idle,
+                price_method,
+                balance: 0,
+                price: 0,
+                price_timestamp: 0,
+            },
+        );
+        true
+    }
+
+    /// Called after the request to get the current price of the liquid staking token. Updates
+    /// veNEAR if the token covers a part of the locked amount.
+    #[private]
+    pub fn on_get_liquid_staking_token_price(
+        &mut self,
+        token_account_id: AccountId,
+        #[callback] price: U128,
+    ) {
+        env::log_str(&format!(
+            "The current price of the liquid staking token @{} is {}",
+            token_account_id, price.0
+        ));
+
+        // The token covered a part of the locked amount before the update.
+        let was_locked = self.internal_is_liquid_staking_token_locked(&token_account_id);
+        let lst_information = self.internal_get_liquid_staking_token_mut(&token_account_id);
+        lst_information.price = price.0;
+        lst_information.price_timestamp = env::block_timestamp();
+
+        if self.migration_lockup_account_id.is_none()
+            && (was_locked || self.internal_is_liquid_staking_token_locked(&token_account_id))
+        {
+            self.venear_lockup_update();
+        }
+    }
+
+    /// Called after the given amount of the liquid staking token was transferred out of this
+    /// account. The balance is restored if the transfer failed.
+    #[private]
+    pub fn on_liquid_staking_token_transfer(
+        &mut self,
+        token_account_id: AccountId,
+        amount: U128,
+    ) -> bool {
+        let transfer_succeeded = is_promise_success();
+        self.on_liquid_staking_token_transfer_inner(&token_account_id, amount, transfer_succeeded)
+    }
+
+    /// Called after the request to get the current total balance from the staking pool to lock
+    /// the staking rewards. The known deposit is only refreshed if the staking pool is idle and its
+    /// known deposit is still the given `deposit_amount`. Returns the amount of NEAR locked.
+    #[private]
+    pub fn on_get_account_total_balance_to_sync_rewards(
+        &mut self,
+        staking_pool_account_id: AccountId,
+        deposit_amount: NearToken,
+        #[callback_result] total_balance: Result<NearToken, PromiseError>,
+    ) -> NearToken {
+        let Ok(total_balance) = total_balance else {
+            env::log_str(&format!(
+                "Fetching total balance from the staking pool @{} has failed",
+                staking_pool_account_id
+            ));
+            return NearToken::from_yoctonear(0);
+        };
+        let Some(staking_information) = self.staking_pools.get_mut(&staking_pool_account_id) else {
+            env::log_str("The staking pool is not selected anymore");
+            return NearToken::from_yoctonear(0);
+        };
+        if matches!(staking_information.status, TransactionStatus::Busy)
+            || staking_information.deposit_amount != deposit_amount
+        {
+            env::log_str("The staking pool was updated since the sync started");
+            return NearToken::from_yoctonear(0);
+        }
+        env::log_str(&format!(
+            "The current total balance on the staking pool @{} is {}",
+            staking_pool_account_id, total_balance
+        ));
+        staking_information.deposit_amount = total_balance;
+
+        let rewards = total_balance.saturating_sub(deposit_amount);
+        let amount = std::cmp::min(rewards.as_yoctonear(), self.venear_liquid_balance());
+        if amount == 0 || !self.auto_lock_rewards {
+            env::log_str("No staking rewards to lock");
+            return NearToken::from_yoctonear(0);
+        }
+        self.internal_lock_near(amount);
+        NearToken::from_yoctonear(amount)
+    }
+
+    /// Called after the veNEAR contract moved the account to the new owner. Transfers the
+    /// ownership if the veNEAR contract succeeded. Returns whether the ownership was transferred.
+    #[private]
+    pub fn on_lockup_owner_transfer(&mut self, new_owner_account_id: AccountId) -> bool {
+        let transfer_succeeded = is_promise_success();
+        self.on_lockup_owner_transfer_inner(new_owner_account_id, transfer_succeeded)
+    }
+
+    /// Called after the veNEAR contract switched to the new lockup contract. Transfers the NEAR
+    /// and the locked balance to the new lockup contract if the veNEAR contract succeeded.
+    /// Returns whether the lockup contract was migrated.
+    #[private]
+    pub fn on_lockup_migration(&mut self, new_lockup_account_id: AccountId) -> bool {
+        let migration_succeeded = is_promise_success();
+        self.on_lockup_migration_inner(new_lockup_account_id, migration_succeeded)
+    }
+
+    /// Called after the new lockup contract was asked to accept the migration. If it failed,
+    /// restores the locked balance, the pending unlock tranches and the lock commitment, and
+    /// switches the veNEAR contract back to this lockup contract. The transferred NEAR is refunded
+    /// by the failed call. Returns whether the migration was accepted.
+    #[private]
+    pub fn on_accept_lockup_migration(
+        &mut self,
+        new_lockup_account_id: AccountId,
+        locked_balance: NearToken,
+        unlock_tranches: Vec<UnlockTranche>,
+        lock_duration_ns: U64,
+        lock_end_timestamp: U64,
+    ) -> bool {
+        let accept_succeeded = is_promise_success();
+        self.on_accept_lockup_migration_inner(
+            new_lockup_account_id,
+            locked_balance,
+            unlock_tranches,
+            lock_duration_ns,
+            lock_end_timestamp,
+            accept_succeeded,
+        )
+    }
 }
 
 impl LockupContract {
+    pub fn on_lockup_owner_transfer_inner(
+        &mut self,
+        new_owner_account_id: AccountId,
+        transfer_succeeded: bool,
+    ) -> bool {
+        if !transfer_succeeded {
+            env::log_str(&format!(
+                "The ownership transfer to @{} has failed",
+                new_owner_account_id
+            ));
+            return false;
+        }
+        env::log_str(&format!(
+            "The ownership was transferred from @{} to @{}",
+            self.owner_account_id, new_owner_account_id
+        ));
+        events::emit::lockup_action(
+            "lockup_owner_transfer",
+            &new_owner_account_id,
+            self.version,
+            &Some(U64::from(self.lockup_update_nonce)),
+            &Some(U64::from(env::block_timestamp())),
+            &None,
+        );
+        self.owner_account_id = new_owner_account_id;
+        if self.proposed_owner_account_id.as_ref() == Some(&self.owner_account_id) {
+            self.proposed_owner_account_id = None;
+            self.owner_transfer_timestamp = 0;
+        }
+        true
+    }
+
+    pub fn on_lockup_migration_inner(
+        &mut self,
+        new_lockup_account_id: AccountId,
+        migration_succeeded: bool,
+    ) -> bool {
+        if !migration_succeeded {
+            env::log_str(&format!(
+                "The migration to the lockup @{} has failed",
+                new_lockup_account_id
+            ));
+            self.migration_lockup_account_id = None;
+            return false;
+        }
+        let amount = self.get_account_balance();
+        env::log_str(&format!(
+            "Migrating {} to the lockup @{}",
+            amount, new_lockup_account_id
+        ));
+        events::emit::lockup_action(
+            "lockup_migration",
+            &self.owner_account_id,
+            self.version,
+            &Some(U64::from(self.lockup_update_nonce)),
+            &Some(U64::from(env::block_timestamp())),
+            &Some(NearToken::from_yoctonear(self.venear_locked_balance)),
+        );
+
+        let locked_balance = NearToken::from_yoctonear(self.venear_locked_balance);
+        let unlock_tranches = std::mem::take(&mut self.unlock_tranches);
+        ext_lockup::ext(new_lockup_account_id.clone())
+            .with_attached_deposit(amount)
+            .with_static_gas(gas::lockup::ACCEPT_LOCKUP_MIGRATION)
+            .accept_lockup_migration(
+                locked_balance,
+                unlock_tranches.clone(),
+                self.lock_duration_ns.into(),
+                self.lock_end_timestamp.into(),
+            )
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(gas::owner_callbacks::ON_ACCEPT_LOCKUP_MIGRATION)
+                    .on_accept_lockup_migration(
+                        new_lockup_account_id,
+                        locked_balance,
+                        unlock_tranches,
+                        self.lock_duration_ns.into(),
+                        self.lock_end_timestamp.into(),
+                    ),
+            );
+        self.venear_locked_balance = 0;
+        self.lock_duration_ns = 0;
+        self.lock_end_timestamp = 0;
+        self.proposed_owner_account_id = None;
+        self.owner_transfer_timestamp = 0;
+        true
+    }
+
+    pub fn on_accept_lockup_migration_inner(
+        &mut self,
+        new_lockup_account_id: AccountId,
+        locked_balance: NearToken,
+        unlock_tranches: Vec<UnlockTranche>,
+        lock_duration_ns: U64,
+        lock_end_timestamp: U64,
+        accept_succeeded: bool,
+    ) -> bool {
+        if accept_succeeded {
+            env::log_str(&format!(
+                "The lockup @{} accepted the migration",
+                new_lockup_account_id
+            ));
+            return true;
+        }
+        env::log_str(&format!(
+            "The lockup @{} failed to accept the migration",
+            new_lockup_account_id
+        ));
+        self.migration_lockup_account_id = None;
+        self.venear_locked_balance = locked_balance.as_yoctonear();
+        self.unlock_tranches = unlock_tranches;
+        self.lock_duration_ns = lock_duration_ns.into();
+        self.lock_end_timestamp = lock_end_timestamp.into();
+        self.lockup_update_nonce += 1;
+
+        ext_venear::ext(self.venear_account_id.clone())
+            .with_static_gas(GAS_FOR_VENEAR_LOCKUP_MIGRATION)
+            .on_lockup_migration_failed(
+                self.version,
+                self.owner_account_id.clone(),
+                U64::from(self.lockup_update_nonce),
+            );
+        false
+    }
+
+    pub fn on_get_account_total_balance_inner(
+        &mut self,
+        staking_pool_account_id: &AccountId,
+        total_balance: Result<NearToken, PromiseError>,
+    ) {
+        self.set_staking_pool_status(staking_pool_account_id, TransactionStatus::Idle);
+
+        match total_balance {
+            Ok(total_balance) => {
+                env::log_str(&format!(
+                    "The current total balance on the staking pool @{} is {}",
+                    staking_pool_account_id, total_balance
+                ));
+
+                self.internal_get_staking_pool_mut(staking_pool_account_id)
+                    .deposit_amount = total_balance;
+            }
+            Err(_) => {
+                env::log_str(&format!(
+                    "Fetching total balance from the staking pool @{} has failed",
+                    staking_pool_account_id
+                ));
+            }
+        }
+    }
+
     pub fn on_staking_pool_deposit_inner(
         &mut self,
+        staking_pool_account_id: &AccountId,
         amount: NearToken,
         deposit_succeeded: bool,
     ) -> bool {
-        self.set_staking_pool_status(TransactionStatus::Idle);
+        self.set_staking_pool_status(staking_pool_account_id, TransactionStatus::Idle);
 
         if deposit_succeeded {
-            self.staking_information.as_mut().unwrap().deposit_amount = NearToken::from_yoctonear(
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .deposit_amount
-                    .as_yoctonear()
-                    + amount.as_yoctonear(),
+            let staking_information = self.internal_get_staking_pool_mut(staking_pool_account_id);
+            staking_information.deposit_amount = NearToken::from_yoctonear(
+                staking_information.deposit_amount.as_yoctonear() + amount.as_yoctonear(),
             );
             env::log_str(&format!(
                 "The deposit of {} to @{} succeeded",
-                amount,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                amount, staking_pool_account_id
             ));
         } else {
             env::log_str(&format!(
                 "The deposit of {} to @{} has failed",
-                amount,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                amount, staking_pool_account_id
             ));
         }
         deposit_succeeded
@@ -216,28 +542,21 @@ impl LockupContract {
 
     pub fn on_staking_pool_stake_inner(
         &mut self,
+        staking_pool_account_id: &AccountId,
         amount: NearToken,
         stake_succeeded: bool,
     ) -> bool {
-        self.set_staking_pool_status(TransactionStatus::Idle);
+        self.set_staking_pool_status(staking_pool_account_id, TransactionStatus::Idle);
 
         if stake_succeeded {
             env::log_str(&format!(
                 "Staking of {} at @{} succeeded",
-                amount,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                amount, staking_pool_account_id
             ));
         } else {
             env::log_str(&format!(
                 "Staking {} at @{} has failed",
-                amount,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                amount, staking_pool_account_id
             ));
         }
         stake_succeeded
@@ -245,28 +564,21 @@ impl LockupContract {
 
     pub fn on_staking_pool_unstake_inner(
         &mut self,
+        staking_pool_account_id: &AccountId,
         amount: NearToken,
         unstake_succeeded: bool,
     ) -> bool {
-        self.set_staking_pool_status(TransactionStatus::Idle);
+        self.set_staking_pool_status(staking_pool_account_id, TransactionStatus::Idle);
 
         if unstake_succeeded {
             env::log_str(&format!(
                 "Unstaking of {} at @{} succeeded",
-                amount,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                amount, staking_pool_account_id
             ));
         } else {
             env::log_str(&format!(
                 "Unstaking {} at @{} has failed",
-                amount,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                amount, staking_pool_account_id
             ));
         }
         unstake_succeeded
@@ -274,40 +586,55 @@ impl LockupContract {
 
     pub fn on_staking_pool_withdraw_inner(
         &mut self,
+        staking_pool_account_id: &AccountId,
         amount: NearToken,
         withdraw_succeeded: bool,
     ) -> bool {
-        self.set_staking_pool_status(TransactionStatus::Idle);
+        self.set_staking_pool_status(staking_pool_account_id, TransactionStatus::Idle);
 
         if withdraw_succeeded {
-            {
-                let staking_information = self.staking_information.as_mut().unwrap();
-                // Due to staking rewards the deposit amount can become negative.
-                staking_information.deposit_amount = NearToken::from_yoctonear(
-                    staking_information
-                        .deposit_amount
-                        .as_yoctonear()
-                        .saturating_sub(amount.as_yoctonear()),
-                );
-            }
+            let staking_information = self.internal_get_staking_pool_mut(staking_pool_account_id);
+            // Due to staking rewards the deposit amount can become negative.
+            staking_information.deposit_amount = NearToken::from_yoctonear(
+                staking_information
+                    .deposit_amount
+                    .as_yoctonear()
+                    .saturating_sub(amount.as_yoctonear()),
+            );
             env::log_str(&format!(
                 "The withdrawal of {} from @{} succeeded",
-                amount,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                amount, staking_pool_account_id
             ));
         } else {
             env::log_str(&format!(
                 "The withdrawal of {} from @{} failed",
-                amount,
-                self.staking_information
-                    .as_ref()
-                    .unwrap()
-                    .staking_pool_account_id
+                amount, staking_pool_account_id
             ));
         }
         withdraw_succeeded
     }
+
+    pub fn on_liquid_staking_token_transfer_inner(
+        &mut self,
+        token_account_id: &AccountId,
+        amount: U128,
+        transfer_succeeded: bool,
+    ) -> bool {
+        let lst_information = self.internal_get_liquid_staking_token_mut(token_account_id);
+        lst_information.status = TransactionStatus::Idle;
+
+        if transfer_succeeded {
+            env::log_str(&format!(
+                "The transfer of {} of @{} succeeded",
+                amount.0, token_account_id
+            ));
+        } else {
+            lst_information.balance += amount.0;
+            env::log_str(&format!(
+                "The transfer of {} of @{} has failed",
+                amount.0, token_account_id
+            ));
+        }
+        transfer_succeeded
+    }
 }
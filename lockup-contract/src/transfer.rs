@@ -1,22 +1,30 @@
 use crate::*;
 use near_sdk::json_types::U128;
-use near_sdk::serde_json;
-use near_sdk::{env, require};
+use near_sdk::{near, PromiseOrValue};
 
-#[cfg(target_arch = "wasm32")]
-#[warn(unused_variables)]
-#[no_mangle]
-pub extern "C" fn ft_on_transfer() {
-    env::setup_panic_hook();
-    let contract: LockupContract = env::state_read().unwrap();
-    require!(
-        Some(&env::predecessor_account_id())
-            == contract
-                .staking_information
-                .as_ref()
-                .map(|s| &s.staking_pool_account_id),
-        "Only currently selected LST is accepted"
-    );
+#[near]
+impl LockupContract {
+    /// Called by one of the selected liquid staking token contracts when the tokens are
+    /// transferred to this account. All the tokens are accepted and added to the balance.
+    #[allow(unused_variables)]
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let token_account_id = env::predecessor_account_id();
+        let lst_information = self
+            .liquid_staking_tokens
+            .get_mut(&token_account_id)
+            .expect("Only currently selected LST is accepted");
+        lst_information.balance += amount.0;
 
-    env::value_return(&serde_json::to_vec(&U128::from(0u128)).unwrap())
+        env::log_str(&format!(
+            "Received {} of @{} from @{}",
+            amount.0, token_account_id, sender_id
+        ));
+
+        PromiseOrValue::Value(U128(0))
+    }
 }
@@ -30,12 +30,20 @@ pub enum TransactionStatus {
     Busy,
 }
 
-/// Contains information about current stake and delegation.
+/// The amount of NEAR that is pending to be unlocked at the given time.
+#[derive(Clone)]
+#[near(serializers=[borsh, json])]
+pub struct UnlockTranche {
+    /// The amount of NEAR that is pending to be unlocked.
+    pub amount: NearToken,
+
+    /// The timestamp in nanoseconds when the amount can be unlocked.
+    pub unlock_timestamp: WrappedTimestamp,
+}
+
+/// Contains information about the stake in one of the selected staking pools.
 #[near(serializers=[borsh])]
 pub struct StakingInformation {
-    /// The Account ID of the staking pool contract.
-    pub staking_pool_account_id: AccountId,
-
     /// Contains status whether there is a transaction in progress.
     pub status: TransactionStatus,
 
@@ -43,3 +51,34 @@ pub struct StakingInformation {
     /// NOTE: The unstaked amount on the staking pool might be higher due to staking rewards.
     pub deposit_amount: NearToken,
 }
+
+/// The view method of the liquid staking token contract that returns the price of one token
+/// (10^24 units) in yoctoNEAR.
+#[derive(Clone, Copy)]
+#[near(serializers=[borsh, json])]
+pub enum LstPriceMethod {
+    /// `ft_price`, e.g. LiNEAR.
+    FtPrice,
+    /// `get_st_near_price`, e.g. stNEAR.
+    GetStNearPrice,
+}
+
+/// Contains information about the balance of one of the selected liquid staking tokens.
+#[near(serializers=[borsh])]
+pub struct LiquidStakingTokenInformation {
+    /// Contains status whether there is a transaction in progress.
+    pub status: TransactionStatus,
+
+    /// The method to query the price of the token.
+    pub price_method: LstPriceMethod,
+
+    /// The amount of tokens that were transferred to this account.
+    pub balance: Balance,
+
+    /// The price of one token in yoctoNEAR at the last refresh. It's 0 until the price is
+    /// refreshed.
+    pub price: Balance,
+
+    /// The timestamp in nanoseconds of the last price refresh.
+    pub price_timestamp: Timestamp,
+}
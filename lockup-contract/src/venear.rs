@@ -1,6 +1,6 @@
 use crate::venear_ext::{ext_venear, GAS_FOR_VENEAR_LOCKUP_UPDATE};
 use crate::*;
-use common::lockup_update::{LockupUpdateV1, VLockupUpdate};
+use common::lockup_update::{FungibleTokenBalance, LockupUpdateV3, VLockupUpdate};
 use common::{events, TimestampNs};
 use near_sdk::json_types::U64;
 use near_sdk::{assert_one_yocto, near, NearToken, Promise};
@@ -11,20 +11,159 @@ impl LockupContract {
         // total available NEAR (including tokens on a lockup and staked in pools)
         let total_near_balance: u128 = env::account_balance().as_yoctonear()
             + self.get_known_deposited_balance().as_yoctonear();
+        // The NEAR value of liquid staking tokens can go below the locked amount, if the price
+        // goes down.
+        let total_balance: u128 =
+            total_near_balance + self.get_liquid_staking_tokens_near_balance().as_yoctonear();
 
-        total_near_balance
+        total_balance
             .checked_sub(self.venear_locked_balance)
-            .expect("Illegal balance")
-            .checked_sub(self.venear_pending_balance)
-            .expect("Illegal balance")
+            .and_then(|balance| balance.checked_sub(self.internal_get_pending_balance()))
+            .unwrap_or_else(|| {
+                require!(total_near_balance < total_balance, "Illegal balance");
+                0
+            })
     }
 
-    fn set_venear_unlock_timestamp(&mut self) {
-        self.venear_unlock_timestamp = env::block_timestamp() + self.unlock_duration_ns;
+    /// Returns whether the given liquid staking token covers a part of the locked amount.
+    pub(crate) fn internal_is_liquid_staking_token_locked(
+        &self,
+        token_account_id: &AccountId,
+    ) -> bool {
+        let (_, fungible_tokens) = self.internal_get_locked_balances();
+        fungible_tokens
+            .iter()
+            .any(|fungible_token| &fungible_token.token_account_id == token_account_id)
+    }
+
+    /// Splits the locked amount into the part covered by NEAR and the locked balances of the
+    /// liquid staking tokens covering the rest. The locked amount is covered by NEAR first.
+    pub(crate) fn internal_get_locked_balances(&self) -> (Balance, Vec<FungibleTokenBalance>) {
+        let total_near_balance: u128 = env::account_balance().as_yoctonear()
+            + self.get_known_deposited_balance().as_yoctonear();
+        let locked_near_balance = std::cmp::min(self.venear_locked_balance, total_near_balance);
+        let mut remaining_balance = self.venear_locked_balance - locked_near_balance;
+        let mut fungible_tokens = vec![];
+        for (token_account_id, lst_information) in self.liquid_staking_tokens.iter() {
+            if remaining_balance == 0 {
+                break;
+            }
+            let near_value = lst_information.near_value(lst_information.balance);
+            let locked_near_value = std::cmp::min(near_value, remaining_balance);
+            if locked_near_value == 0 {
+                continue;
+            }
+            remaining_balance -= locked_near_value;
+            let locked_amount = (U256::from(lst_information.balance)
+                * U256::from(locked_near_value)
+                / U256::from(near_value))
+            .as_u128();
+            fungible_tokens.push(FungibleTokenBalance {
+                token_account_id: token_account_id.clone(),
+                balance: locked_amount.into(),
+                near_balance: NearToken::from_yoctonear(locked_near_value),
+            });
+        }
+        (locked_near_balance, fungible_tokens)
+    }
+
+    /// Returns the amount of NEAR to lock, or `None` if the amount exceeds the liquid balance.
+    pub(crate) fn internal_get_lock_amount(&self, amount: Option<NearToken>) -> Option<Balance> {
+        let liquid_balance = self.venear_liquid_balance();
+        let amount = amount.map_or(liquid_balance, |amount| amount.as_yoctonear());
+        Some(amount).filter(|&amount| amount <= liquid_balance)
+    }
+
+    /// Returns the amount of NEAR to unlock, or `None` if the amount is zero or exceeds the locked
+    /// balance, there are too many unlock tranches or the lock commitment has not ended.
+    pub(crate) fn internal_get_unlock_amount(&self, amount: Option<NearToken>) -> Option<Balance> {
+        if self.unlock_tranches.len() >= MAX_UNLOCK_TRANCHES || self.is_lock_committed() {
+            return None;
+        }
+        let amount = amount.map_or(self.venear_locked_balance, |amount| amount.as_yoctonear());
+        Some(amount).filter(|&amount| amount > 0 && amount <= self.venear_locked_balance)
+    }
+
+    /// Returns whether the lock commitment is active, i.e. the locked NEAR can't be unlocked yet.
+    pub(crate) fn is_lock_committed(&self) -> bool {
+        env::block_timestamp() < self.lock_end_timestamp
+    }
+
+    /// Returns the total amount of NEAR that is pending to be unlocked.
+    pub(crate) fn internal_get_pending_balance(&self) -> Balance {
+        self.unlock_tranches
+            .iter()
+            .map(|tranche| tranche.amount.as_yoctonear())
+            .sum()
+    }
+
+    /// Returns the amount of NEAR that is pending to be unlocked and the unlock timestamp is
+    /// reached.
+    pub(crate) fn internal_get_unlockable_balance(&self) -> Balance {
+        let current_timestamp = env::block_timestamp();
+        self.unlock_tranches
+            .iter()
+            .filter(|tranche| tranche.unlock_timestamp.0 <= current_timestamp)
+            .map(|tranche| tranche.amount.as_yoctonear())
+            .sum()
+    }
+
+    /// Releases the given amount from the unlock tranches that reached the unlock timestamp,
+    /// starting from the oldest one.
+    pub(crate) fn internal_end_unlock_near(&mut self, amount: Balance) {
+        let mut remaining_amount = amount;
+        while remaining_amount > 0 {
+            let tranche = self.unlock_tranches.first_mut().expect("Invalid amount");
+            assert!(
+                tranche.unlock_timestamp.0 <= env::block_timestamp(),
+                "Invalid unlock time"
+            );
+            let tranche_amount = tranche.amount.as_yoctonear();
+            if tranche_amount > remaining_amount {
+                tranche.amount = NearToken::from_yoctonear(tranche_amount - remaining_amount);
+                remaining_amount = 0;
+            } else {
+                self.unlock_tranches.remove(0);
+                remaining_amount -= tranche_amount;
+            }
+        }
+
+        self.venear_lockup_update();
+    }
+
+    pub(crate) fn internal_lock_near(&mut self, amount: Balance) {
+        self.venear_locked_balance += amount;
+
+        events::emit::lockup_action(
+            "lockup_lock_near",
+            &(env::current_account_id()),
+            self.version,
+            &Some(U64::from(self.lockup_update_nonce)),
+            &Some(U64::from(env::block_timestamp())),
+            &Some(NearToken::from_yoctonear(amount)),
+        );
+
+        self.venear_lockup_update();
+    }
+
+    pub(crate) fn internal_begin_unlock_near(&mut self, amount: Balance) {
+        self.venear_locked_balance -= amount;
+        self.unlock_tranches.push(UnlockTranche {
+            amount: NearToken::from_yoctonear(amount),
+            unlock_timestamp: (env::block_timestamp() + self.unlock_duration_ns).into(),
+        });
+
+        self.venear_lockup_update();
     }
 
     pub fn venear_lockup_update(&mut self) -> Promise {
         self.lockup_update_nonce += 1;
+        let (locked_near_balance, fungible_tokens) = self.internal_get_locked_balances();
+        let lock_end_timestamp = if self.is_lock_committed() {
+            self.lock_end_timestamp
+        } else {
+            0
+        };
 
         // Calls veNEAR with new total NEAR balance locked in the lockup
         ext_venear::ext(self.venear_account_id.clone())
@@ -32,10 +171,12 @@ impl LockupContract {
             .on_lockup_update(
                 self.version,
                 self.owner_account_id.clone(),
-                VLockupUpdate::V1(LockupUpdateV1 {
-                    locked_near_balance: self.get_venear_locked_balance(),
+                VLockupUpdate::V3(LockupUpdateV3 {
+                    locked_near_balance: NearToken::from_yoctonear(locked_near_balance),
                     timestamp: env::block_timestamp().into(),
                     lockup_update_nonce: U64::from(self.lockup_update_nonce),
+                    fungible_tokens,
+                    lock_end_timestamp: lock_end_timestamp.into(),
                 }),
             )
     }
@@ -48,9 +189,12 @@ impl LockupContract {
         NearToken::from_yoctonear(self.venear_locked_balance)
     }
 
-    /// Returns the timestamp in nanoseconds when the pending amount will be unlocked
+    /// Returns the timestamp in nanoseconds when all the pending amount will be unlocked, or 0 if
+    /// there is no pending amount.
     pub fn get_venear_unlock_timestamp(&self) -> TimestampNs {
-        self.venear_unlock_timestamp.into()
+        self.unlock_tranches
+            .last()
+            .map_or(0.into(), |tranche| tranche.unlock_timestamp)
     }
 
     /// Returns the nonce of the lockup update
@@ -60,7 +204,28 @@ impl LockupContract {
 
     /// Returns the amount of NEAR that is pending to be unlocked
     pub fn get_venear_pending_balance(&self) -> NearToken {
-        NearToken::from_yoctonear(self.venear_pending_balance)
+        NearToken::from_yoctonear(self.internal_get_pending_balance())
+    }
+
+    /// Returns the amount of NEAR that is pending to be unlocked and can be unlocked now
+    pub fn get_venear_unlockable_balance(&self) -> NearToken {
+        NearToken::from_yoctonear(self.internal_get_unlockable_balance())
+    }
+
+    /// Returns the pending unlock tranches ordered by the unlock timestamp
+    pub fn get_unlock_tranches(&self) -> Vec<UnlockTranche> {
+        self.unlock_tranches.clone()
+    }
+
+    /// Returns the duration in nanoseconds of the lock commitment, or 0 if there is no commitment
+    pub fn get_lock_duration_ns(&self) -> U64 {
+        self.lock_duration_ns.into()
+    }
+
+    /// Returns the timestamp in nanoseconds when the lock commitment ends, or 0 if there is no
+    /// commitment
+    pub fn get_lock_end_timestamp(&self) -> TimestampNs {
+        self.lock_end_timestamp.into()
     }
 
     /// Returns the amount of NEAR that is liquid (the NEAR that can be locked)
@@ -71,90 +236,294 @@ impl LockupContract {
     /// OWNER'S METHOD
     ///
     /// Requires 1 yoctoNEAR attached
+    /// Requires 75 TGas (3 * BASE_GAS) + 20 TGas when refreshing the staking pool balance
     ///
     /// Locks the NEAR in the lockup contract.
     /// You can specify the amount of NEAR to lock, or if you don't specify it, all the liquid NEAR
     /// will be locked.
+    /// If `refresh_staking_pool_account_id` is given, the total balance of the staking pool is
+    /// refreshed first, so the staking rewards can be locked in the same transaction. In this
+    /// case, the amount is checked after the refresh and the lock is skipped if it's invalid.
     #[payable]
-    pub fn lock_near(&mut self, amount: Option<NearToken>) {
+    pub fn lock_near(
+        &mut self,
+        amount: Option<NearToken>,
+        refresh_staking_pool_account_id: Option<AccountId>,
+    ) {
         self.assert_owner();
         assert_one_yocto();
-        let amount: Balance = if let Some(amount) = amount {
-            amount.as_yoctonear()
-        } else {
-            self.venear_liquid_balance()
-        };
+        if let Some(staking_pool_account_id) = refresh_staking_pool_account_id {
+            self.internal_get_staking_pool_total_balance(&staking_pool_account_id)
+                .then(
+                    ext_self_owner::ext(env::current_account_id())
+                        .with_static_gas(gas::owner_callbacks::ON_GET_ACCOUNT_TOTAL_BALANCE_TO_LOCK)
+                        .on_get_account_total_balance_to_lock(staking_pool_account_id, amount),
+                );
+            return;
+        }
 
-        assert!(amount <= self.venear_liquid_balance(), "Invalid amount");
+        let amount = self
+            .internal_get_lock_amount(amount)
+            .expect("Invalid amount");
+        self.internal_lock_near(amount);
+    }
 
-        self.venear_locked_balance += amount;
+    /// Requires 45 TGas (1 * BASE_GAS + 20 TGas for veNEAR)
+    /// Requires to be called by the lockup contract that migrates to this lockup contract
+    ///
+    /// Accepts the NEAR attached by the previous lockup contract during the migration, together
+    /// with its locked balance, pending unlock tranches and lock commitment, and updates veNEAR
+    /// with the locked balance. The attached NEAR has to cover the locked balance. Can only be
+    /// called once.
+    #[payable]
+    pub fn accept_lockup_migration(
+        &mut self,
+        locked_balance: NearToken,
+        unlock_tranches: Vec<UnlockTranche>,
+        lock_duration_ns: U64,
+        lock_end_timestamp: TimestampNs,
+    ) {
+        assert_eq!(
+            self.previous_lockup_account_id.take(),
+            Some(env::predecessor_account_id()),
+            "Can only be called by the previous lockup contract"
+        );
+        assert!(
+            env::attached_deposit() >= locked_balance,
+            "The attached deposit doesn't cover the locked balance"
+        );
+        env::log_str(&format!(
+            "Received {} from the lockup @{}",
+            env::attached_deposit(),
+            env::predecessor_account_id()
+        ));
 
-        events::emit::lockup_action(
-            "lockup_lock_near".as_ref(),
-            &(env::current_account_id()),
-            self.version,
-            &Some(U64::from(self.lockup_update_nonce)),
-            &Some(U64::from(env::block_timestamp())),
-            &Some(NearToken::from_yoctonear(amount)),
+        self.venear_locked_balance += locked_balance.as_yoctonear();
+        self.unlock_tranches.extend(unlock_tranches);
+        self.unlock_tranches
+            .sort_by_key(|tranche| tranche.unlock_timestamp.0);
+        if lock_end_timestamp.0 > self.lock_end_timestamp {
+            self.lock_duration_ns = lock_duration_ns.into();
+            self.lock_end_timestamp = lock_end_timestamp.into();
+        }
+
+        self.venear_lockup_update();
+    }
+
+    /// OWNER'S METHOD
+    ///
+    /// Requires 1 yoctoNEAR attached
+    ///
+    /// Commits the locked NEAR for the given duration starting now. The locked NEAR can't be
+    /// unlocked until the commitment ends, and veNEAR boosts the locked balance based on the
+    /// committed duration. The NEAR locked later is also covered by the commitment.
+    /// The duration has to be between 90 days and 4 years, and the commitment can only be
+    /// extended, i.e. the new end can't be earlier than the current one.
+    #[payable]
+    pub fn commit_lock(&mut self, lock_duration_ns: U64) {
+        self.assert_owner();
+        assert_one_yocto();
+        let lock_duration_ns: Duration = lock_duration_ns.into();
+        assert!(
+            (MIN_LOCK_DURATION_NS..=MAX_LOCK_DURATION_NS).contains(&lock_duration_ns),
+            "Invalid lock duration"
+        );
+        let lock_end_timestamp = env::block_timestamp() + lock_duration_ns;
+        assert!(
+            lock_end_timestamp >= self.lock_end_timestamp,
+            "The lock commitment can't be shortened"
+        );
+        self.lock_duration_ns = lock_duration_ns;
+        self.lock_end_timestamp = lock_end_timestamp;
+
+        self.venear_lockup_update();
+    }
+
+    /// Requires the lock commitment to be ended
+    ///
+    /// Clears the ended lock commitment and updates veNEAR to remove the boost. Can be called by
+    /// anyone.
+    pub fn end_lock_commitment(&mut self) {
+        assert!(self.lock_end_timestamp > 0, "No lock commitment");
+        assert!(
+            !self.is_lock_committed(),
+            "The lock commitment has not ended"
         );
+        self.lock_duration_ns = 0;
+        self.lock_end_timestamp = 0;
 
         self.venear_lockup_update();
     }
 
+    /// Requires 70 TGas (2 * BASE_GAS + 20 TGas for veNEAR)
+    ///
+    /// Retrieves the price of the given liquid staking token and remembers it internally.
+    /// The NEAR value of the token balance at this price can be locked. If the token covers a part
+    /// of the locked amount, veNEAR is updated with the new value. Can be called by anyone, so the
+    /// locked value follows the price in both directions.
+    pub fn refresh_liquid_staking_token_price(&mut self, token_account_id: AccountId) -> Promise {
+        let price_method = self
+            .internal_get_liquid_staking_token_mut(&token_account_id)
+            .price_method;
+
+        env::log_str(&format!(
+            "Fetching the price of the liquid staking token @{}",
+            token_account_id
+        ));
+
+        let ext = ext_liquid_staking_token::ext(token_account_id.clone())
+            .with_static_gas(gas::liquid_staking_token::GET_PRICE);
+        match price_method {
+            LstPriceMethod::FtPrice => ext.ft_price(),
+            LstPriceMethod::GetStNearPrice => ext.get_st_near_price(),
+        }
+        .then(
+            ext_self_owner::ext(env::current_account_id())
+                .with_static_gas(gas::owner_callbacks::ON_GET_LIQUID_STAKING_TOKEN_PRICE)
+                .on_get_liquid_staking_token_price(token_account_id),
+        )
+    }
+
     /// OWNER'S METHOD
     ///
     /// Requires 1 yoctoNEAR attached
     ///
+    /// Enables or disables locking the staking rewards automatically by `sync_rewards`.
+    #[payable]
+    pub fn set_auto_lock_rewards(&mut self, auto_lock_rewards: bool) {
+        self.assert_owner();
+        assert_one_yocto();
+        self.auto_lock_rewards = auto_lock_rewards;
+    }
+
+    /// Requires 75 TGas (3 * BASE_GAS) + 20 TGas
+    /// Requires the auto-locking of the staking rewards to be enabled by the owner
+    ///
+    /// Refreshes the total balance of the given staking pool and locks the staking rewards
+    /// accrued since the last refresh, up to the liquid balance. Can be called by anyone, e.g. by
+    /// a keeper, to keep the veNEAR balance up to date. The staking pool is not marked as busy, so
+    /// the call doesn't block the owner. The result is ignored if the owner started another
+    /// operation with the staking pool in the meantime.
+    pub fn sync_rewards(&mut self, staking_pool_account_id: AccountId) -> Promise {
+        assert!(
+            self.auto_lock_rewards,
+            "Auto-locking of the staking rewards is disabled"
+        );
+        self.assert_staking_pool_is_idle(&staking_pool_account_id);
+        let deposit_amount = self
+            .internal_get_staking_pool_mut(&staking_pool_account_id)
+            .deposit_amount;
+        ext_staking_pool::ext(staking_pool_account_id.clone())
+            .with_static_gas(gas::staking_pool::GET_ACCOUNT_TOTAL_BALANCE)
+            .get_account_total_balance(env::current_account_id())
+            .then(
+                ext_self_owner::ext(env::current_account_id())
+                    .with_static_gas(
+                        gas::owner_callbacks::ON_GET_ACCOUNT_TOTAL_BALANCE_TO_SYNC_REWARDS,
+                    )
+                    .on_get_account_total_balance_to_sync_rewards(
+                        staking_pool_account_id,
+                        deposit_amount,
+                    ),
+            )
+    }
+
+    /// Returns whether the staking rewards are locked automatically by `sync_rewards`.
+    pub fn get_auto_lock_rewards(&self) -> bool {
+        self.auto_lock_rewards
+    }
+
+    /// OWNER'S METHOD
+    ///
+    /// Requires 1 yoctoNEAR attached
+    /// Requires 75 TGas (3 * BASE_GAS) + 20 TGas when refreshing the staking pool balance
+    /// Requires a positive amount of the locked NEAR
+    ///
     /// Starts the unlocking process of the locked NEAR in the lockup contract.
     /// You specify the amount of near to unlock, or if you don't specify it, all the locked NEAR
     /// will be unlocked.
     /// (works similarly to unstaking from a staking pool).
+    /// Can't be called before the lock commitment ends.
+    /// If `refresh_staking_pool_account_id` is given, the total balance of the staking pool is
+    /// refreshed first, so the update sent to veNEAR reflects the staking rewards.
     #[payable]
-    pub fn begin_unlock_near(&mut self, amount: Option<NearToken>) {
+    pub fn begin_unlock_near(
+        &mut self,
+        amount: Option<NearToken>,
+        refresh_staking_pool_account_id: Option<AccountId>,
+    ) {
         self.assert_owner();
         assert_one_yocto();
-        let amount: Balance = if let Some(amount) = amount {
-            amount.as_yoctonear()
-        } else {
-            self.venear_locked_balance
-        };
-
-        assert!(amount <= self.venear_locked_balance, "Invalid amount");
-
-        self.venear_locked_balance -= amount;
-        self.venear_pending_balance += amount;
-        self.set_venear_unlock_timestamp();
+        assert!(
+            self.unlock_tranches.len() < MAX_UNLOCK_TRANCHES,
+            "Too many unlock tranches"
+        );
+        assert!(
+            !self.is_lock_committed(),
+            "The lock commitment has not ended"
+        );
+        if let Some(staking_pool_account_id) = refresh_staking_pool_account_id {
+            self.internal_get_staking_pool_total_balance(&staking_pool_account_id)
+                .then(
+                    ext_self_owner::ext(env::current_account_id())
+                        .with_static_gas(
+                            gas::owner_callbacks::ON_GET_ACCOUNT_TOTAL_BALANCE_TO_BEGIN_UNLOCK,
+                        )
+                        .on_get_account_total_balance_to_begin_unlock(
+                            staking_pool_account_id,
+                            amount,
+                        ),
+                );
+            return;
+        }
 
-        self.venear_lockup_update();
+        let amount = self
+            .internal_get_unlock_amount(amount)
+            .expect("Invalid amount");
+        self.internal_begin_unlock_near(amount);
     }
 
     /// OWNER'S METHOD
     ///
     /// Requires 1 yoctoNEAR attached
-    /// Requires that the unlock timestamp is reached
+    /// Requires a positive amount of the pending NEAR that reached the unlock timestamp
     ///
     /// Finishes the unlocking process of the NEAR in the lockup contract.
     /// You can specify the amount of NEAR to unlock, or if you don't specify it, all the pending
-    /// NEAR will be unlocked.
+    /// NEAR that reached the unlock timestamp will be unlocked. The amount is released from the
+    /// oldest unlock tranches first.
+    /// Fails with "Nothing to unlock" if none of the pending NEAR reached the unlock timestamp,
+    /// instead of only sending the lockup update to veNEAR.
     #[payable]
     pub fn end_unlock_near(&mut self, amount: Option<NearToken>) {
         self.assert_owner();
         assert_one_yocto();
+        let unlockable_balance = self.internal_get_unlockable_balance();
         let amount: Balance = if let Some(amount) = amount {
             amount.as_yoctonear()
         } else {
-            self.venear_pending_balance
+            unlockable_balance
         };
 
-        assert!(amount <= self.venear_pending_balance, "Invalid amount");
-        assert!(
-            env::block_timestamp() >= self.venear_unlock_timestamp,
-            "Invalid unlock time"
+        require!(amount > 0, "Nothing to unlock");
+        require!(
+            amount <= unlockable_balance,
+            "The amount exceeds the pending NEAR that reached the unlock timestamp"
         );
 
-        self.venear_pending_balance -= amount;
+        self.internal_end_unlock_near(amount);
+    }
 
-        self.venear_lockup_update();
+    /// Requires that the unlock timestamp of some pending NEAR is reached
+    ///
+    /// Finishes the unlocking process of all the pending NEAR that reached the unlock timestamp.
+    /// The NEAR stays in the lockup contract as liquid balance. Can be called by anyone, e.g. by
+    /// a wallet or a keeper, to settle the unlocks on behalf of the owner.
+    pub fn finalize_unlock(&mut self) {
+        let amount = self.internal_get_unlockable_balance();
+        assert!(amount > 0, "Nothing to unlock");
+
+        self.internal_end_unlock_near(amount);
     }
 
     /// OWNER'S METHOD
@@ -163,7 +532,7 @@ impl LockupContract {
     ///
     /// Locks the pending NEAR in the lockup contract.
     /// You can specify the amount of NEAR to lock, or if you don't specify it, all the pending NEAR
-    /// will be locked.
+    /// will be locked. The amount is taken from the newest unlock tranches first.
     #[payable]
     pub fn lock_pending_near(&mut self, amount: Option<NearToken>) {
         self.assert_owner();
@@ -171,12 +540,26 @@ impl LockupContract {
         let amount: Balance = if let Some(amount) = amount {
             amount.as_yoctonear()
         } else {
-            self.venear_pending_balance
+            self.internal_get_pending_balance()
         };
 
-        assert!(amount <= self.venear_pending_balance, "Invalid amount");
+        assert!(
+            amount <= self.internal_get_pending_balance(),
+            "Invalid amount"
+        );
 
-        self.venear_pending_balance -= amount;
+        let mut remaining_amount = amount;
+        while remaining_amount > 0 {
+            let tranche = self.unlock_tranches.last_mut().unwrap();
+            let tranche_amount = tranche.amount.as_yoctonear();
+            if tranche_amount > remaining_amount {
+                tranche.amount = NearToken::from_yoctonear(tranche_amount - remaining_amount);
+                remaining_amount = 0;
+            } else {
+                self.unlock_tranches.pop();
+                remaining_amount -= tranche_amount;
+            }
+        }
         self.venear_locked_balance += amount;
 
         self.venear_lockup_update();
@@ -1,9 +1,14 @@
 use crate::*;
-use common::lockup_update::VLockupUpdate;
+use common::lockup_update::{LockupMigrationUpdate, VLockupUpdate};
+use near_sdk::json_types::U64;
 use near_sdk::{ext_contract, AccountId};
 
 pub const GAS_FOR_VENEAR_LOCKUP_UPDATE: Gas = Gas::from_tgas(20);
 
+pub const GAS_FOR_VENEAR_LOCKUP_OWNER_TRANSFER: Gas = Gas::from_tgas(30);
+
+pub const GAS_FOR_VENEAR_LOCKUP_MIGRATION: Gas = Gas::from_tgas(20);
+
 #[allow(dead_code)]
 #[ext_contract(ext_venear)]
 trait ExtVenear {
@@ -13,4 +18,25 @@ trait ExtVenear {
         owner_account_id: AccountId,
         update: VLockupUpdate,
     );
+
+    fn on_lockup_owner_transfer(
+        &mut self,
+        version: Version,
+        owner_account_id: AccountId,
+        new_owner_account_id: AccountId,
+    );
+
+    fn on_lockup_migration(
+        &mut self,
+        version: Version,
+        owner_account_id: AccountId,
+        update: LockupMigrationUpdate,
+    );
+
+    fn on_lockup_migration_failed(
+        &mut self,
+        version: Version,
+        owner_account_id: AccountId,
+        lockup_update_nonce: U64,
+    );
 }
@@ -0,0 +1,99 @@
+//! The hashing and the tree shape used by the Merkle tree.
+//!
+//! The functions don't depend on the NEAR runtime outside of WASM, so they can be used off-chain
+//! to rebuild the tree and to verify proofs.
+
+use near_sdk::borsh::{self, BorshSerialize};
+use near_sdk::{near, CryptoHash};
+use std::sync::OnceLock;
+
+/// The prefix of the serialized value when hashing a leaf in the domain separated format.
+pub const LEAF_PREFIX: u8 = 0x00;
+
+/// The prefix of the concatenated children hashes when hashing an internal node in the domain
+/// separated format.
+pub const NODE_PREFIX: u8 = 0x01;
+
+/// The maximum height of the tree. The leaf indices are `u32`, so the tree has at most 2^32 leaves.
+pub const MAX_TREE_HEIGHT: u8 = 33;
+
+/// The hashing format of the Merkle tree.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[near(serializers=[borsh, json])]
+pub enum MerkleTreeFormat {
+    /// The leaf hash is `sha256(borsh(value))` and the node hash is `sha256(left || right)`.
+    /// The hash of an empty subtree is all zeros.
+    #[default]
+    Legacy,
+    /// The leaf hash is `sha256(0x00 || borsh(value))` and the node hash is
+    /// `sha256(0x01 || left || right)`. The hash of an empty leaf is all zeros and the hash of an
+    /// empty subtree is the node hash of two empty subtrees of the lower height.
+    DomainSeparated,
+}
+
+impl MerkleTreeFormat {
+    /// The format of the new trees.
+    pub const LATEST: Self = Self::DomainSeparated;
+
+    /// Returns the hash of the leaf value. The value is serialized using Borsh.
+    pub fn hash_leaf<T>(&self, value: &T) -> CryptoHash
+    where
+        T: BorshSerialize,
+    {
+        let data = borsh::to_vec(value).expect("Failed to serialize");
+        match self {
+            Self::Legacy => sha256(&data),
+            Self::DomainSeparated => sha256(&[&[LEAF_PREFIX][..], &data[..]].concat()),
+        }
+    }
+
+    /// Returns the hash of the internal node from the hashes of its children.
+    pub fn hash_node(&self, left_hash: &CryptoHash, right_hash: &CryptoHash) -> CryptoHash {
+        match self {
+            Self::Legacy => sha256(&[&left_hash[..], &right_hash[..]].concat()),
+            Self::DomainSeparated => {
+                sha256(&[&[NODE_PREFIX][..], &left_hash[..], &right_hash[..]].concat())
+            }
+        }
+    }
+
+    /// Returns the hash of the subtree of the given height without leaves.
+    /// The hashes of all heights are computed once on the first call.
+    pub fn empty_hash(&self, height: u8) -> CryptoHash {
+        static DOMAIN_SEPARATED_EMPTY_HASHES: OnceLock<Vec<CryptoHash>> = OnceLock::new();
+        match self {
+            Self::Legacy => CryptoHash::default(),
+            Self::DomainSeparated => DOMAIN_SEPARATED_EMPTY_HASHES.get_or_init(|| {
+                let mut hashes = vec![CryptoHash::default()];
+                for height in 1..MAX_TREE_HEIGHT as usize {
+                    let hash = self.hash_node(&hashes[height - 1], &hashes[height - 1]);
+                    hashes.push(hash);
+                }
+                hashes
+            })[height as usize],
+        }
+    }
+}
+
+/// Returns the sha256 hash of the given data.
+/// Uses the host function in the NEAR runtime and the native implementation otherwise.
+pub fn sha256(data: &[u8]) -> CryptoHash {
+    #[cfg(target_arch = "wasm32")]
+    {
+        near_sdk::env::sha256_array(data)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use sha2::Digest;
+        sha2::Sha256::digest(data).into()
+    }
+}
+
+/// Returns the height of the tree with the given number of leaves.
+pub fn tree_height(length: u32) -> u8 {
+    if length == 0 {
+        0
+    } else {
+        33 - (length - 1).leading_zeros() as u8
+    }
+}
@@ -1,8 +1,21 @@
 use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::Base58CryptoHash;
-use near_sdk::store::LookupMap;
-use near_sdk::{borsh, BlockHeight, CryptoHash, IntoStorageKey};
+use near_sdk::store::{LookupMap, Vector};
 use near_sdk::{near, AccountId, BorshStorageKey};
+use near_sdk::{BlockHeight, CryptoHash, IntoStorageKey};
+
+pub mod hash;
+mod multi_proof;
+mod offchain;
+mod sorted_keys;
+mod versions;
+
+pub use hash::{tree_height, MerkleTreeFormat};
+pub use multi_proof::MerkleMultiProof;
+pub use offchain::OffchainMerkleTree;
+use sorted_keys::SortedKeys;
+pub use sorted_keys::{KeyLink, MerkleNonMembershipProof};
+use versions::VersionedMap;
 
 #[derive(BorshStorageKey)]
 #[near(serializers=[borsh])]
@@ -10,8 +23,28 @@ enum MerkleStorageKeys {
     Hashes,
     Data,
     Accounts,
+    Snapshots,
+    HashVersions,
+    DataVersions,
+    FormatHashes(MerkleTreeFormat),
+    SortedKeys,
+    FreeIndices,
+    ReusedIndices,
+    FormatHashVersions(MerkleTreeFormat),
 }
 
+/// The default number of the blocks for which the previous snapshots are retained. It's about a
+/// day with one block per second.
+pub const DEFAULT_SNAPSHOT_RETENTION_BLOCKS: BlockHeight = 86_400;
+
+/// The number of the snapshots that are checked for pruning on every new snapshot.
+/// Every update adds at most one snapshot, so the pruning keeps up with the new snapshots.
+const NUM_PRUNED_SNAPSHOTS_PER_UPDATE: u32 = 2;
+
+/// The number of the top levels of the tree that are cached in the tree state instead of the
+/// hashes map.
+pub const NUM_CACHED_LEVELS: u8 = 4;
+
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Copy)]
 #[near(serializers=[borsh])]
 pub struct HeightAndIndex {
@@ -27,10 +60,22 @@ pub struct HeightAndIndex {
 /// When the number of leaves grows to 2^height, we add a new level to the tree.
 /// We also store hashes of the leaves in the tree to make it easier to verify the proofs.
 /// To hash the leaf, we serialize it using Borsh and then hash the serialized bytes using sha256.
-/// The empty leaf hash is by all zeros hash: `[0u8; 32]`.
+/// The leaves and the internal nodes are hashed according to the tree format, see
+/// `MerkleTreeFormat`. The empty leaf hash is by all zeros hash: `[0u8; 32]`.
 /// Note, that Value `T` has to contain the `account_id` in order to be able to verify the proof.
 /// The `global` field is used to store the global state of the tree. E.g. total sum of balances.
 /// When we save the previous snapshot, we also save the global state at that time.
+/// The previous snapshots taken within the last `snapshot_retention_blocks` blocks are retained, so
+/// the snapshot at the end of any recent block can be retrieved by the block height. The older
+/// snapshots and their versions are pruned.
+/// The previous values of the hashes and the leaves are versioned per snapshot (copy-on-write), so
+/// the proofs can be generated against any retained snapshot. The recent versions are stored
+/// together with the values, see `VersionedMap`.
+/// When an account is removed, its leaf becomes empty and the index is reused by the next new
+/// account.
+/// The hashes of the top `NUM_CACHED_LEVELS` levels are stored in the tree state, so they don't
+/// need separate storage reads and writes. Every snapshot keeps a copy of them. The updates of the leaves can be deferred with
+/// `set_deferred` and applied at once with `flush`, so the shared ancestors are rehashed once.
 #[near(serializers=[borsh])]
 pub struct MerkleTree<V, G>
 where
@@ -39,13 +84,100 @@ where
 {
     pub(crate) root: CryptoHash,
     pub(crate) length: u32,
-    pub(crate) hashes: LookupMap<HeightAndIndex, CryptoHash>,
-    pub(crate) data: LookupMap<u32, V>,
+    pub(crate) hashes: VersionedMap<HeightAndIndex, CryptoHash>,
+    pub(crate) data: VersionedMap<u32, V>,
     pub(crate) accounts: LookupMap<AccountId, u32>,
     /// The global state of the tree. E.g. total sum of balances.
     pub(crate) global_state: G,
-    pub(crate) previous_snapshot: Option<(MerkleTreeSnapshot, G)>,
     pub(crate) last_block_height: BlockHeight,
+    /// The previous snapshots by their numbers.
+    pub(crate) snapshots: LookupMap<u64, RetainedSnapshot<G>>,
+    /// The total number of the previous snapshots that were recorded.
+    pub(crate) num_snapshots: u64,
+    /// The number of the oldest snapshots that were pruned. The snapshots before this number are
+    /// removed from the storage together with their versions.
+    pub(crate) num_pruned_snapshots: u64,
+    /// The number of the blocks for which the previous snapshots are retained.
+    pub(crate) snapshot_retention_blocks: BlockHeight,
+    /// The hashing format of the tree.
+    pub(crate) format: MerkleTreeFormat,
+    /// The ongoing migration of the tree to the new format.
+    pub(crate) format_migration: Option<FormatMigration>,
+    /// The hashes in the previous format, while the snapshots before the format switch are
+    /// retained.
+    pub(crate) previous_format_hashes: Option<PreviousFormatHashes>,
+    /// The accumulator of the account IDs in the sorted order for the non-membership proofs.
+    pub(crate) sorted_keys: SortedKeys,
+    /// The hashes of the top levels of the tree. The level at the index `i` is at the depth `i`
+    /// from the root. The hashes of these nodes in the hashes map are outdated. They are not
+    /// versioned, because every snapshot keeps a copy of them.
+    pub(crate) top_hashes: Vec<Vec<CryptoHash>>,
+    /// The indices of the leaves that were updated, but not yet rehashed.
+    #[borsh(skip)]
+    pub(crate) pending: Vec<u32>,
+    /// The indices of the removed accounts that can be reused.
+    pub(crate) free_indices: Vector<u32>,
+    /// The snapshot number when the index was reused by a new account. The previous snapshots
+    /// contain the value of the removed account at this index.
+    pub(crate) reused_indices: LookupMap<u32, u64>,
+}
+
+/// The migration of the tree hashes to the new format.
+/// The leaves are rehashed in the index order into a separate hashes map. The updates of the
+/// already migrated leaves are applied to both maps. Once all leaves are migrated, the tree
+/// switches to the new hashes map and the new format.
+#[near(serializers=[borsh])]
+pub struct FormatMigration {
+    /// The new format of the tree.
+    pub(crate) format: MerkleTreeFormat,
+    /// The hashes of the tree in the new format.
+    pub(crate) hashes: VersionedMap<HeightAndIndex, CryptoHash>,
+    /// The number of leaves that are already migrated.
+    pub(crate) num_migrated_leaves: u32,
+}
+
+/// The hashes of the tree in the format before the last format switch.
+/// The map is not modified after the switch, so it contains the hashes at the moment of the switch.
+/// The hashes at the snapshots before the switch are the versions in this map, or the current
+/// hashes of this map.
+#[near(serializers=[borsh])]
+pub struct PreviousFormatHashes {
+    /// The previous format of the tree.
+    pub(crate) format: MerkleTreeFormat,
+    /// The hashes of the tree in the previous format.
+    pub(crate) hashes: VersionedMap<HeightAndIndex, CryptoHash>,
+    /// The number of the first snapshot in the new format.
+    pub(crate) snapshot_number: u64,
+}
+
+/// A retained previous snapshot of the tree.
+#[near(serializers=[borsh])]
+pub struct RetainedSnapshot<G> {
+    pub(crate) snapshot: MerkleTreeSnapshot,
+    /// The global state of the tree at the snapshot.
+    pub(crate) global_state: G,
+    /// The hashes of the cached top levels of the tree at the snapshot.
+    pub(crate) top_hashes: Vec<Vec<CryptoHash>>,
+}
+
+/// The layout of the Merkle Tree before the retained snapshots were introduced.
+#[near(serializers=[borsh])]
+pub struct MerkleTreeV0<V, G>
+where
+    V: BorshSerialize + BorshDeserialize + Clone,
+    G: BorshSerialize + BorshDeserialize + Clone,
+{
+    root: CryptoHash,
+    length: u32,
+    #[allow(dead_code)]
+    hashes: LookupMap<HeightAndIndex, CryptoHash>,
+    #[allow(dead_code)]
+    data: LookupMap<u32, V>,
+    accounts: LookupMap<AccountId, u32>,
+    global_state: G,
+    #[allow(dead_code)]
+    previous_snapshot: Option<(MerkleTreeSnapshot, G)>,
+    last_block_height: BlockHeight,
 }
 
 impl<V, G> MerkleTree<V, G>
@@ -57,20 +189,49 @@ where
     where
         S: IntoStorageKey,
     {
+        Self::new_with_snapshot_retention_blocks(
+            storage_key_prefix,
+            global_state,
+            DEFAULT_SNAPSHOT_RETENTION_BLOCKS,
+        )
+    }
+
+    pub fn new_with_snapshot_retention_blocks<S>(
+        storage_key_prefix: S,
+        global_state: G,
+        snapshot_retention_blocks: BlockHeight,
+    ) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        assert!(
+            snapshot_retention_blocks > 0,
+            "Must retain snapshots for at least one block"
+        );
         let prefix = storage_key_prefix.into_storage_key();
 
         Self {
             root: CryptoHash::default(),
             length: 0,
-            hashes: LookupMap::new(
+            hashes: VersionedMap::new(
                 [
                     &prefix[..],
                     &MerkleStorageKeys::Hashes.into_storage_key()[..],
                 ]
                 .concat(),
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::HashVersions.into_storage_key()[..],
+                ]
+                .concat(),
             ),
-            data: LookupMap::new(
+            data: VersionedMap::new(
                 [&prefix[..], &MerkleStorageKeys::Data.into_storage_key()[..]].concat(),
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::DataVersions.into_storage_key()[..],
+                ]
+                .concat(),
             ),
             accounts: LookupMap::new(
                 [
@@ -80,9 +241,218 @@ where
                 .concat(),
             ),
             global_state,
-            previous_snapshot: None,
             last_block_height: near_sdk::env::block_height(),
+            snapshots: LookupMap::new(
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::Snapshots.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            num_snapshots: 0,
+            num_pruned_snapshots: 0,
+            snapshot_retention_blocks,
+            format: MerkleTreeFormat::LATEST,
+            format_migration: None,
+            previous_format_hashes: None,
+            sorted_keys: SortedKeys::new(
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::SortedKeys.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            top_hashes: vec![],
+            pending: vec![],
+            free_indices: Vector::new(
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::FreeIndices.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            reused_indices: LookupMap::new(
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::ReusedIndices.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+        }
+    }
+
+    /// Migrates the tree from the layout without the retained snapshots. The storage key prefix
+    /// has to match the prefix that was used to create the tree.
+    /// The hashes and the leaves of the old tree are kept in place, because the values without
+    /// versions have the same layout. The previous snapshot of the old tree is not retained,
+    /// because its hashes are not versioned.
+    /// The old tree uses the legacy format, so the migration to the latest format is started. It
+    /// has to be completed by calling `migrate_format`.
+    /// The sorted keys of the old tree have to be indexed by calling `index_keys`.
+    pub fn migrate_from_v0<S>(storage_key_prefix: S, tree: MerkleTreeV0<V, G>) -> Self
+    where
+        S: IntoStorageKey,
+    {
+        let prefix = storage_key_prefix.into_storage_key();
+        let mut tree = Self {
+            root: tree.root,
+            length: tree.length,
+            hashes: VersionedMap::new(
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::Hashes.into_storage_key()[..],
+                ]
+                .concat(),
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::HashVersions.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            data: VersionedMap::new(
+                [&prefix[..], &MerkleStorageKeys::Data.into_storage_key()[..]].concat(),
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::DataVersions.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            accounts: tree.accounts,
+            global_state: tree.global_state,
+            last_block_height: tree.last_block_height,
+            snapshots: LookupMap::new(
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::Snapshots.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            num_snapshots: 0,
+            num_pruned_snapshots: 0,
+            snapshot_retention_blocks: DEFAULT_SNAPSHOT_RETENTION_BLOCKS,
+            format: MerkleTreeFormat::Legacy,
+            format_migration: Some(FormatMigration {
+                format: MerkleTreeFormat::LATEST,
+                hashes: VersionedMap::new(
+                    [
+                        &prefix[..],
+                        &MerkleStorageKeys::FormatHashes(MerkleTreeFormat::LATEST)
+                            .into_storage_key()[..],
+                    ]
+                    .concat(),
+                    [
+                        &prefix[..],
+                        &MerkleStorageKeys::FormatHashVersions(MerkleTreeFormat::LATEST)
+                            .into_storage_key()[..],
+                    ]
+                    .concat(),
+                ),
+                num_migrated_leaves: 0,
+            }),
+            previous_format_hashes: None,
+            sorted_keys: SortedKeys::new(
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::SortedKeys.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            top_hashes: vec![],
+            pending: vec![],
+            free_indices: Vector::new(
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::FreeIndices.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            reused_indices: LookupMap::new(
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::ReusedIndices.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+        };
+        tree.internal_load_top_hashes();
+        tree
+    }
+
+    fn internal_push_snapshot(&mut self, snapshot: RetainedSnapshot<G>) {
+        self.snapshots.set(self.num_snapshots, Some(snapshot));
+        self.num_snapshots += 1;
+        self.internal_prune_snapshots(NUM_PRUNED_SNAPSHOTS_PER_UPDATE);
+    }
+
+    /// Returns the lowest block height, at the end of which the snapshot is retained.
+    fn internal_min_retained_block_height(&self) -> BlockHeight {
+        near_sdk::env::block_height().saturating_sub(self.snapshot_retention_blocks)
+    }
+
+    /// Removes up to `limit` oldest snapshots that are no longer retained. The snapshot is the
+    /// state of the tree until the block height of the next snapshot, so it's no longer retained
+    /// once the next snapshot is at or before the lowest retained block height.
+    fn internal_prune_snapshots(&mut self, limit: u32) {
+        let min_block_height = self.internal_min_retained_block_height();
+        for _ in 0..limit {
+            let next_number = self.num_pruned_snapshots + 1;
+            if next_number >= self.num_snapshots {
+                return;
+            }
+            let next_snapshot = self
+                .snapshots
+                .get(&next_number)
+                .expect("Snapshot is missing");
+            if next_snapshot.snapshot.block_height > min_block_height {
+                return;
+            }
+            self.snapshots.set(self.num_pruned_snapshots, None);
+            self.num_pruned_snapshots = next_number;
+        }
+    }
+
+    /// Returns the number of the oldest snapshot that is not pruned. The versions before this
+    /// number are not needed.
+    fn internal_oldest_snapshot_number(&self) -> u64 {
+        self.num_pruned_snapshots
+    }
+
+    /// Returns the previous snapshot by its number, if it's still retained.
+    fn internal_get_snapshot(&self, number: u64) -> Option<&RetainedSnapshot<G>> {
+        if number >= self.num_snapshots || number < self.internal_oldest_snapshot_number() {
+            return None;
         }
+        self.snapshots.get(&number)
+    }
+
+    /// Returns the number of the blocks for which the previous snapshots are retained.
+    pub fn get_snapshot_retention_blocks(&self) -> BlockHeight {
+        self.snapshot_retention_blocks
+    }
+
+    /// Sets the number of the blocks for which the previous snapshots are retained. The snapshots
+    /// that are no longer retained are pruned gradually with the following updates.
+    pub fn set_snapshot_retention_blocks(&mut self, snapshot_retention_blocks: BlockHeight) {
+        assert!(
+            snapshot_retention_blocks > 0,
+            "Must retain snapshots for at least one block"
+        );
+        self.snapshot_retention_blocks = snapshot_retention_blocks;
+    }
+
+    /// Returns the snapshot of the current state of the tree.
+    fn internal_current_snapshot(&self) -> (MerkleTreeSnapshot, G) {
+        self.internal_assert_flushed();
+        (
+            MerkleTreeSnapshot {
+                root: self.root.into(),
+                length: self.length,
+                block_height: self.last_block_height,
+                format: self.format,
+                keys_root: self.get_keys_root().map(Into::into),
+            },
+            self.global_state.clone(),
+        )
     }
 
     /// An internal method to potentially update previous snapshot before we do any changes to the
@@ -90,75 +460,357 @@ where
     fn internal_pre_update(&mut self) {
         let block_height = near_sdk::env::block_height();
         if self.last_block_height != block_height {
-            self.previous_snapshot = Some((
-                MerkleTreeSnapshot {
-                    root: self.root.into(),
-                    length: self.length,
-                    block_height: self.last_block_height,
-                },
-                self.global_state.clone(),
-            ));
+            let (snapshot, global_state) = self.internal_current_snapshot();
+            self.internal_push_snapshot(RetainedSnapshot {
+                snapshot,
+                global_state,
+                top_hashes: self.top_hashes.clone(),
+            });
             self.last_block_height = block_height;
+            // The hashes in the previous format are not needed once the snapshots before the
+            // format switch are no longer retained.
+            if self
+                .previous_format_hashes
+                .as_ref()
+                .is_some_and(|previous| {
+                    previous.snapshot_number <= self.internal_oldest_snapshot_number()
+                })
+            {
+                self.previous_format_hashes = None;
+            }
         }
     }
 
-    fn internal_hash_value(&self, index: u32) -> CryptoHash {
-        let data = self
-            .data
+    fn internal_hash_value(&self, index: u32, format: MerkleTreeFormat) -> CryptoHash {
+        self.data
             .get(&index)
-            .map(|data| borsh::to_vec(data).expect("Failed to serialize data"));
-        data.map(|data| near_sdk::env::sha256(&data).try_into().unwrap())
-            .unwrap_or(CryptoHash::default())
+            .map(|value| format.hash_leaf(value))
+            .unwrap_or_default()
     }
 
     fn tree_height(&self) -> u8 {
-        if self.length == 0 {
-            0
-        } else {
-            33 - (self.length - 1).leading_zeros() as u8
-        }
+        tree_height(self.length)
+    }
+
+    fn internal_assert_flushed(&self) {
+        assert!(self.pending.is_empty(), "The tree has pending updates");
+    }
+
+    /// Returns the depth of the given height from the root, if the level is cached.
+    fn internal_cached_depth(&self, height: u8) -> Option<usize> {
+        let depth = self.tree_height().checked_sub(height + 1)?;
+        (depth < NUM_CACHED_LEVELS).then_some(depth as usize)
+    }
+
+    /// Returns the cached hash at the given depth. The hash is `None` if the level is not cached.
+    fn internal_get_cached_hash(&self, height: u8, index: u32) -> Option<CryptoHash> {
+        let depth = self.internal_cached_depth(height)?;
+        Some(
+            self.top_hashes
+                .get(depth)
+                .and_then(|level| level.get(index as usize))
+                .cloned()
+                .unwrap_or_else(|| self.format.empty_hash(height)),
+        )
     }
 
     fn internal_get_hash(&self, height: u8, index: u32) -> CryptoHash {
-        self.hashes
-            .get(&HeightAndIndex { height, index })
-            .cloned()
-            .unwrap_or_else(|| CryptoHash::default())
+        self.internal_get_cached_hash(height, index)
+            .unwrap_or_else(|| {
+                self.hashes
+                    .get(&HeightAndIndex { height, index })
+                    .cloned()
+                    .unwrap_or_else(|| self.format.empty_hash(height))
+            })
     }
 
     fn internal_set_hash(&mut self, height: u8, index: u32, hash: CryptoHash) {
-        self.hashes.insert(HeightAndIndex { height, index }, hash);
+        match self.internal_cached_depth(height) {
+            Some(depth) => {
+                if self.top_hashes.len() <= depth {
+                    self.top_hashes.resize(depth + 1, vec![]);
+                }
+                let empty_hash = self.format.empty_hash(height);
+                let level = &mut self.top_hashes[depth];
+                if level.len() <= index as usize {
+                    level.resize(index as usize + 1, empty_hash);
+                }
+                level[index as usize] = hash;
+            }
+            None => {
+                let key = HeightAndIndex { height, index };
+                match self.internal_block_snapshot_number() {
+                    Some(snapshot_number) => {
+                        let oldest_snapshot_number = self.internal_oldest_snapshot_number();
+                        let empty_hash = self.format.empty_hash(height);
+                        self.hashes.insert_versioned(
+                            key,
+                            hash,
+                            snapshot_number,
+                            oldest_snapshot_number,
+                            Some(empty_hash),
+                        );
+                    }
+                    None => self.hashes.insert(key, hash),
+                }
+            }
+        }
+    }
+
+    /// Adds a new root level to the cached top levels after the tree height has increased. The
+    /// lowest cached level is moved to the hashes map, if there are too many cached levels.
+    fn internal_grow_top_hashes(&mut self) {
+        self.top_hashes.insert(0, vec![]);
+        if self.top_hashes.len() > NUM_CACHED_LEVELS as usize {
+            let height = self.tree_height() - 1 - NUM_CACHED_LEVELS;
+            let level = self.top_hashes.pop().unwrap();
+            for (index, hash) in level.into_iter().enumerate() {
+                self.hashes.insert(
+                    HeightAndIndex {
+                        height,
+                        index: index as u32,
+                    },
+                    hash,
+                );
+            }
+        }
+    }
+
+    /// Loads the cached top levels from the hashes map.
+    fn internal_load_top_hashes(&mut self) {
+        let tree_height = self.tree_height();
+        self.top_hashes = (0..std::cmp::min(tree_height, NUM_CACHED_LEVELS))
+            .map(|depth| {
+                let height = tree_height - 1 - depth;
+                let num_nodes = (self.length - 1) >> height;
+                (0..=num_nodes)
+                    .map(|index| {
+                        self.hashes
+                            .get(&HeightAndIndex { height, index })
+                            .cloned()
+                            .unwrap_or_else(|| self.format.empty_hash(height))
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    /// Returns the number of the snapshot that was taken at the beginning of the current block, if
+    /// the tree was updated in the current block.
+    fn internal_block_snapshot_number(&self) -> Option<u64> {
+        if self.last_block_height == near_sdk::env::block_height() {
+            self.num_snapshots.checked_sub(1)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the hash at the given snapshot number. The current state of the tree has the number
+    /// `num_snapshots`.
+    fn internal_get_hash_at(&self, height: u8, index: u32, snapshot_number: u64) -> CryptoHash {
+        if snapshot_number == self.num_snapshots {
+            return self.internal_get_hash(height, index);
+        }
+        let snapshot = self
+            .internal_get_snapshot(snapshot_number)
+            .expect("Snapshot is missing");
+        let format = snapshot.snapshot.format;
+        // The levels that were cached at the snapshot are copied into it. A node that wasn't cached
+        // at the snapshot isn't cached later, because the tree only grows.
+        let depth = tree_height(snapshot.snapshot.length) - 1 - height;
+        if let Some(level) = snapshot.top_hashes.get(depth as usize) {
+            return level
+                .get(index as usize)
+                .cloned()
+                .unwrap_or_else(|| format.empty_hash(height));
+        }
+        let hashes = match self.previous_format_hashes.as_ref() {
+            Some(previous) if snapshot_number < previous.snapshot_number => &previous.hashes,
+            _ => &self.hashes,
+        };
+        let key = HeightAndIndex { height, index };
+        hashes
+            .get_at(&key, snapshot_number)
+            .or_else(|| hashes.get(&key))
+            .cloned()
+            .unwrap_or_else(|| format.empty_hash(height))
     }
 
-    fn internal_post_update(&mut self, index: u32) {
-        let hash = self.internal_hash_value(index);
-        self.internal_set_hash(0, index, hash);
+    /// Rehashes the updated leaves and their ancestors. Every ancestor is rehashed once.
+    pub fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let mut indices = std::mem::take(&mut self.pending);
+        indices.sort_unstable();
+        indices.dedup();
+        for &index in &indices {
+            let hash = self.internal_hash_value(index, self.format);
+            self.internal_set_hash(0, index, hash);
+        }
+        let mut height_indices = indices.clone();
         for height in 1..self.tree_height() {
-            let height_index = index >> height;
-            let left_hash = self.internal_get_hash(height - 1, height_index << 1);
-            let right_hash = self.internal_get_hash(height - 1, (height_index << 1) + 1);
-            let concat = [&left_hash[..], &right_hash[..]].concat();
-            let hash = near_sdk::env::sha256(&concat).try_into().unwrap();
-            self.internal_set_hash(height, height_index, hash);
+            height_indices = height_indices.iter().map(|index| index >> 1).collect();
+            height_indices.dedup();
+            for &height_index in &height_indices {
+                let left_hash = self.internal_get_hash(height - 1, height_index << 1);
+                let right_hash = self.internal_get_hash(height - 1, (height_index << 1) + 1);
+                let hash = self.format.hash_node(&left_hash, &right_hash);
+                self.internal_set_hash(height, height_index, hash);
+            }
         }
         self.root = self.internal_get_hash(self.tree_height() - 1, 0);
+        for index in indices {
+            let is_migrated = self
+                .format_migration
+                .as_ref()
+                .is_some_and(|migration| index < migration.num_migrated_leaves);
+            if is_migrated {
+                self.internal_migrate_leaf(index);
+            }
+        }
+    }
+
+    /// Rehashes the leaf and the path to the root in the hashes map of the format migration.
+    fn internal_migrate_leaf(&mut self, index: u32) {
+        let tree_height = self.tree_height();
+        let mut migration = self.format_migration.take().unwrap();
+        let format = migration.format;
+        let get_hash =
+            |hashes: &VersionedMap<HeightAndIndex, CryptoHash>, height: u8, index: u32| {
+                hashes
+                    .get(&HeightAndIndex { height, index })
+                    .cloned()
+                    .unwrap_or_else(|| format.empty_hash(height))
+            };
+        let hash = self.internal_hash_value(index, format);
+        migration
+            .hashes
+            .insert(HeightAndIndex { height: 0, index }, hash);
+        for height in 1..tree_height {
+            let height_index = index >> height;
+            let left_hash = get_hash(&migration.hashes, height - 1, height_index << 1);
+            let right_hash = get_hash(&migration.hashes, height - 1, (height_index << 1) + 1);
+            let hash = format.hash_node(&left_hash, &right_hash);
+            migration.hashes.insert(
+                HeightAndIndex {
+                    height,
+                    index: height_index,
+                },
+                hash,
+            );
+        }
+        self.format_migration = Some(migration);
+    }
+
+    /// Returns the hashing format of the tree.
+    pub fn get_format(&self) -> MerkleTreeFormat {
+        self.format
+    }
+
+    /// Migrates up to `limit` leaves to the new format, if the format migration is in progress.
+    /// Once all leaves are migrated, the tree switches to the new format. The snapshots before
+    /// the switch keep their format and remain provable until they are no longer retained.
+    /// Returns `true` if there is no format migration in progress after the call.
+    pub fn migrate_format(&mut self, limit: u32) -> bool {
+        self.internal_assert_flushed();
+        let Some(migration) = self.format_migration.as_ref() else {
+            return true;
+        };
+        let from_index = migration.num_migrated_leaves;
+        let to_index = std::cmp::min(from_index.saturating_add(limit), self.length);
+        for index in from_index..to_index {
+            self.format_migration.as_mut().unwrap().num_migrated_leaves = index + 1;
+            self.internal_migrate_leaf(index);
+        }
+        if to_index < self.length {
+            return false;
+        }
+        self.internal_pre_update();
+        let migration = self.format_migration.take().unwrap();
+        // The cached top levels are outdated in the hashes map, so they are written back before
+        // the map is kept for the snapshots before the switch.
+        let tree_height = self.tree_height();
+        for (depth, level) in std::mem::take(&mut self.top_hashes).into_iter().enumerate() {
+            let height = tree_height - 1 - depth as u8;
+            for (index, hash) in level.into_iter().enumerate() {
+                self.hashes.insert(
+                    HeightAndIndex {
+                        height,
+                        index: index as u32,
+                    },
+                    hash,
+                );
+            }
+        }
+        // The hashes of the format before the previous one are left in the storage, because
+        // `LookupMap` can't be cleared.
+        self.previous_format_hashes = Some(PreviousFormatHashes {
+            format: self.format,
+            hashes: std::mem::replace(&mut self.hashes, migration.hashes),
+            snapshot_number: self.num_snapshots,
+        });
+        self.format = migration.format;
+        self.internal_load_top_hashes();
+        self.root = match self.tree_height() {
+            0 => CryptoHash::default(),
+            tree_height => self.internal_get_hash(tree_height - 1, 0),
+        };
+        true
     }
 
     /// Returns the previous snapshot if it exists.
     pub fn get_snapshot(&self) -> Option<(MerkleTreeSnapshot, G)> {
         let block_height = near_sdk::env::block_height();
         if self.last_block_height != block_height {
-            Some((
-                MerkleTreeSnapshot {
-                    root: self.root.into(),
-                    length: self.length,
-                    block_height: self.last_block_height,
-                },
-                self.global_state.clone(),
-            ))
+            Some(self.internal_current_snapshot())
         } else {
-            self.previous_snapshot.clone()
+            self.num_snapshots
+                .checked_sub(1)
+                .and_then(|number| self.internal_get_snapshot(number))
+                .map(|snapshot| (snapshot.snapshot.clone(), snapshot.global_state.clone()))
+        }
+    }
+
+    /// Returns the snapshot of the tree at the end of the given block height, if it's still
+    /// retained. The block height has to be before the current block and within the last
+    /// `snapshot_retention_blocks` blocks.
+    /// The returned snapshot has the block height of the last update at or before the given block
+    /// height.
+    pub fn get_snapshot_at(&self, block_height: BlockHeight) -> Option<(MerkleTreeSnapshot, G)> {
+        let snapshot_number = self.internal_snapshot_number_at(block_height)?;
+        if snapshot_number == self.num_snapshots {
+            Some(self.internal_current_snapshot())
+        } else {
+            self.internal_get_snapshot(snapshot_number)
+                .map(|snapshot| (snapshot.snapshot.clone(), snapshot.global_state.clone()))
+        }
+    }
+
+    /// Returns the number of the retained snapshot at the end of the given block height. The
+    /// current state of the tree has the number `num_snapshots`.
+    fn internal_snapshot_number_at(&self, block_height: BlockHeight) -> Option<u64> {
+        if block_height >= near_sdk::env::block_height()
+            || block_height < self.internal_min_retained_block_height()
+        {
+            return None;
+        }
+        if block_height >= self.last_block_height {
+            return Some(self.num_snapshots);
         }
+        // Binary search for the last retained snapshot at or before the given block height.
+        let mut left = self.internal_oldest_snapshot_number();
+        let mut right = self.num_snapshots;
+        while left < right {
+            let mid = left + (right - left) / 2;
+            if self.internal_get_snapshot(mid)?.snapshot.block_height <= block_height {
+                left = mid + 1;
+            } else {
+                right = mid;
+            }
+        }
+        let number = left.checked_sub(1)?;
+        self.internal_get_snapshot(number).map(|_| number)
     }
 
     pub fn get_global_state(&self) -> &G {
@@ -174,24 +826,98 @@ where
     pub fn get(&self, account_id: &AccountId) -> Option<&V> {
         self.accounts
             .get(account_id)
-            .and_then(|index| self.data.get(&index))
+            .and_then(|index| self.data.get(index))
     }
 
     /// Sets the value for the given account_id and returns the old value if it existed.
     pub fn set(&mut self, account_id: AccountId, new_value: V) -> Option<V> {
+        let old_value = self.set_deferred(account_id, new_value);
+        self.flush();
+        old_value
+    }
+
+    /// Sets the value for the given account_id and returns the old value if it existed. The hashes
+    /// are not updated until `flush` is called, which has to happen before the end of the call.
+    pub fn set_deferred(&mut self, account_id: AccountId, new_value: V) -> Option<V> {
         self.internal_pre_update();
-        let index = self.accounts.get(&account_id).cloned().unwrap_or_else(|| {
-            let index = self.length;
-            self.length += 1;
-            self.accounts.insert(account_id.clone(), index);
-            index
-        });
-        let old_value = self.data.insert(index, new_value);
-        self.internal_post_update(index);
+        let index = match self.accounts.get(&account_id) {
+            Some(&index) => index,
+            None => {
+                let index = match self.free_indices.pop() {
+                    Some(index) => {
+                        self.reused_indices.insert(index, self.num_snapshots);
+                        index
+                    }
+                    None => {
+                        let old_tree_height = self.tree_height();
+                        self.length += 1;
+                        if self.tree_height() > old_tree_height {
+                            self.internal_grow_top_hashes();
+                        }
+                        self.length - 1
+                    }
+                };
+                self.accounts.insert(account_id.clone(), index);
+                // The new account can only be indexed once all the previous accounts are indexed.
+                if index <= self.sorted_keys.num_indexed() {
+                    let oldest_snapshot_number = self.internal_oldest_snapshot_number();
+                    self.sorted_keys.insert(
+                        account_id.clone(),
+                        index,
+                        self.num_snapshots,
+                        oldest_snapshot_number,
+                    );
+                }
+                index
+            }
+        };
+        let old_value = match self.internal_block_snapshot_number() {
+            Some(snapshot_number) => {
+                let oldest_snapshot_number = self.internal_oldest_snapshot_number();
+                self.data.insert_versioned(
+                    index,
+                    new_value,
+                    snapshot_number,
+                    oldest_snapshot_number,
+                    None,
+                )
+            }
+            None => {
+                let old_value = self.data.get(&index).cloned();
+                self.data.insert(index, new_value);
+                old_value
+            }
+        };
+        self.pending.push(index);
+        old_value
+    }
+
+    /// Removes the given account and returns its value, if it existed. The leaf of the account
+    /// becomes empty and its index is reused by the next new account. The hashes are not updated
+    /// until `flush` is called.
+    pub fn remove_deferred(&mut self, account_id: &AccountId) -> Option<V> {
+        self.internal_pre_update();
+        let index = self.accounts.remove(account_id)?;
+        // The removed account is not provable at the previous snapshots, so its previous values
+        // are not needed.
+        let old_value = self.data.remove(&index);
+        self.free_indices.push(index);
+        let oldest_snapshot_number = self.internal_oldest_snapshot_number();
+        self.sorted_keys
+            .remove(account_id, self.num_snapshots, oldest_snapshot_number);
+        self.pending.push(index);
+        old_value
+    }
+
+    /// Removes the given account and returns its value, if it existed.
+    pub fn remove(&mut self, account_id: &AccountId) -> Option<V> {
+        let old_value = self.remove_deferred(account_id);
+        self.flush();
         old_value
     }
 
     pub fn get_proof(&self, account_id: &AccountId) -> Option<(MerkleProof, V)> {
+        self.internal_assert_flushed();
         let &index = self.accounts.get(account_id)?;
         let mut path = vec![];
         for height in 0..self.tree_height() - 1 {
@@ -206,6 +932,143 @@ where
         ))
     }
 
+    /// Indexes up to `limit` accounts of the tree in the sorted keys, if the tree has unindexed
+    /// accounts. The `account_id_of` returns the account ID of the value.
+    /// Returns `true` if all accounts are indexed after the call.
+    pub fn index_keys<F>(&mut self, limit: u32, account_id_of: F) -> bool
+    where
+        F: Fn(&V) -> AccountId,
+    {
+        self.internal_pre_update();
+        let from_index = self.sorted_keys.num_indexed();
+        let to_index = std::cmp::min(from_index.saturating_add(limit), self.length);
+        let oldest_snapshot_number = self.internal_oldest_snapshot_number();
+        for index in from_index..to_index {
+            match self.data.get(&index) {
+                Some(value) => self.sorted_keys.insert(
+                    account_id_of(value),
+                    index,
+                    self.num_snapshots,
+                    oldest_snapshot_number,
+                ),
+                None => self
+                    .sorted_keys
+                    .skip(index, self.num_snapshots, oldest_snapshot_number),
+            }
+        }
+        to_index == self.length
+    }
+
+    /// Returns the root of the sorted keys tree, if all accounts are indexed.
+    pub fn get_keys_root(&self) -> Option<CryptoHash> {
+        (self.sorted_keys.num_indexed() == self.length).then(|| self.sorted_keys.root())
+    }
+
+    /// Returns the proof that the given account is not in the tree. The proof is valid for the
+    /// root returned by `get_keys_root` and the current length of the tree.
+    /// Returns `None` if the account is in the tree, or not all accounts are indexed.
+    pub fn get_non_membership_proof(
+        &self,
+        account_id: &AccountId,
+    ) -> Option<MerkleNonMembershipProof> {
+        self.get_keys_root()?;
+        self.sorted_keys
+            .get_non_membership_proof(account_id, self.num_snapshots, self.length)
+    }
+
+    /// Returns the proof that the given account is not in the tree at the end of the given block
+    /// height, if the snapshot is still retained. The proof is valid for the keys root and the
+    /// length of the snapshot returned by `get_snapshot_at` for the same block height.
+    /// Returns `None` if the account is in the snapshot, or not all accounts were indexed at the
+    /// snapshot.
+    pub fn get_non_membership_proof_at(
+        &self,
+        account_id: &AccountId,
+        block_height: BlockHeight,
+    ) -> Option<MerkleNonMembershipProof> {
+        let snapshot_number = self.internal_snapshot_number_at(block_height)?;
+        let length = if snapshot_number == self.num_snapshots {
+            self.get_keys_root()?;
+            self.length
+        } else {
+            let snapshot = &self.internal_get_snapshot(snapshot_number)?.snapshot;
+            snapshot.keys_root?;
+            snapshot.length
+        };
+        self.sorted_keys
+            .get_non_membership_proof(account_id, snapshot_number, length)
+    }
+
+    /// Returns the proof for the given accounts and their values in the order of the leaf indices
+    /// of the proof. The duplicate accounts are proven once.
+    /// Returns `None` if any of the accounts is not found.
+    pub fn get_multi_proof(&self, account_ids: &[AccountId]) -> Option<(MerkleMultiProof, Vec<V>)> {
+        self.internal_assert_flushed();
+        let mut indices = account_ids
+            .iter()
+            .map(|account_id| self.accounts.get(account_id).cloned())
+            .collect::<Option<Vec<_>>>()?;
+        indices.sort_unstable();
+        indices.dedup();
+        let values = indices
+            .iter()
+            .map(|index| self.data.get(index).cloned().unwrap())
+            .collect();
+        let proof = MerkleMultiProof::new(indices, self.length, |height, index| {
+            self.internal_get_hash(height, index)
+        });
+        Some((proof, values))
+    }
+
+    /// Returns the proof for the given account and the value at the end of the given block height,
+    /// if the snapshot is still retained. The proof is valid for the root of the snapshot returned
+    /// by `get_snapshot_at` for the same block height.
+    pub fn get_proof_at(
+        &self,
+        account_id: &AccountId,
+        block_height: BlockHeight,
+    ) -> Option<(MerkleProof, V)> {
+        self.internal_assert_flushed();
+        let snapshot_number = self.internal_snapshot_number_at(block_height)?;
+        let length = if snapshot_number == self.num_snapshots {
+            self.length
+        } else {
+            self.internal_get_snapshot(snapshot_number)?.snapshot.length
+        };
+        let &index = self.accounts.get(account_id)?;
+        if index >= length {
+            return None;
+        }
+        // The index belonged to a removed account at the snapshot.
+        if self
+            .reused_indices
+            .get(&index)
+            .is_some_and(|&reused_at| snapshot_number < reused_at)
+        {
+            return None;
+        }
+        let value = self
+            .data
+            .get_at(&index, snapshot_number)
+            .or_else(|| self.data.get(&index))
+            .cloned()?;
+        let mut path = vec![];
+        for height in 0..tree_height(length) - 1 {
+            let height_index = index >> height;
+            let sibling_index = height_index ^ 1;
+            let sibling_hash = self.internal_get_hash_at(height, sibling_index, snapshot_number);
+            path.push(sibling_hash.into());
+        }
+        Some((MerkleProof { index, path }, value))
+    }
+
+    /// Returns the indices of the empty leaves of the removed accounts that are not reused yet.
+    pub fn get_free_indices(&self) -> Vec<u32> {
+        self.free_indices.iter().cloned().collect()
+    }
+
+    /// Returns the number of the leaves in the tree, including the empty leaves of the removed
+    /// accounts.
     pub fn len(&self) -> u32 {
         self.length
     }
@@ -218,18 +1081,22 @@ where
         self.data.get(&index)
     }
 
+    /// Returns the index of the leaf of the given account_id.
+    pub fn get_index(&self, account_id: &AccountId) -> Option<u32> {
+        self.accounts.get(account_id).copied()
+    }
+
     /// Used in tests to remerkalize the tree.
     #[allow(dead_code)]
     fn remerkalize(&mut self) {
         for i in 0..self.length {
-            self.internal_set_hash(0, i, self.internal_hash_value(i));
+            self.internal_set_hash(0, i, self.internal_hash_value(i, self.format));
         }
         for height in 1..self.tree_height() {
             for i in 0..(1 << (height - 1)) {
                 let left_hash = self.internal_get_hash(height - 1, i << 1);
                 let right_hash = self.internal_get_hash(height - 1, (i << 1) + 1);
-                let concat = [&left_hash[..], &right_hash[..]].concat();
-                let hash = near_sdk::env::sha256(&concat).try_into().unwrap();
+                let hash = self.format.hash_node(&left_hash, &right_hash);
                 self.internal_set_hash(height, i, hash);
             }
         }
@@ -238,6 +1105,7 @@ where
 }
 
 /// A proof of inclusion in the Merkle tree.
+/// The proof doesn't carry the hashing format, the verifier takes it from the snapshot.
 #[derive(Clone)]
 #[near(serializers=[borsh, json])]
 pub struct MerkleProof {
@@ -260,10 +1128,27 @@ pub struct MerkleTreeSnapshot {
 
     /// The block height when the snapshot was taken.
     pub block_height: BlockHeight,
+
+    /// The hashing format of the tree at the snapshot.
+    #[serde(default)]
+    pub format: MerkleTreeFormat,
+
+    /// The root of the sorted keys tree at the snapshot, which is used to verify the
+    /// non-membership proofs. `None` if not all accounts were indexed.
+    #[serde(default)]
+    pub keys_root: Option<Base58CryptoHash>,
 }
 
 impl MerkleProof {
-    pub fn is_valid<T>(&self, root: CryptoHash, length: u32, value: &T) -> bool
+    /// Verifies that the given value is the leaf at the proof index in the tree with the given
+    /// hashing format, root and length.
+    pub fn is_valid<T>(
+        &self,
+        format: MerkleTreeFormat,
+        root: CryptoHash,
+        length: u32,
+        value: &T,
+    ) -> bool
     where
         T: BorshSerialize,
     {
@@ -271,23 +1156,20 @@ impl MerkleProof {
             return false;
         }
         // The length is greater than 0
-        let tree_height = 33 - (length - 1).leading_zeros() as u8;
-        if self.path.len() + 1 != tree_height as usize {
+        if self.path.len() + 1 != tree_height(length) as usize {
             return false;
         }
 
-        let data = borsh::to_vec(value).expect("Failed to serialize");
-        let mut hash: CryptoHash = near_sdk::env::sha256(&data).try_into().unwrap();
+        let mut hash = format.hash_leaf(value);
 
         for (height, sibling_hash) in self.path.iter().enumerate() {
-            let sibling_hash: CryptoHash = sibling_hash.clone().into();
+            let sibling_hash: CryptoHash = (*sibling_hash).into();
             let height_index = self.index >> height;
-            let concat = if height_index & 1 == 0 {
-                [&hash[..], &sibling_hash[..]].concat()
+            hash = if height_index & 1 == 0 {
+                format.hash_node(&hash, &sibling_hash)
             } else {
-                [&sibling_hash[..], &hash[..]].concat()
+                format.hash_node(&sibling_hash, &hash)
             };
-            hash = near_sdk::env::sha256(&concat).try_into().unwrap();
         }
         hash == root
     }
@@ -296,8 +1178,9 @@ impl MerkleProof {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::versions::MAX_INLINE_VERSIONS;
     use near_sdk::test_utils::VMContextBuilder;
-    use near_sdk::testing_env;
+    use near_sdk::{testing_env, VMContext};
 
     #[derive(BorshStorageKey)]
     #[near]
@@ -358,7 +1241,1015 @@ mod tests {
         assert_eq!(global_state, gs);
         let (proof, account) = tree.get_proof(&account_id).unwrap();
         assert_eq!(account, value + 1);
-        assert!(proof.is_valid(snapshot.root.into(), snapshot.length, &account));
+        assert!(proof.is_valid(
+            snapshot.format,
+            snapshot.root.into(),
+            snapshot.length,
+            &account
+        ));
+    }
+
+    #[test]
+    fn test_retained_snapshots() {
+        let mut context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        let snapshot_retention_blocks = 3;
+        let mut tree = MerkleTree::new_with_snapshot_retention_blocks(
+            StorageKeys::Tree,
+            0u32,
+            snapshot_retention_blocks,
+        );
+        let account_id: AccountId = "alice.near".parse().unwrap();
+
+        let mut roots = vec![tree.root];
+        for block_height in 1..=5 {
+            context.block_index = block_height;
+            testing_env!(context.clone());
+            tree.set(account_id.clone(), block_height as u32);
+            tree.set_global_state(block_height as u32);
+            roots.push(tree.root);
+        }
+
+        context.block_index = 6;
+        testing_env!(context.clone());
+
+        // The current block is not finished yet.
+        assert!(tree.get_snapshot_at(6).is_none());
+        for block_height in 3..=5 {
+            let (snapshot, gs) = tree.get_snapshot_at(block_height).unwrap();
+            assert_eq!(snapshot.block_height, block_height);
+            assert_eq!(gs, block_height as u32);
+            assert_eq!(
+                CryptoHash::from(snapshot.root),
+                roots[block_height as usize]
+            );
+        }
+        // Only the snapshots of the last 3 blocks are retained.
+        assert!(tree.get_snapshot_at(2).is_none());
+        assert!(tree.get_proof_at(&account_id, 2).is_none());
+
+        context.block_index = 10;
+        testing_env!(context.clone());
+
+        // The blocks without updates return the snapshot of the last update, even if the update
+        // was before the retained blocks.
+        let (snapshot, gs) = tree.get_snapshot_at(8).unwrap();
+        assert_eq!(snapshot.block_height, 5);
+        assert_eq!(gs, 5);
+
+        tree.set(account_id.clone(), 10);
+
+        let (snapshot, _) = tree.get_snapshot().unwrap();
+        assert_eq!(snapshot.block_height, 5);
+        let (snapshot, _) = tree.get_snapshot_at(7).unwrap();
+        assert_eq!(snapshot.block_height, 5);
+        assert_eq!(CryptoHash::from(snapshot.root), roots[5]);
+        let (proof, value) = tree.get_proof_at(&account_id, 7).unwrap();
+        assert_eq!(value, 5);
+        assert!(proof.is_valid(
+            snapshot.format,
+            snapshot.root.into(),
+            snapshot.length,
+            &value
+        ));
+        assert!(tree.get_snapshot_at(10).is_none());
+        assert!(tree.get_snapshot_at(6).is_none());
+
+        // The snapshots and the versions that are no longer retained are pruned.
+        for block_height in 11..=30 {
+            context.block_index = block_height;
+            testing_env!(context.clone());
+            tree.set(account_id.clone(), block_height as u32);
+        }
+        assert!(tree.num_snapshots - tree.num_pruned_snapshots <= snapshot_retention_blocks + 1);
+        let leaf_key = HeightAndIndex {
+            height: 0,
+            index: 0,
+        };
+        assert!(tree.hashes.num_versions(&leaf_key) as u64 <= snapshot_retention_blocks + 1);
+        assert!(tree.data.num_versions(&0) as u64 <= snapshot_retention_blocks + 1);
+        context.block_index = 31;
+        testing_env!(context.clone());
+        for block_height in 28..=30 {
+            let (snapshot, _) = tree.get_snapshot_at(block_height).unwrap();
+            let (proof, value) = tree.get_proof_at(&account_id, block_height).unwrap();
+            assert_eq!(value, block_height as u32);
+            assert!(proof.is_valid(
+                snapshot.format,
+                snapshot.root.into(),
+                snapshot.length,
+                &value
+            ));
+        }
+    }
+
+    #[test]
+    fn test_proofs_at_retained_snapshots() {
+        let mut context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        let snapshot_retention_blocks = 9;
+        let mut tree = MerkleTree::new_with_snapshot_retention_blocks(
+            StorageKeys::Tree,
+            0u32,
+            snapshot_retention_blocks,
+        );
+        let num_accounts = 5;
+        let accounts: Vec<AccountId> = (0..num_accounts)
+            .map(|i| format!("account{}", i).parse().unwrap())
+            .collect();
+
+        // Every block adds a new account or updates some of the existing ones.
+        let num_blocks = 12u64;
+        let mut expected_values = vec![];
+        let mut values: Vec<Option<u32>> = vec![None; num_accounts];
+        for block_height in 1..=num_blocks {
+            context.block_index = block_height;
+            testing_env!(context.clone());
+            for (i, account_id) in accounts.iter().enumerate() {
+                if i as u64 <= block_height && (block_height + i as u64) & 3 != 0 {
+                    let value = (block_height * 100) as u32 + i as u32;
+                    tree.set(account_id.clone(), value);
+                    values[i] = Some(value);
+                }
+            }
+            expected_values.push(values.clone());
+        }
+
+        context.block_index = num_blocks + 1;
+        testing_env!(context.clone());
+
+        let oldest_block_height = num_blocks + 1 - snapshot_retention_blocks;
+        for block_height in 1..=num_blocks {
+            let snapshot = tree.get_snapshot_at(block_height);
+            if block_height < oldest_block_height {
+                assert!(snapshot.is_none());
+                assert!(tree.get_proof_at(&accounts[0], block_height).is_none());
+                continue;
+            }
+            let (snapshot, _) = snapshot.unwrap();
+            let expected = &expected_values[block_height as usize - 1];
+            for (i, account_id) in accounts.iter().enumerate() {
+                let proof = tree.get_proof_at(account_id, block_height);
+                match expected[i] {
+                    Some(expected_value) => {
+                        let (proof, value) = proof.unwrap();
+                        assert_eq!(value, expected_value);
+                        assert!(proof.is_valid(
+                            snapshot.format,
+                            snapshot.root.into(),
+                            snapshot.length,
+                            &value
+                        ));
+                    }
+                    None => assert!(proof.is_none()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_versions_in_chunks() {
+        let mut context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        let snapshot_retention_blocks = 30;
+        let mut tree = MerkleTree::new_with_snapshot_retention_blocks(
+            StorageKeys::Tree,
+            0u32,
+            snapshot_retention_blocks,
+        );
+        // The tree is deep enough to store the lower levels in the hashes map.
+        let accounts: Vec<AccountId> = (0..40)
+            .map(|i| format!("account{}", i).parse().unwrap())
+            .collect();
+        for (i, account_id) in accounts.iter().enumerate() {
+            tree.set(account_id.clone(), i as u32);
+        }
+
+        // The first account is updated in every block and the second one in every third block, so
+        // their versions are moved to the chunks.
+        let num_blocks = 100u64;
+        let mut expected_values = vec![];
+        let mut values = [0u32, 1u32];
+        for block_height in 1..=num_blocks {
+            context.block_index = block_height;
+            testing_env!(context.clone());
+            values[0] = block_height as u32 * 100;
+            tree.set(accounts[0].clone(), values[0]);
+            if block_height % 3 == 0 {
+                values[1] = block_height as u32 * 100 + 1;
+                tree.set(accounts[1].clone(), values[1]);
+            }
+            expected_values.push(values);
+        }
+
+        context.block_index = num_blocks + 1;
+        testing_env!(context.clone());
+
+        let oldest_block_height = num_blocks + 1 - snapshot_retention_blocks;
+        for block_height in 1..=num_blocks {
+            if block_height < oldest_block_height {
+                assert!(tree.get_proof_at(&accounts[0], block_height).is_none());
+                continue;
+            }
+            let (snapshot, _) = tree.get_snapshot_at(block_height).unwrap();
+            let expected = expected_values[block_height as usize - 1];
+            for (i, account_id) in accounts.iter().enumerate().take(3) {
+                let (proof, value) = tree.get_proof_at(account_id, block_height).unwrap();
+                assert_eq!(value, expected.get(i).copied().unwrap_or(i as u32));
+                assert!(proof.is_valid(
+                    snapshot.format,
+                    snapshot.root.into(),
+                    snapshot.length,
+                    &value
+                ));
+            }
+        }
+
+        // The chunks before the oldest retained snapshot are pruned.
+        let max_versions = snapshot_retention_blocks as usize + 1 + 2 * MAX_INLINE_VERSIONS;
+        let leaf_key = HeightAndIndex {
+            height: 0,
+            index: 0,
+        };
+        assert!(tree.hashes.num_chunks() > 0);
+        assert!(tree.hashes.num_versions(&leaf_key) <= max_versions);
+        assert!(tree.data.num_versions(&0) <= max_versions);
+    }
+
+    #[test]
+    fn test_migrate_from_v0() {
+        let context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        // The tree without the snapshots stores the hashes and the leaves without versions, like
+        // the old tree.
+        let prefix = b"t".to_vec();
+        let mut tree = MerkleTree::new(prefix.clone(), 0u32);
+        tree.format = MerkleTreeFormat::Legacy;
+        let accounts: Vec<AccountId> = (0..20)
+            .map(|i| format!("account{}", i).parse().unwrap())
+            .collect();
+        for (i, account_id) in accounts.iter().enumerate() {
+            tree.set(account_id.clone(), i as u32);
+        }
+        // The old tree doesn't cache the top levels.
+        let tree_height = tree.tree_height();
+        for (depth, level) in tree.top_hashes.clone().into_iter().enumerate() {
+            for (index, hash) in level.into_iter().enumerate() {
+                tree.hashes.insert(
+                    HeightAndIndex {
+                        height: tree_height - 1 - depth as u8,
+                        index: index as u32,
+                    },
+                    hash,
+                );
+            }
+        }
+        let (root, length) = (tree.root, tree.length);
+        drop(tree);
+
+        let old_tree = MerkleTreeV0 {
+            root,
+            length,
+            hashes: LookupMap::new(
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::Hashes.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            data: LookupMap::new(
+                [&prefix[..], &MerkleStorageKeys::Data.into_storage_key()[..]].concat(),
+            ),
+            accounts: LookupMap::new(
+                [
+                    &prefix[..],
+                    &MerkleStorageKeys::Accounts.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            global_state: 0u32,
+            previous_snapshot: None,
+            last_block_height: 0,
+        };
+        let tree = MerkleTree::migrate_from_v0(prefix, old_tree);
+        assert_eq!(tree.root, root);
+        assert_eq!(tree.get_format(), MerkleTreeFormat::Legacy);
+        for (i, account_id) in accounts.iter().enumerate() {
+            assert_eq!(tree.get(account_id), Some(&(i as u32)));
+            let (proof, value) = tree.get_proof(account_id).unwrap();
+            assert!(proof.is_valid(MerkleTreeFormat::Legacy, root, length, &value));
+        }
+    }
+
+    /// The gas of the updates of the tree before the retained snapshots, with a single previous
+    /// snapshot and without the cached top levels. The pairs are the number of the accounts, the
+    /// gas of an update of one leaf and the gas of an update of two leaves. The hashing is not
+    /// included, because it doesn't use the runtime in the tests.
+    const BASELINE_UPDATE_GAS: [(u32, u64, u64); 2] = [
+        (20, 1_650_614_223_635, 2_231_844_289_008),
+        (1000, 2_770_815_312_689, 3_409_482_736_203),
+    ];
+
+    /// Reads the tree from the contract state in a new block, applies the update and writes the
+    /// tree back. Returns the gas used by the call.
+    fn update_in_new_block<F>(context: &mut VMContext, update: F) -> u64
+    where
+        F: FnOnce(&mut MerkleTree<Vec<u8>, u32>),
+    {
+        context.block_index += 1;
+        testing_env!(context.clone());
+        let mut tree =
+            near_sdk::env::state_read().unwrap_or_else(|| MerkleTree::new(StorageKeys::Tree, 0u32));
+        update(&mut tree);
+        near_sdk::env::state_write(&tree);
+        drop(tree);
+        near_sdk::env::used_gas().as_gas()
+    }
+
+    #[test]
+    fn test_update_gas() {
+        let value_of = |index: u32, round: u32| {
+            let mut value = vec![7u8; 160];
+            value[..4].copy_from_slice(&index.to_le_bytes());
+            value[4..8].copy_from_slice(&round.to_le_bytes());
+            value
+        };
+        for (num_accounts, baseline_one_leaf, baseline_two_leaves) in BASELINE_UPDATE_GAS {
+            let mut context = VMContextBuilder::new().build();
+            let accounts: Vec<AccountId> = (0..num_accounts)
+                .map(|i| format!("user{:04}.testnet", i).parse().unwrap())
+                .collect();
+            for chunk in accounts.chunks(50) {
+                update_in_new_block(&mut context, |tree| {
+                    for account_id in chunk {
+                        let index = tree.len();
+                        tree.set_deferred(account_id.clone(), value_of(index, 0));
+                    }
+                    tree.flush();
+                });
+            }
+            // Two full cycles of moving the versions to the chunks.
+            let num_rounds = 2 * MAX_INLINE_VERSIONS as u32;
+            let (mut one_leaf, mut two_leaves) = (0, 0);
+            for round in 1..=num_rounds {
+                one_leaf += update_in_new_block(&mut context, |tree| {
+                    tree.set_global_state(round);
+                    tree.set(accounts[0].clone(), value_of(0, round));
+                });
+                two_leaves += update_in_new_block(&mut context, |tree| {
+                    tree.set_global_state(round);
+                    tree.set_deferred(accounts[1].clone(), value_of(1, round));
+                    tree.set_deferred(accounts[2].clone(), value_of(2, round));
+                    tree.flush();
+                });
+            }
+            assert!(one_leaf / num_rounds as u64 <= baseline_one_leaf);
+            assert!(two_leaves / num_rounds as u64 <= baseline_two_leaves);
+        }
+    }
+
+    /// Creates an empty tree with the given format. The storage prefix depends on the format.
+    fn new_tree_with_format(format: MerkleTreeFormat) -> MerkleTree<u32, u32> {
+        let prefix = near_sdk::borsh::to_vec(&format).unwrap();
+        let mut tree = MerkleTree::new(prefix, 0u32);
+        tree.format = format;
+        tree
+    }
+
+    #[test]
+    fn test_offchain_merkle_tree() {
+        for format in [MerkleTreeFormat::Legacy, MerkleTreeFormat::DomainSeparated] {
+            test_offchain_merkle_tree_with_format(format);
+        }
+    }
+
+    fn test_offchain_merkle_tree_with_format(format: MerkleTreeFormat) {
+        let mut context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        let mut tree = new_tree_with_format(format);
+        let mut offchain_tree = OffchainMerkleTree::new(format);
+        assert_eq!(offchain_tree.root(), tree.root);
+
+        let num_accounts = 20;
+        let accounts: Vec<AccountId> = (0..num_accounts)
+            .map(|i| format!("account{}", i).parse().unwrap())
+            .collect();
+        for i in 0..num_accounts {
+            context.block_index += 1;
+            testing_env!(context.clone());
+            // Add a new account and update some of the existing ones.
+            for j in (0..=i).rev().step_by(3) {
+                let value = (i * num_accounts + j) as u32;
+                tree.set(accounts[j].clone(), value);
+                if j == i {
+                    offchain_tree.push(&value);
+                } else {
+                    offchain_tree.set(j as u32, &value);
+                }
+                assert_eq!(offchain_tree.root(), tree.root);
+            }
+            assert_eq!(offchain_tree.len(), tree.len());
+            for (j, account_id) in accounts.iter().enumerate().take(i + 1) {
+                let (proof, value) = tree.get_proof(account_id).unwrap();
+                let offchain_proof = offchain_tree.get_proof(j as u32).unwrap();
+                assert_eq!(offchain_proof.index, proof.index);
+                assert_eq!(offchain_proof.path, proof.path);
+                assert!(offchain_proof.is_valid(
+                    offchain_tree.format(),
+                    offchain_tree.root(),
+                    offchain_tree.len(),
+                    &value
+                ));
+            }
+        }
+
+        let values: Vec<u32> = (0..tree.len())
+            .map(|i| *tree.get_by_index(i).unwrap())
+            .collect();
+        assert_eq!(
+            OffchainMerkleTree::from_values(format, &values).root(),
+            tree.root
+        );
+    }
+
+    #[test]
+    fn test_multi_proof() {
+        let mut context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        let mut tree = MerkleTree::new(StorageKeys::Tree, 0u32);
+        let num_accounts = 13;
+        let accounts: Vec<AccountId> = (0..num_accounts)
+            .map(|i| format!("account{}", i).parse().unwrap())
+            .collect();
+        for (i, account_id) in accounts.iter().enumerate() {
+            tree.set(account_id.clone(), i as u32 * 10);
+        }
+        context.block_index += 1;
+        testing_env!(context.clone());
+        let values: Vec<u32> = (0..num_accounts).map(|i| i * 10).collect();
+        let offchain_tree = OffchainMerkleTree::from_values(MerkleTreeFormat::LATEST, &values);
+
+        let subsets: Vec<Vec<usize>> = vec![
+            vec![0],
+            vec![12],
+            vec![0, 1],
+            vec![1, 2],
+            vec![3, 0, 7, 3],
+            vec![4, 5, 6, 7],
+            vec![0, 5, 11, 12],
+            (0..num_accounts as usize).collect(),
+        ];
+        for subset in subsets {
+            let account_ids: Vec<AccountId> = subset.iter().map(|&i| accounts[i].clone()).collect();
+            let (proof, proof_values) = tree.get_multi_proof(&account_ids).unwrap();
+            let mut expected_indices: Vec<u32> = subset.iter().map(|&i| i as u32).collect();
+            expected_indices.sort_unstable();
+            expected_indices.dedup();
+            assert_eq!(proof.indices, expected_indices);
+            assert_eq!(
+                proof_values,
+                expected_indices.iter().map(|&i| i * 10).collect::<Vec<_>>()
+            );
+            assert!(proof.is_valid(tree.format, tree.root, tree.len(), &proof_values));
+            assert!(!proof.is_valid(
+                MerkleTreeFormat::Legacy,
+                tree.root,
+                tree.len(),
+                &proof_values
+            ));
+
+            // The siblings are not duplicated, so the proof is not larger than the single proofs.
+            let single_path_len: usize = expected_indices
+                .iter()
+                .map(|&i| offchain_tree.get_proof(i).unwrap().path.len())
+                .sum();
+            assert!(proof.path.len() <= single_path_len);
+
+            let offchain_proof = offchain_tree.get_multi_proof(&expected_indices).unwrap();
+            assert_eq!(offchain_proof.indices, proof.indices);
+            assert_eq!(offchain_proof.path, proof.path);
+
+            let mut wrong_values = proof_values.clone();
+            wrong_values[0] += 1;
+            assert!(!proof.is_valid(tree.format, tree.root, tree.len(), &wrong_values));
+            assert!(!proof.is_valid(tree.format, tree.root, tree.len(), &proof_values[1..]));
+            let mut wrong_proof = proof.clone();
+            wrong_proof.path.push(CryptoHash::default().into());
+            assert!(!wrong_proof.is_valid(tree.format, tree.root, tree.len(), &proof_values));
+        }
+
+        let unknown_account_id: AccountId = "unknown".parse().unwrap();
+        assert!(tree
+            .get_multi_proof(&[accounts[0].clone(), unknown_account_id])
+            .is_none());
+        assert!(offchain_tree.get_multi_proof(&[num_accounts]).is_none());
+        assert!(offchain_tree.get_multi_proof(&[]).is_none());
+    }
+
+    #[test]
+    fn test_non_membership_proof() {
+        let context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        let mut tree = MerkleTree::new(StorageKeys::Tree, 0u32);
+        let unknown_account_id: AccountId = "bob.near".parse().unwrap();
+        let keys_root = tree.get_keys_root().unwrap();
+        let proof = tree.get_non_membership_proof(&unknown_account_id).unwrap();
+        assert!(proof.is_valid(&unknown_account_id, keys_root, tree.len()));
+
+        let account_ids: Vec<AccountId> = ["m", "c", "x", "a", "p", "d", "z", "k"]
+            .iter()
+            .map(|name| format!("{}.near", name).parse().unwrap())
+            .collect();
+        for (i, account_id) in account_ids.iter().enumerate() {
+            tree.set(account_id.clone(), i as u32);
+        }
+        let keys_root = tree.get_keys_root().unwrap();
+        for account_id in &account_ids {
+            assert!(tree.get_non_membership_proof(account_id).is_none());
+        }
+        for name in ["0", "b", "e", "l", "n", "y", "zz"] {
+            let account_id: AccountId = format!("{}.near", name).parse().unwrap();
+            let proof = tree.get_non_membership_proof(&account_id).unwrap();
+            assert!(proof.is_valid(&account_id, keys_root, tree.len()));
+            // The proof doesn't apply to the accounts in the tree.
+            for other_account_id in &account_ids {
+                assert!(!proof.is_valid(other_account_id, keys_root, tree.len()));
+            }
+            let mut wrong_proof = proof.clone();
+            wrong_proof.link.next_account_id = Some("zzz.near".parse().unwrap());
+            assert!(!wrong_proof.is_valid(&account_id, keys_root, tree.len()));
+        }
+
+        // Index the keys of the tree in batches, like after the migration from the old tree.
+        tree.sorted_keys = SortedKeys::new(b"k".to_vec());
+        assert!(tree.get_keys_root().is_none());
+        assert!(tree.get_non_membership_proof(&unknown_account_id).is_none());
+        assert!(!tree.index_keys(3, |value| account_ids[*value as usize].clone()));
+        // The new account is indexed after the existing ones.
+        let new_account_id: AccountId = "f.near".parse().unwrap();
+        let mut all_account_ids = account_ids.clone();
+        all_account_ids.push(new_account_id.clone());
+        tree.set(new_account_id, account_ids.len() as u32);
+        while !tree.index_keys(3, |value| all_account_ids[*value as usize].clone()) {}
+
+        let mut expected_tree = MerkleTree::new(b"e".to_vec(), 0u32);
+        for (i, account_id) in all_account_ids.iter().enumerate() {
+            expected_tree.set(account_id.clone(), i as u32);
+        }
+        assert_eq!(tree.get_keys_root(), expected_tree.get_keys_root());
+        let keys_root = tree.get_keys_root().unwrap();
+        let proof = tree.get_non_membership_proof(&unknown_account_id).unwrap();
+        assert!(proof.is_valid(&unknown_account_id, keys_root, tree.len()));
+    }
+
+    #[test]
+    fn test_snapshot_keys_root() {
+        let mut context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        let mut tree = MerkleTree::new(StorageKeys::Tree, 0u32);
+        tree.set("a.near".parse().unwrap(), 0);
+        let keys_root = tree.get_keys_root();
+        context.block_index += 1;
+        testing_env!(context.clone());
+        let (snapshot, _) = tree.get_snapshot().unwrap();
+        assert_eq!(snapshot.keys_root, keys_root.map(Into::into));
+
+        // The proof is valid for the snapshot, while the accounts aren't updated.
+        tree.set("a.near".parse().unwrap(), 1);
+        let (snapshot, _) = tree.get_snapshot().unwrap();
+        let unknown_account_id: AccountId = "b.near".parse().unwrap();
+        let proof = tree.get_non_membership_proof(&unknown_account_id).unwrap();
+        assert!(proof.is_valid(
+            &unknown_account_id,
+            snapshot.keys_root.unwrap().into(),
+            snapshot.length
+        ));
+
+        // The keys root of the snapshot is retained after the new account is added.
+        tree.set(unknown_account_id.clone(), 2);
+        context.block_index += 1;
+        testing_env!(context.clone());
+        let (old_snapshot, _) = tree.get_snapshot_at(context.block_index - 2).unwrap();
+        assert_eq!(old_snapshot.keys_root, keys_root.map(Into::into));
+        let (snapshot, _) = tree.get_snapshot().unwrap();
+        assert_eq!(snapshot.keys_root, tree.get_keys_root().map(Into::into));
+        assert_ne!(snapshot.keys_root, old_snapshot.keys_root);
+
+        // The snapshots don't have the keys root until all accounts are indexed.
+        tree.sorted_keys = SortedKeys::new(b"k".to_vec());
+        context.block_index += 1;
+        testing_env!(context.clone());
+        let (snapshot, _) = tree.get_snapshot().unwrap();
+        assert!(snapshot.keys_root.is_none());
+    }
+
+    #[test]
+    fn test_non_membership_proofs_at_retained_snapshots() {
+        let mut context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        let snapshot_retention_blocks = 8;
+        let mut tree = MerkleTree::new_with_snapshot_retention_blocks(
+            StorageKeys::Tree,
+            0u32,
+            snapshot_retention_blocks,
+        );
+        let account_ids: Vec<AccountId> = ["m", "c", "x", "a", "p", "d", "z", "k"]
+            .iter()
+            .map(|name| format!("{}.near", name).parse().unwrap())
+            .collect();
+        let temporary_account_id: AccountId = "q.near".parse().unwrap();
+
+        // Every block adds a new account or removes an existing one. The removed accounts are added
+        // again later.
+        let num_blocks = 20u64;
+        let mut expected_account_ids = vec![];
+        let mut registered = std::collections::BTreeSet::new();
+        for block_height in 1..=num_blocks {
+            context.block_index = block_height;
+            testing_env!(context.clone());
+            let account_id = &account_ids[(block_height * 3) as usize % account_ids.len()];
+            if registered.remove(account_id) {
+                tree.remove(account_id);
+            } else {
+                registered.insert(account_id.clone());
+                tree.set(account_id.clone(), block_height as u32);
+            }
+            // The account that is added and removed in the same block is not in any snapshot.
+            if block_height == 5 {
+                tree.set(temporary_account_id.clone(), 0);
+                tree.remove(&temporary_account_id);
+            }
+            expected_account_ids.push(registered.clone());
+        }
+
+        // The proofs at the end of the previous block are available after the update in the
+        // current block.
+        context.block_index = num_blocks + 1;
+        testing_env!(context.clone());
+        tree.remove(&account_ids[0]);
+        tree.set("b.near".parse().unwrap(), 0);
+
+        let mut checked_account_ids = account_ids.clone();
+        checked_account_ids.push(temporary_account_id);
+        checked_account_ids.push("b.near".parse().unwrap());
+        let oldest_block_height = num_blocks + 1 - snapshot_retention_blocks;
+        for block_height in 1..=num_blocks {
+            for account_id in &checked_account_ids {
+                let proof = tree.get_non_membership_proof_at(account_id, block_height);
+                if block_height < oldest_block_height {
+                    assert!(proof.is_none());
+                    continue;
+                }
+                if expected_account_ids[block_height as usize - 1].contains(account_id) {
+                    assert!(proof.is_none());
+                    continue;
+                }
+                let (snapshot, _) = tree.get_snapshot_at(block_height).unwrap();
+                let keys_root = snapshot.keys_root.unwrap().into();
+                assert!(proof
+                    .unwrap()
+                    .is_valid(account_id, keys_root, snapshot.length));
+            }
+        }
+    }
+
+    #[test]
+    fn test_deferred_updates() {
+        // The writes of all blocks are flushed at the end of the test, so the gas is not charged.
+        let mut vm_config = near_sdk::test_vm_config();
+        vm_config.make_free();
+        let mut context = VMContextBuilder::new().build();
+        testing_env!(context.clone(), vm_config.clone());
+
+        let mut tree = MerkleTree::new(b"a".to_vec(), 0u32);
+        let mut deferred_tree = MerkleTree::new(b"b".to_vec(), 0u32);
+        let num_accounts = 40;
+        let accounts: Vec<AccountId> = (0..num_accounts)
+            .map(|i| format!("account{}", i).parse().unwrap())
+            .collect();
+        let mut snapshots = vec![];
+        for i in 0..num_accounts {
+            context.block_index += 1;
+            testing_env!(context.clone(), vm_config.clone());
+            // Add a new account and update a few existing ones, including the same one twice.
+            let mut updates = vec![(i, i as u32)];
+            for j in (0..i).step_by(7) {
+                updates.push((j, (i * num_accounts + j) as u32));
+                updates.push((j, (i * num_accounts + j + 1) as u32));
+            }
+            for &(j, value) in &updates {
+                tree.set(accounts[j].clone(), value);
+                deferred_tree.set_deferred(accounts[j].clone(), value);
+            }
+            deferred_tree.flush();
+            assert_eq!(deferred_tree.root, tree.root);
+            snapshots.push((context.block_index, tree.root, tree.len()));
+        }
+        context.block_index += 1;
+        testing_env!(context.clone(), vm_config.clone());
+
+        // The top levels are cached and not written to the hashes map.
+        assert_eq!(deferred_tree.top_hashes.len(), NUM_CACHED_LEVELS as usize);
+        let root_key = HeightAndIndex {
+            height: deferred_tree.tree_height() - 1,
+            index: 0,
+        };
+        assert!(deferred_tree.hashes.get(&root_key).is_none());
+        let old_root = deferred_tree.root;
+        deferred_tree.remerkalize();
+        assert_eq!(deferred_tree.root, old_root);
+
+        for (block_height, root, length) in snapshots {
+            for account_id in accounts.iter().take(length as usize) {
+                let (proof, value) = deferred_tree
+                    .get_proof_at(account_id, block_height)
+                    .unwrap();
+                assert!(proof.is_valid(MerkleTreeFormat::LATEST, root, length, &value));
+            }
+        }
+        let values: Vec<u32> = (0..deferred_tree.len())
+            .map(|i| *deferred_tree.get_by_index(i).unwrap())
+            .collect();
+        assert_eq!(
+            OffchainMerkleTree::from_values(MerkleTreeFormat::LATEST, &values).root(),
+            deferred_tree.root
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The tree has pending updates")]
+    fn test_proof_with_pending_updates() {
+        let context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        let mut tree = MerkleTree::new(StorageKeys::Tree, 0u32);
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        tree.set_deferred(account_id.clone(), 1);
+        tree.get_proof(&account_id);
+    }
+
+    #[test]
+    fn test_remove_and_reuse() {
+        let mut context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        let mut tree = MerkleTree::new(StorageKeys::Tree, 0u32);
+        let mut offchain_tree = OffchainMerkleTree::default();
+        let accounts: Vec<AccountId> = (0..6)
+            .map(|i| format!("account{}.near", i).parse().unwrap())
+            .collect();
+        for (i, account_id) in accounts.iter().enumerate() {
+            tree.set(account_id.clone(), i as u32);
+            offchain_tree.push(&(i as u32));
+        }
+        context.block_index += 1;
+        testing_env!(context.clone());
+        let (old_snapshot, _) = tree.get_snapshot().unwrap();
+
+        assert_eq!(tree.remove(&accounts[2]), Some(2));
+        assert_eq!(tree.remove(&accounts[2]), None);
+        offchain_tree.clear(2);
+        assert_eq!(tree.len(), 6);
+        assert!(tree.get(&accounts[2]).is_none());
+        assert!(tree.get_by_index(2).is_none());
+        assert!(tree.get_proof(&accounts[2]).is_none());
+        assert_eq!(tree.root, offchain_tree.root());
+        let keys_root = tree.get_keys_root().unwrap();
+        let proof = tree.get_non_membership_proof(&accounts[2]).unwrap();
+        assert!(proof.is_valid(&accounts[2], keys_root, tree.len()));
+
+        context.block_index += 1;
+        testing_env!(context.clone());
+        let removed_block_height = context.block_index - 1;
+        let new_account_id: AccountId = "account2a.near".parse().unwrap();
+        tree.set(new_account_id.clone(), 100);
+        offchain_tree.set(2, &100u32);
+        assert_eq!(tree.len(), 6);
+        assert_eq!(tree.root, offchain_tree.root());
+        let (proof, value) = tree.get_proof(&new_account_id).unwrap();
+        assert_eq!(proof.index, 2);
+        assert!(proof.is_valid(tree.format, tree.root, tree.len(), &value));
+        assert!(tree.get_non_membership_proof(&new_account_id).is_none());
+        let keys_root = tree.get_keys_root().unwrap();
+        let proof = tree.get_non_membership_proof(&accounts[2]).unwrap();
+        assert!(proof.is_valid(&accounts[2], keys_root, tree.len()));
+
+        context.block_index += 1;
+        testing_env!(context.clone());
+        // The new account doesn't have the proofs at the snapshots before it reused the index.
+        assert!(tree
+            .get_proof_at(&new_account_id, old_snapshot.block_height)
+            .is_none());
+        assert!(tree
+            .get_proof_at(&new_account_id, removed_block_height)
+            .is_none());
+        let (proof, value) = tree
+            .get_proof_at(&accounts[3], old_snapshot.block_height)
+            .unwrap();
+        assert!(proof.is_valid(
+            old_snapshot.format,
+            old_snapshot.root.into(),
+            old_snapshot.length,
+            &value
+        ));
+        let (snapshot, _) = tree.get_snapshot_at(removed_block_height).unwrap();
+        let (proof, value) = tree
+            .get_proof_at(&accounts[3], removed_block_height)
+            .unwrap();
+        assert!(proof.is_valid(
+            snapshot.format,
+            snapshot.root.into(),
+            snapshot.length,
+            &value
+        ));
+
+        // The indexing of the sorted keys skips the empty leaves.
+        tree.remove(&accounts[4]);
+        let keys_root = tree.get_keys_root().unwrap();
+        tree.sorted_keys = SortedKeys::new(b"k".to_vec());
+        let mut account_ids = accounts.clone();
+        account_ids.push(new_account_id);
+        assert!(tree.index_keys(10, |value| {
+            if *value == 100 {
+                account_ids[6].clone()
+            } else {
+                account_ids[*value as usize].clone()
+            }
+        }));
+        assert_eq!(tree.get_keys_root(), Some(keys_root));
+    }
+
+    #[test]
+    fn test_domain_separated_hashes() {
+        let legacy = MerkleTreeFormat::Legacy;
+        let domain_separated = MerkleTreeFormat::DomainSeparated;
+        // A node hash in the legacy format can be passed off as a leaf value.
+        let (left, right) = (legacy.hash_leaf(&1u32), legacy.hash_leaf(&2u32));
+        let node = legacy.hash_node(&left, &right);
+        let fake_leaf = (left, right);
+        assert_eq!(legacy.hash_leaf(&fake_leaf), node);
+
+        let (left, right) = (
+            domain_separated.hash_leaf(&1u32),
+            domain_separated.hash_leaf(&2u32),
+        );
+        let node = domain_separated.hash_node(&left, &right);
+        let fake_leaf = (left, right);
+        assert_ne!(domain_separated.hash_leaf(&fake_leaf), node);
+
+        assert_eq!(legacy.empty_hash(3), CryptoHash::default());
+        assert_eq!(domain_separated.empty_hash(0), CryptoHash::default());
+        assert_eq!(
+            domain_separated.empty_hash(2),
+            domain_separated.hash_node(
+                &domain_separated.empty_hash(1),
+                &domain_separated.empty_hash(1)
+            )
+        );
+    }
+
+    #[test]
+    fn test_migrate_format() {
+        let mut context = VMContextBuilder::new().build();
+        testing_env!(context.clone());
+
+        let snapshot_retention_blocks = 12;
+        let mut tree = MerkleTree::new_with_snapshot_retention_blocks(
+            b"t".to_vec(),
+            0u32,
+            snapshot_retention_blocks,
+        );
+        tree.format = MerkleTreeFormat::Legacy;
+        assert!(tree.migrate_format(10));
+
+        let num_accounts = 13;
+        let accounts: Vec<AccountId> = (0..num_accounts)
+            .map(|i| format!("account{}", i).parse().unwrap())
+            .collect();
+        let mut values = vec![];
+        for (i, account_id) in accounts.iter().enumerate().take(10) {
+            context.block_index += 1;
+            testing_env!(context.clone());
+            tree.set(account_id.clone(), i as u32);
+            values.push(i as u32);
+        }
+        // Start the migration like the migration from the old tree does.
+        tree.format_migration = Some(FormatMigration {
+            format: MerkleTreeFormat::DomainSeparated,
+            hashes: VersionedMap::new(b"m".to_vec(), b"v".to_vec()),
+            num_migrated_leaves: 0,
+        });
+        context.block_index += 1;
+        testing_env!(context.clone());
+
+        // The values at the end of every block in the legacy format.
+        let mut legacy_snapshots = vec![(context.block_index, values.clone())];
+        let mut index = 0;
+        while !tree.migrate_format(3) {
+            assert_eq!(tree.get_format(), MerkleTreeFormat::Legacy);
+            assert_eq!(
+                OffchainMerkleTree::from_values(MerkleTreeFormat::Legacy, &values).root(),
+                tree.root
+            );
+            context.block_index += 1;
+            testing_env!(context.clone());
+            // Update migrated and not yet migrated leaves and add new ones.
+            let value = 100 + index;
+            let account_index = (index * 5) as usize % accounts.len();
+            tree.set(accounts[account_index].clone(), value);
+            if account_index < values.len() {
+                values[account_index] = value;
+            } else {
+                values.push(value);
+            }
+            legacy_snapshots.push((context.block_index, values.clone()));
+            index += 1;
+        }
+        // The format switch happened in the block with the last legacy update.
+        legacy_snapshots.pop();
+        assert_eq!(tree.get_format(), MerkleTreeFormat::DomainSeparated);
+        assert!(tree.format_migration.is_none());
+        assert_eq!(tree.len(), values.len() as u32);
+        assert_eq!(
+            OffchainMerkleTree::from_values(MerkleTreeFormat::DomainSeparated, &values).root(),
+            tree.root
+        );
+        let old_root = tree.root;
+        tree.remerkalize();
+        assert_eq!(tree.root, old_root);
+
+        // Update the leaves in the same block after the switch, including the ones that were not
+        // updated before the switch.
+        for (i, account_id) in accounts.iter().enumerate().take(values.len()) {
+            let value = 200 + i as u32;
+            tree.set(account_id.clone(), value);
+            values[i] = value;
+        }
+        let switch_block_height = context.block_index;
+        context.block_index += 1;
+        testing_env!(context.clone());
+
+        // The snapshots before the switch are retained in the legacy format.
+        for (block_height, values) in &legacy_snapshots {
+            let (snapshot, _) = tree.get_snapshot_at(*block_height).unwrap();
+            assert_eq!(snapshot.format, MerkleTreeFormat::Legacy);
+            assert_eq!(
+                CryptoHash::from(snapshot.root),
+                OffchainMerkleTree::from_values(MerkleTreeFormat::Legacy, values).root()
+            );
+            for (i, account_id) in accounts.iter().enumerate().take(values.len()) {
+                let (proof, value) = tree.get_proof_at(account_id, *block_height).unwrap();
+                assert_eq!(value, values[i]);
+                assert!(proof.is_valid(
+                    snapshot.format,
+                    snapshot.root.into(),
+                    snapshot.length,
+                    &value
+                ));
+                assert!(!proof.is_valid(
+                    MerkleTreeFormat::DomainSeparated,
+                    snapshot.root.into(),
+                    snapshot.length,
+                    &value
+                ));
+            }
+        }
+
+        let (snapshot, _) = tree.get_snapshot_at(switch_block_height).unwrap();
+        assert_eq!(snapshot.format, MerkleTreeFormat::DomainSeparated);
+        let (proof, value) = tree
+            .get_proof_at(&accounts[0], switch_block_height)
+            .unwrap();
+        assert!(proof.is_valid(
+            snapshot.format,
+            snapshot.root.into(),
+            snapshot.length,
+            &value
+        ));
+        assert!(tree.migrate_format(10));
+
+        // The hashes in the legacy format are dropped once the snapshots before the switch age out.
+        let last_legacy_block_height = legacy_snapshots.last().unwrap().0;
+        for _ in 0..=snapshot_retention_blocks {
+            context.block_index += 1;
+            testing_env!(context.clone());
+            tree.set(accounts[0].clone(), context.block_index as u32);
+            assert_eq!(
+                tree.previous_format_hashes.is_some(),
+                tree.get_snapshot_at(last_legacy_block_height).is_some()
+            );
+        }
+        assert!(tree.previous_format_hashes.is_none());
+        assert!(tree.get_snapshot_at(last_legacy_block_height).is_none());
     }
 
     #[test]
@@ -402,17 +2293,24 @@ mod tests {
                 let account_id = accounts[j].clone();
                 let (proof, value) = tree.get_proof(&account_id).unwrap();
                 assert_eq!(value, (i * num_accounts + j) as u32);
-                assert!(proof.is_valid(snapshot.root.into(), snapshot.length, &value));
+                assert!(proof.is_valid(
+                    snapshot.format,
+                    snapshot.root.into(),
+                    snapshot.length,
+                    &value
+                ));
                 for k in 0..i {
                     if k != j {
                         let other_account_id = accounts[k].clone();
                         let (other_proof, other_value) = tree.get_proof(&other_account_id).unwrap();
                         assert!(!proof.is_valid(
+                            snapshot.format,
                             snapshot.root.into(),
                             snapshot.length,
                             &other_value
                         ));
                         assert!(!other_proof.is_valid(
+                            snapshot.format,
                             snapshot.root.into(),
                             snapshot.length,
                             &value
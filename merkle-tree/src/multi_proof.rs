@@ -0,0 +1,109 @@
+use crate::hash::{tree_height, MerkleTreeFormat};
+use near_sdk::borsh::BorshSerialize;
+use near_sdk::json_types::Base58CryptoHash;
+use near_sdk::{near, CryptoHash};
+
+/// A proof of inclusion of multiple leaves in the Merkle tree.
+///
+/// The nodes on the paths from the leaves to the root are computed level by level from the
+/// bottom. The path contains the hashes of the siblings that can't be computed from the proven
+/// leaves, in the order they are needed: by height and then by index. The siblings that are shared
+/// by multiple leaves are included only once.
+/// The proof doesn't carry the hashing format, the verifier takes it from the snapshot.
+#[derive(Clone)]
+#[near(serializers=[borsh, json])]
+pub struct MerkleMultiProof {
+    /// The indices of the leaves in the tree in the increasing order.
+    pub indices: Vec<u32>,
+
+    /// The hashes of the siblings that are needed to compute the root.
+    pub path: Vec<Base58CryptoHash>,
+}
+
+impl MerkleMultiProof {
+    /// Creates the proof for the given leaf indices. The indices have to be sorted and unique.
+    /// The `get_hash` returns the hash of the node at the given height and index.
+    pub(crate) fn new<F>(indices: Vec<u32>, length: u32, get_hash: F) -> Self
+    where
+        F: Fn(u8, u32) -> CryptoHash,
+    {
+        let mut path = vec![];
+        let mut nodes = indices.clone();
+        for height in 0..tree_height(length).saturating_sub(1) {
+            let mut next_nodes = vec![];
+            let mut i = 0;
+            while i < nodes.len() {
+                let index = nodes[i];
+                if index & 1 == 0 && nodes.get(i + 1) == Some(&(index + 1)) {
+                    i += 2;
+                } else {
+                    path.push(get_hash(height, index ^ 1).into());
+                    i += 1;
+                }
+                next_nodes.push(index >> 1);
+            }
+            nodes = next_nodes;
+        }
+        Self { indices, path }
+    }
+
+    /// Verifies that the given values are the leaves at the proof indices in the tree with the
+    /// given hashing format, root and length. The values have to be in the order of the indices.
+    pub fn is_valid<T>(
+        &self,
+        format: MerkleTreeFormat,
+        root: CryptoHash,
+        length: u32,
+        values: &[T],
+    ) -> bool
+    where
+        T: BorshSerialize,
+    {
+        if self.indices.is_empty() || self.indices.len() != values.len() {
+            return false;
+        }
+        if self.indices.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return false;
+        }
+        if *self.indices.last().unwrap() >= length {
+            return false;
+        }
+
+        let mut nodes: Vec<(u32, CryptoHash)> = self
+            .indices
+            .iter()
+            .zip(values)
+            .map(|(&index, value)| (index, format.hash_leaf(value)))
+            .collect();
+        let mut path = self.path.iter().map(|hash| CryptoHash::from(*hash));
+        for _ in 0..tree_height(length) - 1 {
+            let mut next_nodes = vec![];
+            let mut i = 0;
+            while i < nodes.len() {
+                let (index, hash) = nodes[i];
+                let parent_hash = match nodes.get(i + 1) {
+                    Some((right_index, right_hash))
+                        if index & 1 == 0 && *right_index == index + 1 =>
+                    {
+                        i += 2;
+                        format.hash_node(&hash, right_hash)
+                    }
+                    _ => {
+                        let Some(sibling_hash) = path.next() else {
+                            return false;
+                        };
+                        i += 1;
+                        if index & 1 == 0 {
+                            format.hash_node(&hash, &sibling_hash)
+                        } else {
+                            format.hash_node(&sibling_hash, &hash)
+                        }
+                    }
+                };
+                next_nodes.push((index >> 1, parent_hash));
+            }
+            nodes = next_nodes;
+        }
+        path.next().is_none() && nodes.len() == 1 && nodes[0].1 == root
+    }
+}
@@ -0,0 +1,157 @@
+use crate::hash::{tree_height, MerkleTreeFormat};
+use crate::{MerkleMultiProof, MerkleProof};
+use near_sdk::borsh::BorshSerialize;
+use near_sdk::CryptoHash;
+
+/// In-memory Merkle tree that produces the same roots and proofs as the persistent `MerkleTree`.
+///
+/// It doesn't depend on the NEAR runtime, so it can be used off-chain, e.g. to rebuild the tree
+/// from the raw values returned in the index order of the persistent tree and to generate proofs
+/// that don't go stale.
+/// The format has to match the format of the persistent tree. The hash of a node without leaves
+/// under it is the empty subtree hash of the format.
+#[derive(Clone)]
+pub struct OffchainMerkleTree {
+    /// The hashing format of the tree.
+    format: MerkleTreeFormat,
+    /// The hashes of the nodes per height. The height zero contains the hashes of the leaves.
+    levels: Vec<Vec<CryptoHash>>,
+}
+
+impl Default for OffchainMerkleTree {
+    fn default() -> Self {
+        Self::new(MerkleTreeFormat::LATEST)
+    }
+}
+
+impl OffchainMerkleTree {
+    pub fn new(format: MerkleTreeFormat) -> Self {
+        Self {
+            format,
+            levels: vec![],
+        }
+    }
+
+    /// Builds the tree from the given values in the index order.
+    pub fn from_values<'a, T, I>(format: MerkleTreeFormat, values: I) -> Self
+    where
+        T: BorshSerialize + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        let mut tree = Self::new(format);
+        for value in values {
+            tree.push(value);
+        }
+        tree
+    }
+
+    pub fn format(&self) -> MerkleTreeFormat {
+        self.format
+    }
+
+    pub fn len(&self) -> u32 {
+        self.levels.first().map(|leaves| leaves.len()).unwrap_or(0) as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the root hash of the tree. The root of the empty tree is all zeros.
+    pub fn root(&self) -> CryptoHash {
+        match tree_height(self.len()) {
+            0 => CryptoHash::default(),
+            height => self.internal_get_hash(height - 1, 0),
+        }
+    }
+
+    /// Adds a new leaf with the given value to the end of the tree.
+    pub fn push<T>(&mut self, value: &T)
+    where
+        T: BorshSerialize,
+    {
+        let index = self.len();
+        self.internal_set_leaf_hash(index, self.format.hash_leaf(value));
+    }
+
+    /// Replaces the value of the existing leaf at the given index.
+    pub fn set<T>(&mut self, index: u32, value: &T)
+    where
+        T: BorshSerialize,
+    {
+        assert!(index < self.len(), "Index is out of bounds");
+        self.internal_set_leaf_hash(index, self.format.hash_leaf(value));
+    }
+
+    /// Adds a new empty leaf to the end of the tree, e.g. for the index of a removed account.
+    pub fn push_empty(&mut self) {
+        let index = self.len();
+        self.internal_set_leaf_hash(index, CryptoHash::default());
+    }
+
+    /// Clears the existing leaf at the given index, e.g. when the account is removed.
+    pub fn clear(&mut self, index: u32) {
+        assert!(index < self.len(), "Index is out of bounds");
+        self.internal_set_leaf_hash(index, CryptoHash::default());
+    }
+
+    /// Returns the proof for the leaf at the given index.
+    pub fn get_proof(&self, index: u32) -> Option<MerkleProof> {
+        if index >= self.len() {
+            return None;
+        }
+        let path = (0..tree_height(self.len()) - 1)
+            .map(|height| {
+                let sibling_index = (index >> height) ^ 1;
+                self.internal_get_hash(height, sibling_index).into()
+            })
+            .collect();
+        Some(MerkleProof { index, path })
+    }
+
+    /// Returns the proof for the leaves at the given indices. The duplicate indices are proven once.
+    pub fn get_multi_proof(&self, indices: &[u32]) -> Option<MerkleMultiProof> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.last()? >= &self.len() {
+            return None;
+        }
+        Some(MerkleMultiProof::new(
+            indices,
+            self.len(),
+            |height, index| self.internal_get_hash(height, index),
+        ))
+    }
+
+    fn internal_get_hash(&self, height: u8, index: u32) -> CryptoHash {
+        self.levels
+            .get(height as usize)
+            .and_then(|level| level.get(index as usize))
+            .cloned()
+            .unwrap_or_else(|| self.format.empty_hash(height))
+    }
+
+    fn internal_set_hash(&mut self, height: u8, index: u32, hash: CryptoHash) {
+        if self.levels.len() <= height as usize {
+            self.levels.push(vec![]);
+        }
+        let level = &mut self.levels[height as usize];
+        if index as usize == level.len() {
+            level.push(hash);
+        } else {
+            level[index as usize] = hash;
+        }
+    }
+
+    fn internal_set_leaf_hash(&mut self, index: u32, hash: CryptoHash) {
+        self.internal_set_hash(0, index, hash);
+        for height in 1..tree_height(self.len()) {
+            let height_index = index >> height;
+            let left_hash = self.internal_get_hash(height - 1, height_index << 1);
+            let right_hash = self.internal_get_hash(height - 1, (height_index << 1) + 1);
+            let hash = self.format.hash_node(&left_hash, &right_hash);
+            self.internal_set_hash(height, height_index, hash);
+        }
+    }
+}
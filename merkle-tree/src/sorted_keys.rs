@@ -0,0 +1,478 @@
+use crate::hash::{tree_height, MerkleTreeFormat};
+use crate::versions::VersionedMap;
+use crate::{HeightAndIndex, MerkleProof};
+use near_sdk::store::{LookupMap, TreeMap};
+use near_sdk::{near, AccountId, BorshStorageKey, CryptoHash, IntoStorageKey};
+use std::ops::Bound;
+
+#[derive(BorshStorageKey)]
+#[near(serializers=[borsh])]
+enum SortedKeysStorageKeys {
+    Sorted,
+    Hashes,
+    HashVersions,
+    Removed,
+}
+
+/// The number of the removed accounts that are checked for pruning on every removal.
+/// Every removal adds one account, so the pruning keeps up with the removals.
+const NUM_PRUNED_ACCOUNTS_PER_REMOVAL: u32 = 2;
+
+/// The format of the sorted keys tree. The tree didn't exist before the domain separated format.
+const FORMAT: MerkleTreeFormat = MerkleTreeFormat::DomainSeparated;
+
+/// A leaf of the sorted keys tree. The leaves form a linked list of the account IDs in the sorted
+/// order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[near(serializers=[borsh, json])]
+pub struct KeyLink {
+    /// The account ID of the leaf. `None` for the head of the list.
+    pub account_id: Option<AccountId>,
+
+    /// The next account ID in the sorted order. `None` for the last account.
+    pub next_account_id: Option<AccountId>,
+}
+
+/// The snapshots of the Merkle tree that contain an indexed account.
+#[derive(Clone, Copy)]
+#[near(serializers=[borsh])]
+struct IndexedRange {
+    /// The index of the account in the Merkle tree.
+    index: u32,
+    /// The number of the first snapshot that contains the account.
+    added_at: u64,
+    /// The number of the first snapshot that doesn't contain the account, if it was removed.
+    removed_at: Option<u64>,
+}
+
+impl IndexedRange {
+    fn contains(&self, snapshot_number: u64) -> bool {
+        self.added_at <= snapshot_number
+            && self
+                .removed_at
+                .is_none_or(|removed_at| snapshot_number < removed_at)
+    }
+}
+
+/// The accumulator of the account IDs of the Merkle tree in the sorted order.
+///
+/// It's a separate Merkle tree of `KeyLink` leaves. The leaf at the index `0` is the head of the
+/// list and the leaf of the account is at its index in the Merkle tree plus one. The leaves of the
+/// removed accounts are empty. An account is not in the tree if there is a link that skips over
+/// it.
+/// The hashes are versioned with the snapshots of the Merkle tree, and the removed accounts are
+/// kept until the snapshots that contain them are pruned, so the non-membership proofs can be
+/// generated against any retained snapshot. The current state of the tree has the number
+/// `num_snapshots` of the Merkle tree.
+#[near(serializers=[borsh])]
+pub struct SortedKeys {
+    /// The indexed account IDs in the sorted order with the snapshots that contain them.
+    sorted: TreeMap<AccountId, Vec<IndexedRange>>,
+    /// The hashes of the tree nodes.
+    hashes: VersionedMap<HeightAndIndex, CryptoHash>,
+    /// The removed accounts with the numbers of the snapshots they were removed at, in the order
+    /// of the removal.
+    removed: LookupMap<u64, (u64, AccountId)>,
+    /// The position of the first removed account that is not pruned.
+    removed_start: u64,
+    /// The position after the last removed account.
+    removed_end: u64,
+    /// The number of the indexed accounts. The accounts are indexed in the order of their indices
+    /// in the Merkle tree.
+    num_indexed: u32,
+    /// The root hash of the tree.
+    root: CryptoHash,
+}
+
+impl SortedKeys {
+    pub fn new(prefix: Vec<u8>) -> Self {
+        let mut sorted_keys = Self {
+            sorted: TreeMap::new(
+                [
+                    &prefix[..],
+                    &SortedKeysStorageKeys::Sorted.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            hashes: VersionedMap::new(
+                [
+                    &prefix[..],
+                    &SortedKeysStorageKeys::Hashes.into_storage_key()[..],
+                ]
+                .concat(),
+                [
+                    &prefix[..],
+                    &SortedKeysStorageKeys::HashVersions.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            removed: LookupMap::new(
+                [
+                    &prefix[..],
+                    &SortedKeysStorageKeys::Removed.into_storage_key()[..],
+                ]
+                .concat(),
+            ),
+            removed_start: 0,
+            removed_end: 0,
+            num_indexed: 0,
+            root: CryptoHash::default(),
+        };
+        // There are no snapshots yet, so the hashes are not versioned.
+        sorted_keys.internal_set_link(
+            0,
+            &KeyLink {
+                account_id: None,
+                next_account_id: None,
+            },
+            0,
+            0,
+        );
+        sorted_keys
+    }
+
+    /// Returns the number of the indexed accounts.
+    pub fn num_indexed(&self) -> u32 {
+        self.num_indexed
+    }
+
+    pub fn root(&self) -> CryptoHash {
+        self.root
+    }
+
+    /// Adds the account with the given index in the Merkle tree. The new indices have to be added
+    /// in the increasing order. The index of a removed account can be reused.
+    pub fn insert(
+        &mut self,
+        account_id: AccountId,
+        index: u32,
+        num_snapshots: u64,
+        oldest_snapshot_number: u64,
+    ) {
+        assert!(
+            index <= self.num_indexed,
+            "Accounts are indexed out of order"
+        );
+        let (prev_account_id, prev_index) = self.internal_prev(&account_id, num_snapshots);
+        let next_account_id = self.internal_next(&account_id, num_snapshots);
+        let mut ranges = self.sorted.get(&account_id).cloned().unwrap_or_default();
+        ranges.push(IndexedRange {
+            index,
+            added_at: num_snapshots,
+            removed_at: None,
+        });
+        self.sorted.insert(account_id.clone(), ranges);
+        if index == self.num_indexed {
+            self.num_indexed += 1;
+        }
+        self.internal_set_link(
+            index + 1,
+            &KeyLink {
+                account_id: Some(account_id.clone()),
+                next_account_id,
+            },
+            num_snapshots,
+            oldest_snapshot_number,
+        );
+        self.internal_set_link(
+            prev_index,
+            &KeyLink {
+                account_id: prev_account_id,
+                next_account_id: Some(account_id),
+            },
+            num_snapshots,
+            oldest_snapshot_number,
+        );
+    }
+
+    /// Skips the index of the removed account that doesn't need to be indexed.
+    pub fn skip(&mut self, index: u32, num_snapshots: u64, oldest_snapshot_number: u64) {
+        assert_eq!(index, self.num_indexed, "Accounts are indexed out of order");
+        self.num_indexed += 1;
+        self.internal_set_leaf_hash(
+            index + 1,
+            CryptoHash::default(),
+            num_snapshots,
+            oldest_snapshot_number,
+        );
+    }
+
+    /// Removes the account, if it is indexed. The leaf of the account becomes empty. The account
+    /// is kept until the snapshots that contain it are pruned.
+    pub fn remove(
+        &mut self,
+        account_id: &AccountId,
+        num_snapshots: u64,
+        oldest_snapshot_number: u64,
+    ) {
+        self.internal_prune_removed(oldest_snapshot_number, NUM_PRUNED_ACCOUNTS_PER_REMOVAL);
+        let Some(mut ranges) = self.sorted.get(account_id).cloned() else {
+            return;
+        };
+        let Some(range) = ranges
+            .last_mut()
+            .filter(|range| range.contains(num_snapshots))
+        else {
+            return;
+        };
+        let index = range.index;
+        if range.added_at == num_snapshots {
+            // The account was added after the last snapshot, so no snapshot contains it.
+            ranges.pop();
+        } else {
+            range.removed_at = Some(num_snapshots);
+            self.removed
+                .set(self.removed_end, Some((num_snapshots, account_id.clone())));
+            self.removed_end += 1;
+        }
+        if ranges.is_empty() {
+            self.sorted.remove(account_id);
+        } else {
+            self.sorted.insert(account_id.clone(), ranges);
+        }
+        let (prev_account_id, prev_index) = self.internal_prev(account_id, num_snapshots);
+        let next_account_id = self.internal_next(account_id, num_snapshots);
+        self.internal_set_leaf_hash(
+            index + 1,
+            CryptoHash::default(),
+            num_snapshots,
+            oldest_snapshot_number,
+        );
+        self.internal_set_link(
+            prev_index,
+            &KeyLink {
+                account_id: prev_account_id,
+                next_account_id,
+            },
+            num_snapshots,
+            oldest_snapshot_number,
+        );
+    }
+
+    /// Returns the proof that the given account is not indexed at the given snapshot number, or
+    /// `None` if it is. The `length` is the length of the Merkle tree at the snapshot.
+    pub fn get_non_membership_proof(
+        &self,
+        account_id: &AccountId,
+        snapshot_number: u64,
+        length: u32,
+    ) -> Option<MerkleNonMembershipProof> {
+        let is_indexed = self
+            .sorted
+            .get(account_id)
+            .is_some_and(|ranges| ranges.iter().any(|range| range.contains(snapshot_number)));
+        if is_indexed {
+            return None;
+        }
+        let (prev_account_id, index) = self.internal_prev(account_id, snapshot_number);
+        let next_account_id = self.internal_next(account_id, snapshot_number);
+        let path = (0..tree_height(length + 1) - 1)
+            .map(|height| {
+                let sibling_index = (index >> height) ^ 1;
+                self.internal_get_hash_at(height, sibling_index, snapshot_number)
+                    .into()
+            })
+            .collect();
+        Some(MerkleNonMembershipProof {
+            link: KeyLink {
+                account_id: prev_account_id,
+                next_account_id,
+            },
+            proof: MerkleProof { index, path },
+        })
+    }
+
+    /// Returns the number of the leaves in the tree including the head.
+    fn len(&self) -> u32 {
+        self.num_indexed + 1
+    }
+
+    /// Returns the index of the account in the Merkle tree at the given snapshot number, if it's
+    /// indexed at the snapshot.
+    fn internal_index_at(ranges: &[IndexedRange], snapshot_number: u64) -> Option<u32> {
+        ranges
+            .iter()
+            .find(|range| range.contains(snapshot_number))
+            .map(|range| range.index)
+    }
+
+    /// Returns the previous account ID before the given account ID at the given snapshot number
+    /// and its leaf index.
+    fn internal_prev(
+        &self,
+        account_id: &AccountId,
+        snapshot_number: u64,
+    ) -> (Option<AccountId>, u32) {
+        self.sorted
+            .range::<_, AccountId>(..account_id)
+            .rev()
+            .find_map(|(account_id, ranges)| {
+                Self::internal_index_at(ranges, snapshot_number)
+                    .map(|index| (Some(account_id.clone()), index + 1))
+            })
+            .unwrap_or((None, 0))
+    }
+
+    /// Returns the next account ID after the given account ID at the given snapshot number.
+    fn internal_next(&self, account_id: &AccountId, snapshot_number: u64) -> Option<AccountId> {
+        self.sorted
+            .range::<_, AccountId>((Bound::Excluded(account_id), Bound::Unbounded))
+            .find(|(_, ranges)| Self::internal_index_at(ranges, snapshot_number).is_some())
+            .map(|(account_id, _)| account_id.clone())
+    }
+
+    /// Removes up to `limit` removed accounts that are not contained in the retained snapshots.
+    fn internal_prune_removed(&mut self, oldest_snapshot_number: u64, limit: u32) {
+        for _ in 0..limit {
+            if self.removed_start == self.removed_end {
+                return;
+            }
+            let (removed_at, account_id) = self
+                .removed
+                .get(&self.removed_start)
+                .cloned()
+                .expect("Removed account is missing");
+            if removed_at > oldest_snapshot_number {
+                return;
+            }
+            self.removed.set(self.removed_start, None);
+            self.removed_start += 1;
+            let mut ranges = self.sorted.get(&account_id).cloned().unwrap_or_default();
+            ranges.retain(|range| {
+                range
+                    .removed_at
+                    .is_none_or(|removed_at| removed_at > oldest_snapshot_number)
+            });
+            if ranges.is_empty() {
+                self.sorted.remove(&account_id);
+            } else {
+                self.sorted.insert(account_id, ranges);
+            }
+        }
+    }
+
+    fn internal_get_hash(&self, height: u8, index: u32) -> CryptoHash {
+        self.hashes
+            .get(&HeightAndIndex { height, index })
+            .cloned()
+            .unwrap_or_else(|| FORMAT.empty_hash(height))
+    }
+
+    fn internal_get_hash_at(&self, height: u8, index: u32, snapshot_number: u64) -> CryptoHash {
+        let key = HeightAndIndex { height, index };
+        self.hashes
+            .get_at(&key, snapshot_number)
+            .or_else(|| self.hashes.get(&key))
+            .cloned()
+            .unwrap_or_else(|| FORMAT.empty_hash(height))
+    }
+
+    fn internal_set_link(
+        &mut self,
+        index: u32,
+        link: &KeyLink,
+        num_snapshots: u64,
+        oldest_snapshot_number: u64,
+    ) {
+        self.internal_set_leaf_hash(
+            index,
+            FORMAT.hash_leaf(link),
+            num_snapshots,
+            oldest_snapshot_number,
+        );
+    }
+
+    /// Sets the hash. The previous hash is saved as the version of the last snapshot, which was
+    /// taken at the beginning of the current block, if there is one.
+    fn internal_set_hash(
+        &mut self,
+        key: HeightAndIndex,
+        hash: CryptoHash,
+        num_snapshots: u64,
+        oldest_snapshot_number: u64,
+    ) {
+        match num_snapshots.checked_sub(1) {
+            Some(snapshot_number) => {
+                let empty_hash = FORMAT.empty_hash(key.height);
+                self.hashes.insert_versioned(
+                    key,
+                    hash,
+                    snapshot_number,
+                    oldest_snapshot_number,
+                    Some(empty_hash),
+                );
+            }
+            None => self.hashes.insert(key, hash),
+        }
+    }
+
+    fn internal_set_leaf_hash(
+        &mut self,
+        index: u32,
+        hash: CryptoHash,
+        num_snapshots: u64,
+        oldest_snapshot_number: u64,
+    ) {
+        self.internal_set_hash(
+            HeightAndIndex { height: 0, index },
+            hash,
+            num_snapshots,
+            oldest_snapshot_number,
+        );
+        let tree_height = tree_height(self.len());
+        for height in 1..tree_height {
+            let height_index = index >> height;
+            let left_hash = self.internal_get_hash(height - 1, height_index << 1);
+            let right_hash = self.internal_get_hash(height - 1, (height_index << 1) + 1);
+            self.internal_set_hash(
+                HeightAndIndex {
+                    height,
+                    index: height_index,
+                },
+                FORMAT.hash_node(&left_hash, &right_hash),
+                num_snapshots,
+                oldest_snapshot_number,
+            );
+        }
+        self.root = self.internal_get_hash(tree_height - 1, 0);
+    }
+}
+
+/// A proof that an account is not in the Merkle tree.
+///
+/// It proves the link of the sorted keys tree from the closest account before the given account
+/// to the closest account after it.
+#[derive(Clone)]
+#[near(serializers=[borsh, json])]
+pub struct MerkleNonMembershipProof {
+    /// The link that skips over the account.
+    pub link: KeyLink,
+
+    /// The proof of the link in the sorted keys tree.
+    pub proof: MerkleProof,
+}
+
+impl MerkleNonMembershipProof {
+    /// Verifies that the given account is not in the Merkle tree with the given length. The
+    /// `keys_root` is the root of the sorted keys tree of the Merkle tree.
+    pub fn is_valid(&self, account_id: &AccountId, keys_root: CryptoHash, length: u32) -> bool {
+        let Some(keys_length) = length.checked_add(1) else {
+            return false;
+        };
+        // Only the head of the list doesn't have an account ID.
+        let is_after_prev = match self.link.account_id.as_ref() {
+            Some(prev_account_id) => prev_account_id < account_id,
+            None => self.proof.index == 0,
+        };
+        let is_before_next = self
+            .link
+            .next_account_id
+            .as_ref()
+            .is_none_or(|next_account_id| account_id < next_account_id);
+        is_after_prev
+            && is_before_next
+            && self
+                .proof
+                .is_valid(FORMAT, keys_root, keys_length, &self.link)
+    }
+}
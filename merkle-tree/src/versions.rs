@@ -0,0 +1,378 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::store::LookupMap;
+use near_sdk::{near, BorshStorageKey, IntoStorageKey};
+
+#[derive(BorshStorageKey)]
+#[near(serializers=[borsh])]
+enum VersionsStorageKeys {
+    Chunks,
+    PruningQueue,
+}
+
+/// The maximum number of the versions that are stored together with the value of a key. Once there
+/// are more versions, the older ones are moved to a separate chunk.
+pub(crate) const MAX_INLINE_VERSIONS: usize = 8;
+
+/// The number of the chunks that are checked for pruning on every new chunk.
+/// Every new chunk is added to the pruning queue, so the pruning keeps up with the new chunks.
+const NUM_PRUNED_CHUNKS_PER_SPILL: u32 = 2;
+
+/// The value of a key with its previous versions.
+///
+/// The value is serialized first and the versions are only serialized if there are any, so a value
+/// without versions has the same layout as the plain value. It allows to read the maps that were
+/// stored before the versions were introduced. The versions are read till the end of the input, so
+/// the entry can only be stored as a whole map value.
+struct Entry<T> {
+    value: T,
+    versions: EntryVersions<T>,
+}
+
+/// The previous versions of a key. The oldest versions are in the chunks and the most recent ones
+/// are inline.
+#[near(serializers=[borsh])]
+struct EntryVersions<T> {
+    /// The most recent versions as pairs of the snapshot number and the value.
+    inline: Vec<(u64, T)>,
+    /// The chunks with the older versions.
+    chunks: Option<ChunkRange>,
+}
+
+/// The chunks of the versions of a key.
+#[derive(Clone, Copy)]
+#[near(serializers=[borsh])]
+struct ChunkRange {
+    /// The unique ID of the chunks of the key.
+    id: u64,
+    /// The index of the oldest chunk that might be retained.
+    first: u32,
+    /// The index after the last chunk.
+    end: u32,
+    /// The snapshot number of the last version in the last chunk.
+    last_snapshot_number: u64,
+}
+
+impl<T> EntryVersions<T> {
+    fn is_empty(&self) -> bool {
+        self.inline.is_empty() && self.chunks.is_none()
+    }
+
+    fn last_snapshot_number(&self) -> Option<u64> {
+        self.inline
+            .last()
+            .map(|(snapshot_number, _)| *snapshot_number)
+            .or_else(|| self.chunks.map(|chunks| chunks.last_snapshot_number))
+    }
+}
+
+impl<T: BorshSerialize> BorshSerialize for Entry<T> {
+    fn serialize<W: borsh::io::Write>(&self, writer: &mut W) -> borsh::io::Result<()> {
+        self.value.serialize(writer)?;
+        if !self.versions.is_empty() {
+            self.versions.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: BorshDeserialize> BorshDeserialize for Entry<T> {
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<Self> {
+        let value = T::deserialize_reader(reader)?;
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest)?;
+        let versions = if rest.is_empty() {
+            EntryVersions {
+                inline: vec![],
+                chunks: None,
+            }
+        } else {
+            EntryVersions::try_from_slice(&rest)?
+        };
+        Ok(Self { value, versions })
+    }
+}
+
+/// The chunks of the older versions of all keys with the queue to prune them.
+#[near(serializers=[borsh])]
+struct VersionChunks<T>
+where
+    T: BorshSerialize + BorshDeserialize,
+{
+    /// The versions by the ID of the chunks of the key and the index of the chunk.
+    chunks: LookupMap<(u64, u32), Vec<(u64, T)>>,
+    /// The chunks with the snapshot numbers of their last versions in the order they were added.
+    pruning_queue: LookupMap<u64, (u64, u64, u32)>,
+    /// The position of the first entry in the pruning queue.
+    pruning_queue_start: u64,
+    /// The position after the last entry in the pruning queue.
+    pruning_queue_end: u64,
+    /// The ID for the chunks of the next key.
+    next_id: u64,
+}
+
+impl<T> VersionChunks<T>
+where
+    T: BorshSerialize + BorshDeserialize,
+{
+    /// Adds the versions as the next chunk of the key and prunes the chunks that are no longer
+    /// needed.
+    fn push(
+        &mut self,
+        range: Option<ChunkRange>,
+        versions: Vec<(u64, T)>,
+        oldest_snapshot_number: u64,
+    ) -> ChunkRange {
+        let last_snapshot_number = versions.last().expect("No versions to push").0;
+        let mut range = range.unwrap_or_else(|| {
+            self.next_id += 1;
+            ChunkRange {
+                id: self.next_id - 1,
+                first: 0,
+                end: 0,
+                last_snapshot_number,
+            }
+        });
+        self.chunks.set((range.id, range.end), Some(versions));
+        self.pruning_queue.set(
+            self.pruning_queue_end,
+            Some((last_snapshot_number, range.id, range.end)),
+        );
+        self.pruning_queue_end += 1;
+        range.end += 1;
+        range.last_snapshot_number = last_snapshot_number;
+        self.internal_prune(oldest_snapshot_number, NUM_PRUNED_CHUNKS_PER_SPILL);
+        // Skips the pruned chunks, so the search doesn't need to go over them.
+        while range.first < range.end && !self.chunks.contains_key(&(range.id, range.first)) {
+            range.first += 1;
+        }
+        range
+    }
+
+    /// Returns the first version with the snapshot number not less than the given one. The last
+    /// version of the range has to be not less than the given snapshot number.
+    fn find(&self, range: &ChunkRange, snapshot_number: u64) -> &T {
+        // Binary search for the first chunk with the last version not less than the snapshot
+        // number. The pruned chunks only have the versions before the oldest retained snapshot.
+        let mut left = range.first;
+        let mut right = range.end - 1;
+        while left < right {
+            let mid = left + (right - left) / 2;
+            let is_before = self
+                .chunks
+                .get(&(range.id, mid))
+                .is_none_or(|versions| versions.last().unwrap().0 < snapshot_number);
+            if is_before {
+                left = mid + 1;
+            } else {
+                right = mid;
+            }
+        }
+        self.chunks
+            .get(&(range.id, left))
+            .expect("Version chunk is missing")
+            .iter()
+            .find(|(version_number, _)| *version_number >= snapshot_number)
+            .map(|(_, value)| value)
+            .unwrap()
+    }
+
+    /// Removes up to `limit` chunks from the front of the pruning queue, if all their versions are
+    /// before the oldest retained snapshot.
+    fn internal_prune(&mut self, oldest_snapshot_number: u64, limit: u32) {
+        for _ in 0..limit {
+            if self.pruning_queue_start == self.pruning_queue_end {
+                return;
+            }
+            let &(last_snapshot_number, id, index) = self
+                .pruning_queue
+                .get(&self.pruning_queue_start)
+                .expect("Pruning queue entry is missing");
+            if last_snapshot_number >= oldest_snapshot_number {
+                return;
+            }
+            self.pruning_queue.set(self.pruning_queue_start, None);
+            self.pruning_queue_start += 1;
+            self.chunks.set((id, index), None);
+        }
+    }
+}
+
+/// A map that keeps the previous values of the keys at the snapshots of the Merkle tree.
+///
+/// Before a key is modified for the first time after the snapshot number `n` was taken, its value
+/// is saved as the version `n`. The value of the key at the snapshot `m` is the value of the first
+/// version with the number not less than `m`, or the current value if there is no such version.
+/// The versions with the numbers before the oldest retained snapshot are no longer needed.
+///
+/// The recent versions are stored together with the value, so saving a version doesn't need
+/// separate storage reads and writes. Once a key has more than `MAX_INLINE_VERSIONS` versions, they
+/// are moved to a new chunk. The chunks are pruned in the order they were added, once all their
+/// versions are no longer needed. The outdated inline versions are dropped on the next save.
+#[near(serializers=[borsh])]
+pub struct VersionedMap<K, T>
+where
+    K: BorshSerialize + Ord,
+    T: BorshSerialize + BorshDeserialize,
+{
+    entries: LookupMap<K, Entry<T>>,
+    chunks: VersionChunks<T>,
+}
+
+impl<K, T> VersionedMap<K, T>
+where
+    K: BorshSerialize + Ord + Clone,
+    T: BorshSerialize + BorshDeserialize + Clone,
+{
+    /// Creates the map with the given prefix of the values and the prefix of the older versions.
+    /// The values can be stored before as a `LookupMap` with the same prefix.
+    pub fn new(prefix: Vec<u8>, versions_prefix: Vec<u8>) -> Self {
+        Self {
+            entries: LookupMap::new(prefix),
+            chunks: VersionChunks {
+                chunks: LookupMap::new(
+                    [
+                        &versions_prefix[..],
+                        &VersionsStorageKeys::Chunks.into_storage_key()[..],
+                    ]
+                    .concat(),
+                ),
+                pruning_queue: LookupMap::new(
+                    [
+                        &versions_prefix[..],
+                        &VersionsStorageKeys::PruningQueue.into_storage_key()[..],
+                    ]
+                    .concat(),
+                ),
+                pruning_queue_start: 0,
+                pruning_queue_end: 0,
+                next_id: 0,
+            },
+        }
+    }
+
+    /// Returns the current value of the key.
+    pub fn get(&self, key: &K) -> Option<&T> {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Returns the value of the key at the given snapshot number. Returns `None` if the value
+    /// wasn't modified since the snapshot, so the current value should be used.
+    pub fn get_at(&self, key: &K, snapshot_number: u64) -> Option<&T> {
+        let versions = &self.entries.get(key)?.versions;
+        if let Some(range) = versions
+            .chunks
+            .as_ref()
+            .filter(|range| range.last_snapshot_number >= snapshot_number)
+        {
+            return Some(self.chunks.find(range, snapshot_number));
+        }
+        versions
+            .inline
+            .iter()
+            .find(|(version_number, _)| *version_number >= snapshot_number)
+            .map(|(_, value)| value)
+    }
+
+    /// Sets the value of the key without saving the previous value as a version.
+    pub fn insert(&mut self, key: K, value: T) {
+        match self.entries.get_mut(&key) {
+            Some(entry) => entry.value = value,
+            None => {
+                self.entries.insert(
+                    key,
+                    Entry {
+                        value,
+                        versions: EntryVersions {
+                            inline: vec![],
+                            chunks: None,
+                        },
+                    },
+                );
+            }
+        }
+    }
+
+    /// Sets the value of the key and returns the previous value. The previous value is saved as the
+    /// version `snapshot_number`, unless it was already saved. If the key doesn't exist, the
+    /// `absent_value` is saved instead, or nothing if it's `None`.
+    /// The versions before the `oldest_snapshot_number` are dropped.
+    pub fn insert_versioned(
+        &mut self,
+        key: K,
+        value: T,
+        snapshot_number: u64,
+        oldest_snapshot_number: u64,
+        absent_value: Option<T>,
+    ) -> Option<T> {
+        let (version, old_value) = match self.entries.get_mut(&key) {
+            Some(entry) => {
+                let old_value = std::mem::replace(&mut entry.value, value);
+                (old_value.clone(), Some(old_value))
+            }
+            None => {
+                self.insert(key.clone(), value);
+                // Nothing is saved for the new key without the absent value.
+                (absent_value?, None)
+            }
+        };
+        let versions = &mut self.entries.get_mut(&key).unwrap().versions;
+        if versions.last_snapshot_number() == Some(snapshot_number) {
+            return old_value;
+        }
+        let num_outdated = versions
+            .inline
+            .iter()
+            .take_while(|(version_number, _)| *version_number < oldest_snapshot_number)
+            .count();
+        versions.inline.drain(..num_outdated);
+        if versions
+            .chunks
+            .is_some_and(|range| range.last_snapshot_number < oldest_snapshot_number)
+        {
+            // The outdated chunks are pruned from the queue.
+            versions.chunks = None;
+        }
+        versions.inline.push((snapshot_number, version));
+        if versions.inline.len() > MAX_INLINE_VERSIONS {
+            let chunk = versions.inline.drain(..MAX_INLINE_VERSIONS).collect();
+            versions.chunks = Some(self.chunks.push(
+                versions.chunks,
+                chunk,
+                oldest_snapshot_number,
+            ));
+        }
+        old_value
+    }
+
+    /// Removes the key with its versions and returns its value. The chunks of the versions are
+    /// pruned from the queue.
+    pub fn remove(&mut self, key: &K) -> Option<T> {
+        self.entries.remove(key).map(|entry| entry.value)
+    }
+
+    /// Returns the number of the stored versions of the key, including the outdated ones that are
+    /// not dropped yet.
+    #[cfg(test)]
+    pub fn num_versions(&self, key: &K) -> usize {
+        let Some(entry) = self.entries.get(key) else {
+            return 0;
+        };
+        let num_chunk_versions: usize = entry
+            .versions
+            .chunks
+            .map(|range| {
+                (range.first..range.end)
+                    .filter_map(|index| self.chunks.chunks.get(&(range.id, index)))
+                    .map(Vec::len)
+                    .sum()
+            })
+            .unwrap_or(0);
+        entry.versions.inline.len() + num_chunk_versions
+    }
+
+    /// Returns the number of the stored chunks of the versions.
+    #[cfg(test)]
+    pub fn num_chunks(&self) -> u64 {
+        self.chunks.pruning_queue_end - self.chunks.pruning_queue_start
+    }
+}
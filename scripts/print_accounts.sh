@@ -13,7 +13,7 @@ export VENEAR_ACCOUNT_ID="v.$ROOT_ACCOUNT_ID"
 
 DATA=$(near --quiet contract call-function as-read-only $VENEAR_ACCOUNT_ID get_accounts json-args '{}' network-config $CHAIN_ID now)
 
-export BALANCES=$(echo $DATA | jq 'map({
+export BALANCES=$(echo $DATA | jq '.accounts | map({
   account_id: .account.account_id,
   venear: (((.account.delegated_balance.extra_venear_balance | tonumber) +
    (.account.delegated_balance.near_balance | tonumber) + (
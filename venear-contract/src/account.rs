@@ -1,7 +1,14 @@
+use crate::top_accounts::internal_voting_power;
 use crate::*;
-use common::{events, near_add, truncate_to_seconds, VenearBalance, Version};
+use common::{events, page_range, truncate_to_seconds, VenearBalance, Version};
 use near_sdk::json_types::U64;
 
+/// The maximum number of accounts returned by `get_accounts`.
+pub const MAX_ACCOUNTS_PAGE_SIZE: u32 = 100;
+
+/// The maximum number of accounts returned by `get_accounts_raw` and `get_account_summaries`.
+pub const MAX_RAW_ACCOUNTS_PAGE_SIZE: u32 = 500;
+
 /// Full information about the account
 #[derive(Clone)]
 #[near(serializers=[json])]
@@ -25,11 +32,92 @@ pub struct AccountInternal {
 
     /// The nonce of the last lockup update.
     pub lockup_update_nonce: U64,
+
+    /// The account ID of the lockup contract that was transferred from another owner or migrated
+    /// to. None means the lockup account ID is derived from the account ID.
+    pub lockup_account_id: Option<AccountId>,
+
+    /// The new lockup contract deployed for the migration, that the current lockup contract hasn't
+    /// migrated to yet.
+    pub lockup_migration: Option<LockupMigration>,
+
+    /// The previous lockup contract that migrated to the current lockup contract, until the
+    /// current lockup contract accepts the migration. The migration is rolled back to the previous
+    /// lockup contract if the acceptance fails.
+    pub migrated_from_lockup: Option<LockupMigration>,
+}
+
+/// The lockup contract on either side of the lockup migration.
+#[derive(Clone)]
+#[near(serializers=[borsh, json])]
+pub struct LockupMigration {
+    /// The account ID of the lockup contract.
+    pub lockup_account_id: AccountId,
+
+    /// The version of the lockup contract.
+    pub lockup_version: Version,
+
+    /// The nonce of the lockup updates of the lockup contract at the migration.
+    pub lockup_update_nonce: U64,
+}
+
+/// Internal account information before the lockup ownership transfers and migrations were
+/// introduced.
+#[derive(Clone)]
+#[near(serializers=[borsh])]
+pub struct AccountInternalV0 {
+    pub lockup_version: Option<Version>,
+    pub deposit: NearToken,
+    pub lockup_update_nonce: U64,
+}
+
+/// The account ID and the current veNEAR balance of the account.
+#[derive(Clone)]
+#[near(serializers=[json])]
+pub struct AccountSummary {
+    pub account_id: AccountId,
+
+    /// The current veNEAR balance, including the delegated balance.
+    pub balance: NearToken,
+}
+
+/// A page of the account info.
+#[derive(Clone)]
+#[near(serializers=[json])]
+pub struct AccountInfos {
+    /// The info of the registered accounts in the page. The unregistered accounts are skipped.
+    pub accounts: Vec<AccountInfo>,
+
+    /// The index to request the next page from. `None` if it's the last page.
+    pub next_from_index: Option<u32>,
+}
+
+/// A page of the raw account data.
+#[derive(Clone)]
+#[near(serializers=[json])]
+pub struct RawAccounts {
+    /// The raw data of the registered accounts in the page. The unregistered accounts are skipped.
+    pub accounts: Vec<VAccount>,
+
+    /// The index to request the next page from. `None` if it's the last page.
+    pub next_from_index: Option<u32>,
+}
+
+/// A page of the account summaries.
+#[derive(Clone)]
+#[near(serializers=[json])]
+pub struct AccountSummaries {
+    /// The summaries of the registered accounts in the page. The unregistered accounts are skipped.
+    pub accounts: Vec<AccountSummary>,
+
+    /// The index to request the next page from. `None` if it's the last page.
+    pub next_from_index: Option<u32>,
 }
 
 #[derive(Clone)]
 #[near(serializers=[borsh])]
 pub enum VAccountInternal {
+    V0(AccountInternalV0),
     Current(AccountInternal),
 }
 
@@ -42,6 +130,14 @@ impl From<AccountInternal> for VAccountInternal {
 impl From<VAccountInternal> for AccountInternal {
     fn from(value: VAccountInternal) -> Self {
         match value {
+            VAccountInternal::V0(account) => AccountInternal {
+                lockup_version: account.lockup_version,
+                deposit: account.deposit,
+                lockup_update_nonce: account.lockup_update_nonce,
+                lockup_account_id: None,
+                lockup_migration: None,
+                migrated_from_lockup: None,
+            },
             VAccountInternal::Current(account) => account,
         }
     }
@@ -58,9 +154,10 @@ impl Contract {
             })
     }
 
-    /// Returns the number of accounts.
+    /// Returns the number of accounts, including the indices of the unregistered accounts that are
+    /// not reused yet.
     pub fn get_num_accounts(&self) -> u32 {
-        self.tree.len() as u32
+        self.tree.len()
     }
 
     /// Returns the account info for a given index in the Merkle tree.
@@ -78,26 +175,83 @@ impl Contract {
         })
     }
 
-    /// Returns a list of account info from the given index based on the merkle tree order.
-    pub fn get_accounts(&self, from_index: Option<u32>, limit: Option<u32>) -> Vec<AccountInfo> {
-        let from_index = from_index.unwrap_or(0);
-        let limit = limit.unwrap_or(u32::MAX);
-        let to_index = std::cmp::min(from_index.saturating_add(limit), self.get_num_accounts());
-        (from_index..to_index)
-            .into_iter()
-            .filter_map(|i| self.get_account_by_index(i))
-            .collect()
-    }
-
-    /// Returns a list of raw account data from the given index based on the merkle tree order.
-    pub fn get_accounts_raw(&self, from_index: Option<u32>, limit: Option<u32>) -> Vec<&VAccount> {
-        let from_index = from_index.unwrap_or(0);
-        let limit = limit.unwrap_or(u32::MAX);
-        let to_index = std::cmp::min(from_index.saturating_add(limit), self.get_num_accounts());
-        (from_index..to_index)
-            .into_iter()
+    /// Returns a list of account info from the given index based on the merkle tree order,
+    /// together with the index of the next page.
+    /// The limit is capped by `MAX_ACCOUNTS_PAGE_SIZE`.
+    pub fn get_accounts(&self, from_index: Option<u32>, limit: Option<u32>) -> AccountInfos {
+        let num_accounts = self.get_num_accounts();
+        let range = page_range(
+            from_index.unwrap_or(0),
+            limit,
+            MAX_ACCOUNTS_PAGE_SIZE,
+            num_accounts,
+        );
+        let next_from_index = Some(range.end).filter(|&index| index < num_accounts);
+        AccountInfos {
+            accounts: range.filter_map(|i| self.get_account_by_index(i)).collect(),
+            next_from_index,
+        }
+    }
+
+    /// Returns the account IDs and the current veNEAR balances from the given index based on the
+    /// merkle tree order, together with the index of the next page.
+    /// The limit is capped by `MAX_RAW_ACCOUNTS_PAGE_SIZE`.
+    pub fn get_account_summaries(
+        &self,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> AccountSummaries {
+        let num_accounts = self.get_num_accounts();
+        let range = page_range(
+            from_index.unwrap_or(0),
+            limit,
+            MAX_RAW_ACCOUNTS_PAGE_SIZE,
+            num_accounts,
+        );
+        let next_from_index = Some(range.end).filter(|&index| index < num_accounts);
+        let current_timestamp = env::block_timestamp().into();
+        let venear_growth_config = self.internal_get_venear_growth_config();
+        let accounts = range
             .filter_map(|i| self.tree.get_by_index(i))
-            .collect()
+            .map(|account| {
+                let account: Account = account.clone().into();
+                AccountSummary {
+                    balance: account.total_balance(current_timestamp, venear_growth_config),
+                    account_id: account.account_id,
+                }
+            })
+            .collect();
+        AccountSummaries {
+            accounts,
+            next_from_index,
+        }
+    }
+
+    /// Returns the indices of the unregistered accounts that are not reused yet. The leaves at
+    /// these indices are empty.
+    pub fn get_free_indices(&self) -> Vec<u32> {
+        self.tree.get_free_indices()
+    }
+
+    /// Returns a list of raw account data from the given index based on the merkle tree order,
+    /// together with the index of the next page.
+    /// The limit is capped by `MAX_RAW_ACCOUNTS_PAGE_SIZE`.
+    pub fn get_accounts_raw(&self, from_index: Option<u32>, limit: Option<u32>) -> RawAccounts {
+        let num_accounts = self.get_num_accounts();
+        let range = page_range(
+            from_index.unwrap_or(0),
+            limit,
+            MAX_RAW_ACCOUNTS_PAGE_SIZE,
+            num_accounts,
+        );
+        let next_from_index = Some(range.end).filter(|&index| index < num_accounts);
+        RawAccounts {
+            accounts: range
+                .filter_map(|i| self.tree.get_by_index(i))
+                .cloned()
+                .collect(),
+            next_from_index,
+        }
     }
 }
 
@@ -110,24 +264,46 @@ impl Contract {
                     lockup_version: None,
                     deposit,
                     lockup_update_nonce: 0.into(),
+                    lockup_account_id: None,
+                    lockup_migration: None,
+                    migrated_from_lockup: None,
                 },
             )
             .is_none(),
             "Already registered"
         );
         let mut global_state: GlobalState = self.internal_global_state_updated();
-        let account = Account {
+        let mut account = Account {
             account_id: account_id.clone(),
             update_timestamp: truncate_to_seconds(env::block_timestamp().into()),
             balance: VenearBalance::from_near(deposit),
             delegated_balance: Default::default(),
             delegation: None,
+            lock_boost: None,
         };
-        global_state.total_venear_balance = global_state
-            .total_venear_balance
-            .pooled_add(&account.balance);
+        self.internal_add_to_total_balance(&mut global_state, &mut account);
         self.internal_set_account(account_id.clone(), account);
         self.internal_set_global_state(global_state);
+        self.tree.flush();
+    }
+
+    /// Removes the account from the tree and burns its veNEAR balance. The checkpoints of the
+    /// account are removed.
+    pub fn internal_unregister_account(&mut self, account_id: &AccountId, account: Account) {
+        self.internal_take_epoch_snapshots();
+        let mut global_state: GlobalState = self.internal_global_state_updated();
+        self.internal_remove_from_total_balance(&mut global_state, &account);
+        let old_account = self.internal_get_account(account_id).unwrap();
+        self.internal_update_top_accounts(Some(&old_account), None);
+        self.internal_remove_account_checkpoints(account_id);
+        let balance = account.balance.total();
+        if balance > NearToken::from_near(0) {
+            events::emit::ft_burn(account_id, balance);
+        }
+        self.accounts.remove(account_id);
+        self.tree.remove_deferred(account_id);
+        self.internal_set_global_state(global_state);
+        self.tree.flush();
     }
 
     pub fn internal_get_account_internal(&self, account_id: &AccountId) -> Option<AccountInternal> {
@@ -163,28 +339,26 @@ impl Contract {
         account
     }
 
+    /// Updates the account in the Merkle tree. The hashes of the tree are updated once per call by
+    /// `self.tree.flush()`, which has to be called after all accounts are updated.
     pub fn internal_set_account(&mut self, account_id: AccountId, account: Account) {
-        // Previous balance
-        let old_balance = self
-            .internal_get_account(&account_id)
-            .map(|account| {
-                let mut total = account.delegated_balance.total();
-                if account.delegation.is_none() {
-                    total = near_add(total, account.balance.total());
-                }
-                total
-            })
+        self.internal_take_epoch_snapshots();
+        let old_account = self.internal_get_account(&account_id);
+        let old_balance = old_account
+            .as_ref()
+            .map(internal_voting_power)
             .unwrap_or_default();
-        // New balance
-        let mut new_balance = account.delegated_balance.total();
-        if account.delegation.is_none() {
-            new_balance = near_add(new_balance, account.balance.total());
-        }
+        let new_balance = internal_voting_power(&account);
         if new_balance > old_balance {
             events::emit::ft_mint(&account_id, new_balance.checked_sub(old_balance).unwrap());
         } else if new_balance < old_balance {
             events::emit::ft_burn(&account_id, old_balance.checked_sub(new_balance).unwrap());
         }
-        self.tree.set(account_id, account.into());
+        self.internal_update_top_accounts(old_account.as_ref(), Some(&account));
+        if let Some(old_account) = old_account {
+            let update_timestamp = account.update_timestamp;
+            self.internal_add_account_checkpoint(&account_id, old_account, update_timestamp);
+        }
+        self.tree.set_deferred(account_id, account.into());
     }
 }
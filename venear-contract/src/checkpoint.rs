@@ -0,0 +1,120 @@
+use crate::*;
+use common::{truncate_to_seconds, TimestampNs};
+
+/// The maximum number of checkpoints kept per account. The older checkpoints are removed, so the
+/// storage used by the history of the account is bounded.
+pub const MAX_NUM_ACCOUNT_CHECKPOINTS: u32 = 16;
+
+#[near]
+impl Contract {
+    /// Returns the number of historical checkpoints of the given account that are kept.
+    pub fn get_num_account_checkpoints(&self, account_id: AccountId) -> u32 {
+        let (from_index, to_index) = self.internal_account_checkpoints_range(&account_id);
+        to_index - from_index
+    }
+
+    /// Returns the account value that was active at the given past timestamp. The value is not
+    /// updated to the timestamp.
+    /// Returns `None` if the account was not registered at the timestamp, or the history of the
+    /// account doesn't go back to it.
+    pub fn get_account_at(&self, account_id: AccountId, timestamp: TimestampNs) -> Option<Account> {
+        require!(
+            timestamp.0 <= env::block_timestamp(),
+            "Timestamp must not be in the future"
+        );
+        self.internal_get_account_at(&account_id, timestamp)
+    }
+}
+
+impl Contract {
+    /// Returns the range of indices of the checkpoints of the given account that are kept.
+    fn internal_account_checkpoints_range(&self, account_id: &AccountId) -> (u32, u32) {
+        let num_checkpoints = self
+            .num_account_checkpoints
+            .get(account_id)
+            .cloned()
+            .unwrap_or_default();
+        (
+            num_checkpoints.saturating_sub(MAX_NUM_ACCOUNT_CHECKPOINTS),
+            num_checkpoints,
+        )
+    }
+
+    /// Stores the previous value of the account as a checkpoint. The checkpoint is active from its
+    /// `update_timestamp` until the `update_timestamp` of the next checkpoint or the current value.
+    /// The oldest checkpoint is removed, once the account has `MAX_NUM_ACCOUNT_CHECKPOINTS`.
+    pub fn internal_add_account_checkpoint(
+        &mut self,
+        account_id: &AccountId,
+        account: Account,
+        update_timestamp: TimestampNs,
+    ) {
+        // The previous value that was set at the same time can't be observed.
+        if account.update_timestamp >= update_timestamp {
+            return;
+        }
+        let (from_index, to_index) = self.internal_account_checkpoints_range(account_id);
+        if to_index - from_index == MAX_NUM_ACCOUNT_CHECKPOINTS {
+            self.account_checkpoints
+                .set((account_id.clone(), from_index), None);
+        }
+        self.account_checkpoints
+            .set((account_id.clone(), to_index), Some(account.into()));
+        self.num_account_checkpoints
+            .set(account_id.clone(), Some(to_index + 1));
+    }
+
+    /// Removes the checkpoints of the unregistered account, since its storage deposit is
+    /// refunded.
+    pub fn internal_remove_account_checkpoints(&mut self, account_id: &AccountId) {
+        let (from_index, to_index) = self.internal_account_checkpoints_range(account_id);
+        for index in from_index..to_index {
+            self.account_checkpoints
+                .set((account_id.clone(), index), None);
+        }
+        self.num_account_checkpoints.set(account_id.clone(), None);
+    }
+
+    /// Returns the account value that was active at the given timestamp.
+    pub fn internal_get_account_at(
+        &self,
+        account_id: &AccountId,
+        timestamp: TimestampNs,
+    ) -> Option<Account> {
+        let timestamp = truncate_to_seconds(timestamp);
+        let account = self.internal_get_account(account_id)?;
+        if account.update_timestamp <= timestamp {
+            return Some(account);
+        }
+        // Binary search for the last checkpoint with the `update_timestamp` not after the given
+        // timestamp.
+        let (from_index, to_index) = self.internal_account_checkpoints_range(account_id);
+        let mut left = from_index;
+        let mut right = to_index;
+        while left < right {
+            let mid = left + (right - left) / 2;
+            if self
+                .internal_get_account_checkpoint(account_id, mid)
+                .update_timestamp
+                <= timestamp
+            {
+                left = mid + 1;
+            } else {
+                right = mid;
+            }
+        }
+        if left == from_index {
+            None
+        } else {
+            Some(self.internal_get_account_checkpoint(account_id, left - 1))
+        }
+    }
+
+    fn internal_get_account_checkpoint(&self, account_id: &AccountId, index: u32) -> Account {
+        self.account_checkpoints
+            .get(&(account_id.clone(), index))
+            .cloned()
+            .expect("Checkpoint is missing")
+            .into()
+    }
+}
@@ -1,6 +1,8 @@
 use crate::*;
-use common::Version;
+use common::account::LockBoost;
+use common::{truncate_to_seconds, Fraction, TimestampNs, Version};
 use near_sdk::json_types::{Base58CryptoHash, U64};
+use std::collections::BTreeMap;
 
 #[derive(Clone)]
 #[near(serializers=[json, borsh])]
@@ -10,6 +12,64 @@ pub struct LockupContractConfig {
     pub contract_hash: Base58CryptoHash,
 }
 
+/// The policy for the extra veNEAR when the locked NEAR balance decreases.
+#[derive(Clone, Copy, Default, PartialEq)]
+#[near(serializers=[json, borsh])]
+pub enum UnlockForfeiturePolicy {
+    /// Decreasing the locked NEAR forfeits all extra veNEAR.
+    #[default]
+    Full,
+    /// Decreasing the locked NEAR forfeits extra veNEAR in proportion to the decrease, so the
+    /// remaining extra veNEAR is proportional to the remaining NEAR balance.
+    Proportional,
+}
+
+/// The boost of the locked balance for the lock commitments. The locked balance is increased by
+/// `max_boost` multiplied by the remaining duration of the commitment relative to
+/// `max_lock_duration_ns`, so the boost decreases to zero when the commitment ends.
+#[derive(Clone)]
+#[near(serializers=[json, borsh])]
+pub struct LockDurationBoost {
+    /// The lock duration in nanoseconds that receives the maximum boost. Longer durations receive
+    /// the same boost.
+    pub max_lock_duration_ns: U64,
+
+    /// The maximum boost, e.g. `1/1` doubles the locked balance.
+    pub max_boost: Fraction,
+}
+
+impl LockDurationBoost {
+    /// Returns the boost of the given locked balance at the given timestamp for the commitment
+    /// that ends at the given timestamp, or `None` if the commitment has ended or the boost is
+    /// empty.
+    /// The boost stays at its initial value while the remaining duration is longer than
+    /// `max_lock_duration_ns`, and then decreases linearly with the remaining duration.
+    pub fn lock_boost(
+        &self,
+        timestamp: TimestampNs,
+        lock_end_timestamp: TimestampNs,
+        locked_near_balance: NearToken,
+    ) -> Option<LockBoost> {
+        let lock_end_timestamp = truncate_to_seconds(lock_end_timestamp);
+        let remaining_duration_ns = lock_end_timestamp
+            .0
+            .checked_sub(truncate_to_seconds(timestamp).0)?;
+        let remaining_duration_ns =
+            std::cmp::min(remaining_duration_ns, self.max_lock_duration_ns.0);
+        let duration_fraction = Fraction {
+            numerator: (remaining_duration_ns as u128).into(),
+            denominator: (self.max_lock_duration_ns.0 as u128).into(),
+        };
+        LockBoost::new(
+            NearToken::from_yoctonear(
+                duration_fraction * (self.max_boost * locked_near_balance.as_yoctonear()),
+            ),
+            (lock_end_timestamp.0 - remaining_duration_ns).into(),
+            lock_end_timestamp,
+        )
+    }
+}
+
 #[derive(Clone)]
 #[near(serializers=[json, borsh])]
 pub struct Config {
@@ -38,6 +98,30 @@ pub struct Config {
 
     /// Proposed new owner account ID. The account has to accept ownership.
     pub proposed_new_owner_account_id: Option<AccountId>,
+
+    /// The policy for the extra veNEAR when the locked NEAR balance decreases.
+    #[serde(default)]
+    pub unlock_forfeiture_policy: UnlockForfeiturePolicy,
+
+    /// The list of account IDs that can schedule epoch snapshots, e.g. the voting contracts.
+    #[serde(default)]
+    pub snapshot_schedulers: Vec<AccountId>,
+
+    /// The weights of the fungible tokens locked in the lockup contracts. The NEAR equivalent of
+    /// the locked fungible token balance is multiplied by the weight of the token. The tokens
+    /// without a weight are not counted.
+    #[serde(default)]
+    pub fungible_token_weights: BTreeMap<AccountId, Fraction>,
+
+    /// The boost of the locked balance for the lock commitments. `None` means the lock
+    /// commitments are not boosted.
+    #[serde(default)]
+    pub lock_duration_boost: Option<LockDurationBoost>,
+
+    /// Initialization argument for the lockup contract. The time in nanoseconds between proposing
+    /// the new owner of the lockup contract and accepting the ownership.
+    #[serde(default)]
+    pub owner_transfer_delay_ns: U64,
 }
 
 #[near]
@@ -18,20 +18,26 @@ impl Contract {
                 return;
             }
         }
+        let mut global_state: GlobalState = self.internal_global_state_updated();
+        self.internal_remove_from_total_balance(&mut global_state, &account);
         if account.delegation.is_some() {
-            self.internal_undelegate(&mut account);
+            self.internal_undelegate(&mut global_state, &mut account);
         }
 
-        let mut delegation_account = self.internal_expect_account_updated(&receiver_id);
-        delegation_account.delegated_balance = delegation_account
-            .delegated_balance
-            .pooled_add(&account.balance);
-        self.internal_set_account(receiver_id.clone(), delegation_account);
-
         account.delegation = Some(AccountDelegation {
-            account_id: receiver_id,
+            account_id: receiver_id.clone(),
         });
+        // Caps the balance of the account before it's delegated.
+        self.internal_add_to_total_balance(&mut global_state, &mut account);
+        self.internal_replace_delegated_balance(
+            &mut global_state,
+            &receiver_id,
+            None,
+            Some(&account.balance),
+        );
         self.internal_set_account(predecessor_id, account);
+        self.internal_set_global_state(global_state);
+        self.tree.flush();
     }
 
     /// Undelegate all veNEAR tokens.
@@ -42,18 +48,51 @@ impl Contract {
         self.assert_not_paused();
         let predecessor_id = env::predecessor_account_id();
         let mut account = self.internal_expect_account_updated(&predecessor_id);
-        self.internal_undelegate(&mut account);
+        let mut global_state: GlobalState = self.internal_global_state_updated();
+        self.internal_remove_from_total_balance(&mut global_state, &account);
+        self.internal_undelegate(&mut global_state, &mut account);
+        self.internal_add_to_total_balance(&mut global_state, &mut account);
         self.internal_set_account(predecessor_id, account);
+        self.internal_set_global_state(global_state);
+        self.tree.flush();
     }
 }
 
 impl Contract {
-    pub fn internal_undelegate(&mut self, account: &mut Account) {
+    /// Removes the balance of the account from the delegated balance of the account it delegates
+    /// to. The balance of the account has to be removed from the total veNEAR balance before.
+    pub fn internal_undelegate(&mut self, global_state: &mut GlobalState, account: &mut Account) {
         let delegation_account_id = account.delegation.take().expect("Not delegated").account_id;
-        let mut delegation_account = self.internal_expect_account_updated(&delegation_account_id);
-        delegation_account.delegated_balance = delegation_account
-            .delegated_balance
-            .pooled_sub(&account.balance);
-        self.internal_set_account(delegation_account_id, delegation_account);
+        self.internal_replace_delegated_balance(
+            global_state,
+            &delegation_account_id,
+            Some(&account.balance),
+            None,
+        );
+    }
+
+    /// Replaces the old balance of a delegating account with its new balance in the delegated
+    /// balance of the given account. The delegated balance is removed from the total veNEAR
+    /// balance before the change and added back after it.
+    pub fn internal_replace_delegated_balance(
+        &mut self,
+        global_state: &mut GlobalState,
+        delegation_account_id: &AccountId,
+        old_balance: Option<&VenearBalance>,
+        new_balance: Option<&VenearBalance>,
+    ) {
+        let mut delegation_account = self.internal_expect_account_updated(delegation_account_id);
+        self.internal_remove_from_total_balance(global_state, &delegation_account);
+        if let Some(old_balance) = old_balance {
+            delegation_account.delegated_balance = delegation_account
+                .delegated_balance
+                .pooled_sub_delegated(old_balance);
+        }
+        if let Some(new_balance) = new_balance {
+            delegation_account.delegated_balance =
+                delegation_account.delegated_balance.pooled_add(new_balance);
+        }
+        self.internal_add_to_total_balance(global_state, &mut delegation_account);
+        self.internal_set_account(delegation_account_id.clone(), delegation_account);
     }
 }
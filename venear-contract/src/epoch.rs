@@ -0,0 +1,150 @@
+use crate::*;
+use common::TimestampNs;
+use near_sdk::assert_one_yocto;
+
+/// The ID of the scheduled epoch snapshot.
+pub type EpochId = u32;
+
+/// The maximum number of the scheduled epoch snapshots that are not taken yet.
+pub const MAX_PENDING_EPOCHS: usize = 16;
+
+/// The snapshot of the Merkle tree and the global state scheduled for a governance epoch.
+#[derive(Clone)]
+#[near(serializers=[borsh, json])]
+pub struct EpochSnapshot {
+    /// The timestamp in nanoseconds at which the snapshot is taken.
+    pub timestamp: TimestampNs,
+
+    /// The snapshot of the state at the timestamp. `None` if the timestamp is not reached yet.
+    /// The proofs for the snapshot are returned by `get_proof_at` and
+    /// `get_non_membership_proof_at` at the block height of the snapshot while it's retained.
+    pub snapshot: Option<(MerkleTreeSnapshot, VGlobalState)>,
+}
+
+#[derive(Clone)]
+#[near(serializers=[borsh])]
+pub enum VEpochSnapshot {
+    Current(EpochSnapshot),
+}
+
+impl From<EpochSnapshot> for VEpochSnapshot {
+    fn from(epoch_snapshot: EpochSnapshot) -> Self {
+        Self::Current(epoch_snapshot)
+    }
+}
+
+impl From<VEpochSnapshot> for EpochSnapshot {
+    fn from(value: VEpochSnapshot) -> Self {
+        match value {
+            VEpochSnapshot::Current(epoch_snapshot) => epoch_snapshot,
+        }
+    }
+}
+
+#[near]
+impl Contract {
+    /// Schedules the snapshot of the Merkle tree and the global state at the given future
+    /// timestamp. The snapshot is taken by the first call that changes the state at or after the
+    /// timestamp, before the state is changed. The global state of the snapshot is updated to the
+    /// timestamp.
+    /// Returns the ID of the epoch to get the snapshot with `get_epoch_snapshot`.
+    /// Can only be called by the owner or the snapshot schedulers.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn schedule_snapshot(&mut self, at_timestamp: TimestampNs) -> EpochId {
+        assert_one_yocto();
+        self.assert_snapshot_scheduler();
+        require!(
+            at_timestamp.0 > env::block_timestamp(),
+            "Timestamp must be in the future"
+        );
+        require!(
+            self.pending_epochs.len() < MAX_PENDING_EPOCHS,
+            "Too many scheduled snapshots"
+        );
+        let epoch_id = self.epoch_snapshots.len();
+        self.epoch_snapshots.push(
+            EpochSnapshot {
+                timestamp: at_timestamp,
+                snapshot: None,
+            }
+            .into(),
+        );
+        self.pending_epochs.push((epoch_id, at_timestamp));
+        epoch_id
+    }
+
+    /// Returns the number of the scheduled epoch snapshots.
+    pub fn get_num_epochs(&self) -> u32 {
+        self.epoch_snapshots.len()
+    }
+
+    /// Returns the scheduled epoch snapshot by the given epoch ID.
+    /// If the timestamp is reached, but the state hasn't changed since then, the current snapshot
+    /// is returned.
+    pub fn get_epoch_snapshot(&self, epoch_id: EpochId) -> Option<EpochSnapshot> {
+        self.assert_not_paused();
+        let mut epoch_snapshot: EpochSnapshot = self.epoch_snapshots.get(epoch_id)?.clone().into();
+        if epoch_snapshot.snapshot.is_none() && epoch_snapshot.timestamp.0 <= env::block_timestamp()
+        {
+            epoch_snapshot.snapshot = self
+                .tree
+                .get_snapshot()
+                .map(|snapshot| self.internal_epoch_snapshot(snapshot, epoch_snapshot.timestamp));
+        }
+        Some(epoch_snapshot)
+    }
+}
+
+impl Contract {
+    /// Asserts that the caller is one of the snapshot schedulers or the owner.
+    pub fn assert_snapshot_scheduler(&self) {
+        let predecessor = env::predecessor_account_id();
+        require!(
+            self.config.snapshot_schedulers.contains(&predecessor)
+                || predecessor == self.config.owner_account_id,
+            "Only the snapshot scheduler can call this method"
+        );
+    }
+
+    /// Takes the scheduled epoch snapshots whose timestamps are reached. Has to be called before
+    /// the state is changed.
+    pub fn internal_take_epoch_snapshots(&mut self) {
+        let current_timestamp = env::block_timestamp();
+        if self
+            .pending_epochs
+            .iter()
+            .all(|(_, timestamp)| timestamp.0 > current_timestamp)
+        {
+            return;
+        }
+        // The snapshot is not available only in the block of the contract initialization.
+        let Some(snapshot) = self.tree.get_snapshot() else {
+            return;
+        };
+        let (reached_epochs, pending_epochs) = std::mem::take(&mut self.pending_epochs)
+            .into_iter()
+            .partition(|(_, timestamp)| timestamp.0 <= current_timestamp);
+        self.pending_epochs = pending_epochs;
+        for (epoch_id, timestamp) in reached_epochs {
+            self.epoch_snapshots[epoch_id] = EpochSnapshot {
+                timestamp,
+                snapshot: Some(self.internal_epoch_snapshot(snapshot.clone(), timestamp)),
+            }
+            .into();
+        }
+    }
+
+    /// Returns the snapshot with the global state updated to the timestamp of the epoch.
+    fn internal_epoch_snapshot(
+        &self,
+        (snapshot, global_state): (MerkleTreeSnapshot, VGlobalState),
+        timestamp: TimestampNs,
+    ) -> (MerkleTreeSnapshot, VGlobalState) {
+        (
+            snapshot,
+            self.internal_global_state_at(global_state.into(), timestamp)
+                .into(),
+        )
+    }
+}
@@ -1,13 +1,330 @@
 use crate::*;
+use common::venear::VenearGrowthConfigCappedFixedRate;
+use common::{near_add, near_sub, truncate_to_seconds};
+use std::ops::Bound;
+
+/// How the balance of the account is pooled in the total veNEAR balance.
+enum PooledGrowth {
+    /// The balance grows with the pool, until the optional saturation.
+    Growing(Option<(TimestampNs, VenearBalance)>),
+    /// The balance doesn't grow.
+    Fixed,
+}
+
+#[near]
+impl Contract {
+    /// Returns whether the capped growth is applied to the balances of all accounts that were
+    /// registered when the growth became capped.
+    pub fn is_venear_growth_cap_migrated(&self) -> bool {
+        self.num_venear_growth_cap_migrated.is_none()
+    }
+}
 
 impl Contract {
     pub fn internal_global_state_updated(&self) -> GlobalState {
-        let mut global_state: GlobalState = self.tree.get_global_state().clone().into();
-        global_state.update(env::block_timestamp().into());
+        self.internal_global_state_at(
+            self.tree.get_global_state().clone().into(),
+            env::block_timestamp().into(),
+        )
+    }
+
+    /// Updates the given global state to the given timestamp, applying the saturations of the
+    /// capped growth and the changes of the lock boosts until then.
+    pub fn internal_global_state_at(
+        &self,
+        mut global_state: GlobalState,
+        timestamp: TimestampNs,
+    ) -> GlobalState {
+        let timestamp = truncate_to_seconds(timestamp);
+        if timestamp <= global_state.update_timestamp {
+            return global_state;
+        }
+        for (saturation_timestamp, saturation) in self.venear_saturations.range((
+            Bound::Excluded(global_state.update_timestamp),
+            Bound::Included(timestamp),
+        )) {
+            self.internal_apply_lock_boost_rate_changes(&mut global_state, *saturation_timestamp);
+            global_state.update(*saturation_timestamp);
+            global_state.total_venear_balance = global_state
+                .total_venear_balance
+                .pooled_saturate(saturation);
+        }
+        self.internal_apply_lock_boost_rate_changes(&mut global_state, timestamp);
+        global_state.update(timestamp);
         global_state
     }
 
+    /// Updates the given global state to the timestamps of the changes of the lock boosts until
+    /// the given timestamp, and applies the changes.
+    fn internal_apply_lock_boost_rate_changes(
+        &self,
+        global_state: &mut GlobalState,
+        timestamp: TimestampNs,
+    ) {
+        if timestamp <= global_state.update_timestamp {
+            return;
+        }
+        for (change_timestamp, change) in self.lock_boost_rate_changes.range((
+            Bound::Excluded(global_state.update_timestamp),
+            Bound::Included(timestamp),
+        )) {
+            global_state.update(*change_timestamp);
+            global_state.total_lock_boost.apply(change);
+        }
+    }
+
     pub fn internal_set_global_state(&mut self, global_state: GlobalState) {
+        self.internal_take_epoch_snapshots();
+        // The saturations and the changes of the lock boosts are kept while the current snapshot
+        // may need them.
+        if let Some((_, snapshot_global_state)) = self.tree.get_snapshot() {
+            let snapshot_timestamp = GlobalState::from(snapshot_global_state).update_timestamp;
+            let applied_timestamps: Vec<TimestampNs> = self
+                .venear_saturations
+                .range(..=snapshot_timestamp)
+                .map(|(timestamp, _)| *timestamp)
+                .collect();
+            for timestamp in applied_timestamps {
+                self.venear_saturations.remove(&timestamp);
+            }
+            let applied_timestamps: Vec<TimestampNs> = self
+                .lock_boost_rate_changes
+                .range(..=snapshot_timestamp)
+                .map(|(timestamp, _)| *timestamp)
+                .collect();
+            for timestamp in applied_timestamps {
+                self.lock_boost_rate_changes.remove(&timestamp);
+            }
+        }
         self.tree.set_global_state(global_state.into());
     }
+
+    /// Removes the balances of the account from the total veNEAR balance: its balance, unless it
+    /// delegates, and the balances delegated to it. The account and the global state have to be
+    /// updated to the current timestamp.
+    pub fn internal_remove_from_total_balance(
+        &mut self,
+        global_state: &mut GlobalState,
+        account: &Account,
+    ) {
+        // The balances were pooled with the cap, if the account was updated after the cap started.
+        let is_capped = self
+            .internal_get_account(&account.account_id)
+            .is_some_and(|old_account| {
+                global_state
+                    .venear_growth_config
+                    .is_capped_at(old_account.update_timestamp)
+            });
+        for balance in internal_pooled_balances(account) {
+            global_state.total_venear_balance =
+                match internal_pooled_growth(global_state, &balance, is_capped) {
+                    PooledGrowth::Growing(saturation) => {
+                        if let Some((saturation_timestamp, saturation)) = saturation {
+                            let saturation = *self
+                                .venear_saturations
+                                .get(&saturation_timestamp)
+                                .expect("Saturation is missing")
+                                - saturation;
+                            if saturation.total().is_zero() {
+                                self.venear_saturations.remove(&saturation_timestamp);
+                            } else {
+                                self.venear_saturations
+                                    .insert(saturation_timestamp, saturation);
+                            }
+                        }
+                        global_state.total_venear_balance.pooled_sub(&balance)
+                    }
+                    PooledGrowth::Fixed => {
+                        global_state.total_venear_balance.pooled_sub_fixed(&balance)
+                    }
+                };
+        }
+        if let Some(lock_boost) = internal_pooled_lock_boost(global_state, account) {
+            let timestamp = global_state.update_timestamp;
+            let decrease_per_second = lock_boost.decrease_per_second();
+            let total_lock_boost = &mut global_state.total_lock_boost;
+            total_lock_boost.near_balance =
+                near_sub(total_lock_boost.near_balance, lock_boost.balance(timestamp));
+            if timestamp >= lock_boost.start_timestamp {
+                total_lock_boost.decrease_per_second =
+                    near_sub(total_lock_boost.decrease_per_second, decrease_per_second);
+            } else {
+                self.internal_update_lock_boost_rate_change(lock_boost.start_timestamp, |change| {
+                    change.started = near_sub(change.started, decrease_per_second)
+                });
+            }
+            self.internal_update_lock_boost_rate_change(lock_boost.end_timestamp, |change| {
+                change.ended = near_sub(change.ended, decrease_per_second)
+            });
+        }
+    }
+
+    /// Adds the balances of the account to the total veNEAR balance: its balance, unless it
+    /// delegates, and the balances delegated to it. With the capped growth, the balances are capped
+    /// first. The account and the global state have to be updated to the current timestamp.
+    pub fn internal_add_to_total_balance(
+        &mut self,
+        global_state: &mut GlobalState,
+        account: &mut Account,
+    ) {
+        let venear_growth_config = &global_state.venear_growth_config;
+        let is_capped = venear_growth_config.is_capped_at(account.update_timestamp);
+        if is_capped {
+            account.balance.cap(venear_growth_config);
+            account.delegated_balance.cap(venear_growth_config);
+        }
+        for balance in internal_pooled_balances(account) {
+            global_state.total_venear_balance =
+                match internal_pooled_growth(global_state, &balance, is_capped) {
+                    PooledGrowth::Growing(saturation) => {
+                        if let Some((saturation_timestamp, saturation)) = saturation {
+                            let saturation = self
+                                .venear_saturations
+                                .get(&saturation_timestamp)
+                                .copied()
+                                .unwrap_or_default()
+                                + saturation;
+                            self.venear_saturations
+                                .insert(saturation_timestamp, saturation);
+                        }
+                        global_state.total_venear_balance.pooled_add(&balance)
+                    }
+                    PooledGrowth::Fixed => {
+                        global_state.total_venear_balance.pooled_add_fixed(&balance)
+                    }
+                };
+        }
+        if let Some(lock_boost) = internal_pooled_lock_boost(global_state, account) {
+            let timestamp = global_state.update_timestamp;
+            let decrease_per_second = lock_boost.decrease_per_second();
+            let total_lock_boost = &mut global_state.total_lock_boost;
+            total_lock_boost.near_balance =
+                near_add(total_lock_boost.near_balance, lock_boost.balance(timestamp));
+            if timestamp >= lock_boost.start_timestamp {
+                total_lock_boost.decrease_per_second =
+                    near_add(total_lock_boost.decrease_per_second, decrease_per_second);
+            } else {
+                self.internal_update_lock_boost_rate_change(lock_boost.start_timestamp, |change| {
+                    change.started = near_add(change.started, decrease_per_second)
+                });
+            }
+            self.internal_update_lock_boost_rate_change(lock_boost.end_timestamp, |change| {
+                change.ended = near_add(change.ended, decrease_per_second)
+            });
+        }
+    }
+
+    /// Updates the change of the lock boosts at the given future timestamp. The empty change is
+    /// removed.
+    fn internal_update_lock_boost_rate_change(
+        &mut self,
+        timestamp: TimestampNs,
+        update: impl FnOnce(&mut LockBoostRateChange),
+    ) {
+        let mut change = self
+            .lock_boost_rate_changes
+            .get(&timestamp)
+            .copied()
+            .unwrap_or_default();
+        update(&mut change);
+        if change.is_empty() {
+            self.lock_boost_rate_changes.remove(&timestamp);
+        } else {
+            self.lock_boost_rate_changes.insert(timestamp, change);
+        }
+    }
+
+    /// Caps the growth of the extra veNEAR balance at the given multiple of the NEAR balance from
+    /// the current timestamp. The balances of the existing accounts are capped when they are
+    /// updated, or with `internal_migrate_venear_growth_cap_accounts`. Until then they keep
+    /// growing without the cap, together with the total veNEAR balance.
+    pub fn internal_set_max_extra_venear_multiplier(
+        &mut self,
+        max_extra_venear_multiplier: Fraction,
+    ) {
+        require!(
+            max_extra_venear_multiplier.denominator.0 > 0,
+            "Denominator must be positive"
+        );
+        let mut global_state: GlobalState = self.internal_global_state_updated();
+        let VenearGrowthConfig::FixedRate(venear_growth_config) =
+            &global_state.venear_growth_config
+        else {
+            env::panic_str("The growth is already capped");
+        };
+        global_state.venear_growth_config = VenearGrowthConfigCappedFixedRate {
+            annual_growth_rate_ns: venear_growth_config.annual_growth_rate_ns,
+            max_extra_venear_multiplier,
+            start_timestamp: global_state.update_timestamp,
+        }
+        .into();
+        self.num_venear_growth_cap_migrated = (!self.tree.is_empty()).then_some(0);
+        self.internal_set_global_state(global_state);
+    }
+
+    /// Caps the balances of up to `limit` accounts that were not updated since the growth was
+    /// capped.
+    /// Returns `true` if all accounts are capped.
+    pub fn internal_migrate_venear_growth_cap_accounts(&mut self, limit: u32) -> bool {
+        let Some(from_index) = self.num_venear_growth_cap_migrated else {
+            return true;
+        };
+        let to_index = std::cmp::min(from_index.saturating_add(limit), self.get_num_accounts());
+        let mut global_state: GlobalState = self.internal_global_state_updated();
+        for index in from_index..to_index {
+            let Some(account) = self.tree.get_by_index(index) else {
+                continue;
+            };
+            let account = Account::from(account.clone());
+            if !global_state
+                .venear_growth_config
+                .is_capped_at(account.update_timestamp)
+            {
+                let mut account = self.internal_expect_account_updated(&account.account_id);
+                self.internal_remove_from_total_balance(&mut global_state, &account);
+                self.internal_add_to_total_balance(&mut global_state, &mut account);
+                self.internal_set_account(account.account_id.clone(), account);
+            }
+        }
+        self.num_venear_growth_cap_migrated =
+            Some(to_index).filter(|&index| index < self.get_num_accounts());
+        self.internal_set_global_state(global_state);
+        self.tree.flush();
+        self.is_venear_growth_cap_migrated()
+    }
+}
+
+/// Returns the lock boost of the account that is pooled in the total veNEAR balance. The boost of
+/// the account that delegates is not pooled, and the ended boost is no longer pooled.
+fn internal_pooled_lock_boost(global_state: &GlobalState, account: &Account) -> Option<LockBoost> {
+    account.lock_boost.filter(|lock_boost| {
+        account.delegation.is_none() && global_state.update_timestamp < lock_boost.end_timestamp
+    })
+}
+
+/// Returns the balances of the account that are pooled in the total veNEAR balance.
+fn internal_pooled_balances(account: &Account) -> Vec<VenearBalance> {
+    let mut balances = vec![*account.delegated_balance.balance()];
+    if account.delegation.is_none() {
+        balances.push(account.balance);
+    }
+    balances
+}
+
+fn internal_pooled_growth(
+    global_state: &GlobalState,
+    balance: &VenearBalance,
+    is_capped: bool,
+) -> PooledGrowth {
+    let venear_growth_config = &global_state.venear_growth_config;
+    let Some(max_extra_venear_balance) = venear_growth_config
+        .max_extra_venear_balance(balance.near_balance)
+        .filter(|_| is_capped)
+    else {
+        return PooledGrowth::Growing(None);
+    };
+    if balance.extra_venear_balance >= max_extra_venear_balance {
+        return PooledGrowth::Fixed;
+    }
+    PooledGrowth::Growing(venear_growth_config.saturation(balance, global_state.update_timestamp))
 }
@@ -1,6 +1,7 @@
+use crate::config::{LockDurationBoost, UnlockForfeiturePolicy};
 use crate::*;
-use near_sdk::assert_one_yocto;
 use near_sdk::json_types::{Base58CryptoHash, U64};
+use near_sdk::{assert_one_yocto, BlockHeight};
 
 #[near]
 impl Contract {
@@ -79,6 +80,19 @@ impl Contract {
         self.config.unlock_duration_ns = U64::from(unlock_duration_sec as u64 * 1_000_000_000);
     }
 
+    /// Sets the delay in seconds between proposing the new owner of the lockup contract and
+    /// accepting the ownership.
+    /// Note, this method will only affect new lockups.
+    /// Can only be called by the owner.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn set_owner_transfer_delay_sec(&mut self, owner_transfer_delay_sec: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.config.owner_transfer_delay_ns =
+            U64::from(owner_transfer_delay_sec as u64 * 1_000_000_000);
+    }
+
     /// Sets the list of account IDs that can store new lockup contract code.
     /// Can only be called by the owner.
     /// Requires 1 yocto NEAR.
@@ -89,6 +103,107 @@ impl Contract {
         self.config.lockup_code_deployers = lockup_code_deployers;
     }
 
+    /// Sets the list of account IDs that can schedule epoch snapshots.
+    /// Can only be called by the owner.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn set_snapshot_schedulers(&mut self, snapshot_schedulers: Vec<AccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.config.snapshot_schedulers = snapshot_schedulers;
+    }
+
+    /// Sets the weight of the given fungible token locked in the lockup contracts, or removes it
+    /// if the weight is not given. The weight can't exceed 1.
+    /// Note, this method only affects the following lockup updates.
+    /// Can only be called by the owner.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn set_fungible_token_weight(
+        &mut self,
+        token_account_id: AccountId,
+        weight: Option<Fraction>,
+    ) {
+        assert_one_yocto();
+        self.assert_owner();
+        match weight {
+            Some(weight) => {
+                require!(weight.denominator.0 > 0, "Denominator must be positive");
+                require!(
+                    weight.numerator.0 <= weight.denominator.0,
+                    "Weight can't exceed 1"
+                );
+                self.config
+                    .fungible_token_weights
+                    .insert(token_account_id, weight);
+            }
+            None => {
+                self.config.fungible_token_weights.remove(&token_account_id);
+            }
+        }
+    }
+
+    /// Sets the boost of the locked balance for the lock commitments, or disables it if the boost
+    /// is not given.
+    /// Note, this method only affects the following lockup updates.
+    /// Can only be called by the owner.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn set_lock_duration_boost(&mut self, lock_duration_boost: Option<LockDurationBoost>) {
+        assert_one_yocto();
+        self.assert_owner();
+        if let Some(lock_duration_boost) = &lock_duration_boost {
+            require!(
+                lock_duration_boost.max_lock_duration_ns.0 > 0,
+                "Max lock duration must be positive"
+            );
+            require!(
+                lock_duration_boost.max_boost.denominator.0 > 0,
+                "Denominator must be positive"
+            );
+        }
+        self.config.lock_duration_boost = lock_duration_boost;
+    }
+
+    /// Sets the policy for the extra veNEAR when the locked NEAR balance decreases.
+    /// Can only be called by the owner.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn set_unlock_forfeiture_policy(
+        &mut self,
+        unlock_forfeiture_policy: UnlockForfeiturePolicy,
+    ) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.config.unlock_forfeiture_policy = unlock_forfeiture_policy;
+    }
+
+    /// Caps the extra veNEAR balance at the given multiple of the NEAR balance from now on. The
+    /// balances delegated to an account are capped as a single balance, the same way as the balance
+    /// of the account. The growth can only be capped once.
+    /// The balances of the existing accounts are capped when the accounts are updated, or by
+    /// `migrate_venear_growth_cap`. The total supply always matches the sum of the balances.
+    /// Can only be called by the owner.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn set_max_extra_venear_multiplier(&mut self, max_extra_venear_multiplier: Fraction) {
+        assert_one_yocto();
+        self.assert_owner();
+        self.internal_set_max_extra_venear_multiplier(max_extra_venear_multiplier);
+    }
+
+    /// Caps the balances of up to `limit` accounts that were not updated since the growth was
+    /// capped by `set_max_extra_venear_multiplier`.
+    /// Returns `true` if all accounts are capped.
+    /// Can only be called by the owner.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn migrate_venear_growth_cap(&mut self, limit: u32) -> bool {
+        assert_one_yocto();
+        self.assert_owner();
+        self.internal_migrate_venear_growth_cap_accounts(limit)
+    }
+
     /// Sets the list of account IDs that can pause the contract.
     /// Can only be called by the owner.
     /// Requires 1 yocto NEAR.
@@ -98,6 +213,64 @@ impl Contract {
         self.assert_owner();
         self.config.guardians = guardians;
     }
+
+    /// Sets the number of the blocks for which the previous snapshots of the Merkle tree are
+    /// retained. The snapshots that are no longer retained are pruned with the following updates.
+    /// Can only be called by the owner.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn set_snapshot_retention_blocks(&mut self, snapshot_retention_blocks: BlockHeight) {
+        assert_one_yocto();
+        self.assert_owner();
+        require!(
+            snapshot_retention_blocks > 0,
+            "Must retain snapshots for at least one block"
+        );
+        self.tree
+            .set_snapshot_retention_blocks(snapshot_retention_blocks);
+    }
+
+    /// Migrates up to `limit` accounts of the Merkle tree to the latest hashing format. Once all
+    /// accounts are migrated, the tree switches to the new format. The snapshots taken before the
+    /// switch keep the previous format until they are no longer retained.
+    /// Returns `true` if the migration is complete.
+    /// Can only be called by the owner.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn migrate_tree_format(&mut self, limit: u32) -> bool {
+        assert_one_yocto();
+        self.assert_owner();
+        self.internal_take_epoch_snapshots();
+        self.tree.migrate_format(limit)
+    }
+
+    /// Indexes up to `limit` accounts of the Merkle tree in the sorted keys tree, which is used
+    /// for the non-membership proofs. The accounts registered before the sorted keys tree was
+    /// introduced have to be indexed before the non-membership proofs are available.
+    /// Returns `true` if all accounts are indexed.
+    /// Can only be called by the owner.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn index_tree_keys(&mut self, limit: u32) -> bool {
+        assert_one_yocto();
+        self.assert_owner();
+        self.tree.index_keys(limit, |v_account| {
+            Account::from(v_account.clone()).account_id
+        })
+    }
+
+    /// Indexes the voting power of up to `limit` accounts for the top accounts views. The
+    /// accounts registered before the index was introduced have to be indexed before the views
+    /// are available.
+    /// Returns `true` if all accounts are indexed.
+    /// Can only be called by the owner.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn index_top_accounts(&mut self, limit: u32) -> bool {
+        assert_one_yocto();
+        self.assert_owner();
+        self.internal_index_top_accounts(limit)
+    }
 }
 
 impl Contract {
@@ -1,6 +1,8 @@
 mod account;
+mod checkpoint;
 mod config;
 mod delegation;
+mod epoch;
 mod global_state;
 mod governance;
 mod lockup;
@@ -8,17 +10,22 @@ mod pause;
 mod snapshot;
 mod storage;
 mod token;
+mod top_accounts;
 mod upgrade;
 
-use merkle_tree::{MerkleProof, MerkleTree, MerkleTreeSnapshot};
+use merkle_tree::{
+    MerkleMultiProof, MerkleNonMembershipProof, MerkleProof, MerkleTree, MerkleTreeFormat,
+    MerkleTreeSnapshot,
+};
 
 use crate::account::VAccountInternal;
 use crate::config::Config;
+use crate::epoch::{EpochId, VEpochSnapshot};
 use common::account::*;
 use common::global_state::*;
 use common::venear::{VenearGrowthConfig, VenearGrowthConfigFixedRate};
-use common::Version;
-use near_sdk::store::LookupMap;
+use common::{Fraction, TimestampNs, VenearBalance, Version};
+use near_sdk::store::{LookupMap, TreeMap, Vector};
 use near_sdk::{
     env, near, require, sys, AccountId, BorshStorageKey, CryptoHash, NearToken, PanicOnDefault,
 };
@@ -29,6 +36,13 @@ enum StorageKeys {
     Tree,
     LockupCode(CryptoHash),
     Accounts,
+    AccountCheckpoints,
+    NumAccountCheckpoints,
+    TopAccounts,
+    EpochSnapshots,
+    VenearSaturations,
+    NumTransferredLockups,
+    LockBoostRateChanges,
 }
 
 #[derive(PanicOnDefault)]
@@ -41,6 +55,33 @@ pub struct Contract {
     /// The paused contract will not create new lockups and new accounts. It will not return
     /// snapshots or proofs (preventing future voting). The accounts can't delegate or undelegate.
     paused: bool,
+    /// The previous values of the accounts, used to compute historical balances. Only the last
+    /// `MAX_NUM_ACCOUNT_CHECKPOINTS` checkpoints of each account are kept.
+    account_checkpoints: LookupMap<(AccountId, u32), VAccount>,
+    /// The number of checkpoints ever stored per account. The index of the next checkpoint.
+    num_account_checkpoints: LookupMap<AccountId, u32>,
+    /// The accounts with non-zero voting power ordered by the voting power at their last update.
+    top_accounts: TreeMap<(NearToken, AccountId), ()>,
+    /// The number of accounts indexed in the top accounts after the upgrade that introduced the
+    /// index. `None` if all accounts are indexed.
+    num_top_accounts_indexed: Option<u32>,
+    /// The scheduled epoch snapshots by the epoch ID.
+    epoch_snapshots: Vector<VEpochSnapshot>,
+    /// The IDs and the timestamps of the scheduled epoch snapshots that are not taken yet.
+    pending_epochs: Vec<(EpochId, TimestampNs)>,
+    /// The saturations of the total veNEAR balance with the capped growth, by the timestamps when
+    /// the growing balances reach the cap.
+    venear_saturations: TreeMap<TimestampNs, VenearBalance>,
+    /// The number of accounts the capped growth was applied to by the migration after it was
+    /// enabled. `None` if it's applied to all accounts.
+    num_venear_growth_cap_migrated: Option<u32>,
+    /// The number of lockup contracts each account transferred to other owners. It's kept after
+    /// the account is unregistered, and it's part of the derived lockup account IDs, so the new
+    /// lockup contracts of the account don't collide with the transferred ones.
+    num_transferred_lockups: LookupMap<AccountId, u32>,
+    /// The changes of the total decrease per second of the lock boosts, by the timestamps when the
+    /// boosts start decreasing and end.
+    lock_boost_rate_changes: TreeMap<TimestampNs, LockBoostRateChange>,
 }
 
 #[near]
@@ -54,6 +95,7 @@ impl Contract {
             venear_growth_config.annual_growth_rate_ns.denominator.0 == 10u128.pow(30),
             "Denominator must be 10^30"
         );
+        upgrade::internal_write_state_version();
         Self {
             tree: MerkleTree::new(
                 StorageKeys::Tree,
@@ -62,6 +104,16 @@ impl Contract {
             accounts: LookupMap::new(StorageKeys::Accounts),
             config,
             paused: false,
+            account_checkpoints: LookupMap::new(StorageKeys::AccountCheckpoints),
+            num_account_checkpoints: LookupMap::new(StorageKeys::NumAccountCheckpoints),
+            top_accounts: TreeMap::new(StorageKeys::TopAccounts),
+            num_top_accounts_indexed: None,
+            epoch_snapshots: Vector::new(StorageKeys::EpochSnapshots),
+            pending_epochs: vec![],
+            venear_saturations: TreeMap::new(StorageKeys::VenearSaturations),
+            num_venear_growth_cap_migrated: None,
+            num_transferred_lockups: LookupMap::new(StorageKeys::NumTransferredLockups),
+            lock_boost_rate_changes: TreeMap::new(StorageKeys::LockBoostRateChanges),
         }
     }
 }
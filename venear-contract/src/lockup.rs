@@ -1,9 +1,9 @@
-use crate::account::AccountInternal;
-use crate::config::LockupContractConfig;
+use crate::account::{AccountInternal, LockupMigration};
+use crate::config::{LockupContractConfig, UnlockForfeiturePolicy};
 use crate::*;
-use common::lockup_update::{LockupUpdateV1, VLockupUpdate};
+use common::lockup_update::{FungibleTokenBalance, LockupMigrationUpdate, VLockupUpdate};
 use common::near_add;
-use common::{events, near_sub};
+use common::{events, near_sub, VenearBalance};
 use near_sdk::json_types::{Base58CryptoHash, U64};
 use near_sdk::{env, is_promise_success, Gas, IntoStorageKey, Promise};
 
@@ -26,6 +26,11 @@ pub struct LockupInitArgs {
     lockup_update_nonce: U64,
 
     min_lockup_deposit: NearToken,
+
+    owner_transfer_delay_ns: U64,
+
+    /// The account ID of the lockup contract that migrates to the new lockup contract.
+    previous_lockup_account_id: Option<AccountId>,
 }
 
 #[near(serializers=[json])]
@@ -52,6 +57,18 @@ impl Contract {
         self.internal_deploy_lockup(env::predecessor_account_id());
     }
 
+    /// Deploys the current version of the lockup contract at a new account, to migrate the
+    /// existing lockup contract of the caller to it without unlocking.
+    /// The migration is completed by calling `migrate_lockup` on the existing lockup contract.
+    /// Requires the caller to attach the deposit for the lockup contract of at least
+    /// `get_lockup_deployment_cost()`.
+    /// Requires the caller to have a lockup contract of an older version.
+    #[payable]
+    pub fn deploy_migration_lockup(&mut self) {
+        self.assert_not_paused();
+        self.internal_deploy_migration_lockup(env::predecessor_account_id());
+    }
+
     /// Called by one of the lockup contracts to update the amount of NEAR locked in the lockup
     /// contract.
     pub fn on_lockup_update(
@@ -65,27 +82,269 @@ impl Contract {
             env::predecessor_account_id() == lockup_account_id,
             "Permission denied"
         );
-        let account_internal = self
+        let mut account_internal = self
             .internal_get_account_internal(&owner_account_id)
             .expect("Account not found");
         require!(
             account_internal.lockup_version == Some(version),
             "Invalid lockup version"
         );
+        // The first update of the new lockup contract confirms the migration.
+        account_internal.migrated_from_lockup = None;
 
-        match update {
-            VLockupUpdate::V1(lockup_update) => {
-                events::emit::lockup_action(
-                    "lockup_update",
-                    &owner_account_id,
-                    version,
-                    &Some(lockup_update.lockup_update_nonce),
-                    &Some(lockup_update.timestamp),
-                    &Some(lockup_update.locked_near_balance),
+        let (lockup_update_nonce, timestamp, locked_near_balance, lock_boost) = match update {
+            VLockupUpdate::V1(lockup_update) => (
+                lockup_update.lockup_update_nonce,
+                lockup_update.timestamp,
+                lockup_update.locked_near_balance,
+                None,
+            ),
+            VLockupUpdate::V2(lockup_update) => (
+                lockup_update.lockup_update_nonce,
+                lockup_update.timestamp,
+                near_add(
+                    lockup_update.locked_near_balance,
+                    self.internal_fungible_tokens_near_balance(&lockup_update.fungible_tokens),
+                ),
+                None,
+            ),
+            VLockupUpdate::V3(lockup_update) => {
+                let locked_near_balance = near_add(
+                    lockup_update.locked_near_balance,
+                    self.internal_fungible_tokens_near_balance(&lockup_update.fungible_tokens),
                 );
-                self.internal_lockup_update(owner_account_id, account_internal, lockup_update);
+                let lock_boost =
+                    self.config
+                        .lock_duration_boost
+                        .as_ref()
+                        .and_then(|lock_duration_boost| {
+                            lock_duration_boost.lock_boost(
+                                lockup_update.timestamp,
+                                lockup_update.lock_end_timestamp,
+                                locked_near_balance,
+                            )
+                        });
+                (
+                    lockup_update.lockup_update_nonce,
+                    lockup_update.timestamp,
+                    locked_near_balance,
+                    lock_boost,
+                )
             }
-        }
+        };
+        events::emit::lockup_action(
+            "lockup_update",
+            &owner_account_id,
+            version,
+            &Some(lockup_update_nonce),
+            &Some(timestamp),
+            &Some(locked_near_balance),
+        );
+        self.internal_lockup_update(
+            owner_account_id,
+            account_internal,
+            lockup_update_nonce,
+            locked_near_balance,
+            lock_boost,
+        );
+    }
+
+    /// Called by one of the lockup contracts when its ownership is transferred to the new owner.
+    /// The locked balance, the extra veNEAR balance and the lockup information move from the
+    /// current owner to the new owner, and the delegated veNEAR follows the delegation of the new
+    /// owner. The storage deposit and the veNEAR delegated to the current owner stay with the
+    /// current owner.
+    /// The new owner has to be registered and can't have a lockup.
+    pub fn on_lockup_owner_transfer(
+        &mut self,
+        version: Version,
+        owner_account_id: AccountId,
+        new_owner_account_id: AccountId,
+    ) {
+        let lockup_account_id = self.get_lockup_account_id(&owner_account_id);
+        require!(
+            env::predecessor_account_id() == lockup_account_id,
+            "Permission denied"
+        );
+        let mut account_internal = self
+            .internal_get_account_internal(&owner_account_id)
+            .expect("Account not found");
+        require!(
+            account_internal.lockup_version == Some(version),
+            "Invalid lockup version"
+        );
+        require!(
+            account_internal.lockup_migration.is_none()
+                && account_internal.migrated_from_lockup.is_none(),
+            "The lockup migration is pending"
+        );
+        let mut new_account_internal = self
+            .internal_get_account_internal(&new_owner_account_id)
+            .expect("The new owner is not registered");
+        require!(
+            new_account_internal.lockup_version.is_none()
+                && new_account_internal.lockup_account_id.is_none(),
+            "The new owner already has a lockup"
+        );
+
+        let mut global_state: GlobalState = self.internal_global_state_updated();
+
+        let mut account: Account = self.internal_expect_account_updated(&owner_account_id);
+        let old_balance = account.balance;
+        let moved_balance = VenearBalance {
+            near_balance: near_sub(old_balance.near_balance, account_internal.deposit),
+            extra_venear_balance: old_balance.extra_venear_balance,
+        };
+        self.internal_remove_from_total_balance(&mut global_state, &account);
+        let lock_boost = account.lock_boost.take();
+        account.balance = VenearBalance::from_near(account_internal.deposit);
+        self.internal_add_to_total_balance(&mut global_state, &mut account);
+        self.internal_update_delegated_balance(&mut global_state, &account, &old_balance);
+        self.internal_set_account(owner_account_id.clone(), account);
+
+        let mut new_account: Account = self.internal_expect_account_updated(&new_owner_account_id);
+        let new_old_balance = new_account.balance;
+        self.internal_remove_from_total_balance(&mut global_state, &new_account);
+        new_account.balance += moved_balance;
+        new_account.lock_boost = lock_boost;
+        self.internal_add_to_total_balance(&mut global_state, &mut new_account);
+        self.internal_update_delegated_balance(&mut global_state, &new_account, &new_old_balance);
+        self.internal_set_account(new_owner_account_id.clone(), new_account);
+
+        new_account_internal.lockup_version = account_internal.lockup_version.take();
+        new_account_internal.lockup_update_nonce = account_internal.lockup_update_nonce;
+        new_account_internal.lockup_account_id = Some(lockup_account_id);
+        account_internal.lockup_account_id = None;
+        let num_transferred_lockups = self.internal_get_num_transferred_lockups(&owner_account_id);
+        self.num_transferred_lockups
+            .insert(owner_account_id.clone(), num_transferred_lockups + 1);
+
+        events::emit::lockup_action(
+            "lockup_owner_transfer",
+            &new_owner_account_id,
+            version,
+            &Some(new_account_internal.lockup_update_nonce),
+            &None,
+            &None,
+        );
+
+        self.internal_set_account_internal(owner_account_id, account_internal);
+        self.internal_set_account_internal(new_owner_account_id, new_account_internal);
+        self.internal_set_global_state(global_state);
+        self.tree.flush();
+    }
+
+    /// Called by one of the lockup contracts when it migrates to the new lockup contract deployed
+    /// by `deploy_migration_lockup`. The new lockup contract becomes the lockup contract of the
+    /// owner. The veNEAR balance is not changed, including the extra veNEAR balance, since the
+    /// locked NEAR moves to the new lockup contract. The migrated locked NEAR has to match the
+    /// locked NEAR of the account.
+    pub fn on_lockup_migration(
+        &mut self,
+        version: Version,
+        owner_account_id: AccountId,
+        update: LockupMigrationUpdate,
+    ) {
+        let lockup_account_id = self.get_lockup_account_id(&owner_account_id);
+        require!(
+            env::predecessor_account_id() == lockup_account_id,
+            "Permission denied"
+        );
+        let mut account_internal = self
+            .internal_get_account_internal(&owner_account_id)
+            .expect("Account not found");
+        require!(
+            account_internal.lockup_version == Some(version),
+            "Invalid lockup version"
+        );
+        require!(
+            update.lockup_update_nonce > account_internal.lockup_update_nonce,
+            "Invalid nonce"
+        );
+        let lockup_migration = account_internal
+            .lockup_migration
+            .take()
+            .expect("The lockup migration is not pending");
+        require!(
+            lockup_migration.lockup_account_id == update.new_lockup_account_id,
+            "Invalid new lockup account ID"
+        );
+        let account = self.internal_expect_account_updated(&owner_account_id);
+        require!(
+            near_add(update.locked_near_balance, account_internal.deposit)
+                == account.balance.near_balance,
+            "The migrated locked balance doesn't match"
+        );
+
+        events::emit::lockup_action(
+            "lockup_migration",
+            &owner_account_id,
+            lockup_migration.lockup_version,
+            &Some(update.lockup_update_nonce),
+            &Some(update.timestamp),
+            &Some(update.locked_near_balance),
+        );
+
+        account_internal.migrated_from_lockup = Some(LockupMigration {
+            lockup_account_id,
+            lockup_version: version,
+            lockup_update_nonce: update.lockup_update_nonce,
+        });
+        account_internal.lockup_version = Some(lockup_migration.lockup_version);
+        account_internal.lockup_update_nonce = lockup_migration.lockup_update_nonce;
+        account_internal.lockup_account_id = Some(lockup_migration.lockup_account_id);
+        self.internal_set_account_internal(owner_account_id, account_internal);
+    }
+
+    /// Called by the previous lockup contract when the new lockup contract failed to accept the
+    /// migration. The previous lockup contract becomes the lockup contract of the owner again, and
+    /// the migration to the new lockup contract is pending again.
+    pub fn on_lockup_migration_failed(
+        &mut self,
+        version: Version,
+        owner_account_id: AccountId,
+        lockup_update_nonce: U64,
+    ) {
+        let mut account_internal = self
+            .internal_get_account_internal(&owner_account_id)
+            .expect("Account not found");
+        let migrated_from_lockup = account_internal
+            .migrated_from_lockup
+            .take()
+            .expect("The lockup migration is not pending");
+        require!(
+            env::predecessor_account_id() == migrated_from_lockup.lockup_account_id,
+            "Permission denied"
+        );
+        require!(
+            migrated_from_lockup.lockup_version == version,
+            "Invalid lockup version"
+        );
+        require!(
+            lockup_update_nonce > migrated_from_lockup.lockup_update_nonce,
+            "Invalid nonce"
+        );
+
+        events::emit::lockup_action(
+            "lockup_migration_failed",
+            &owner_account_id,
+            version,
+            &Some(lockup_update_nonce),
+            &None,
+            &None,
+        );
+
+        account_internal.lockup_migration = Some(LockupMigration {
+            lockup_account_id: self.get_lockup_account_id(&owner_account_id),
+            lockup_version: account_internal
+                .lockup_version
+                .expect("The account doesn't have a lockup"),
+            lockup_update_nonce: account_internal.lockup_update_nonce,
+        });
+        account_internal.lockup_version = Some(version);
+        account_internal.lockup_update_nonce = lockup_update_nonce;
+        account_internal.lockup_account_id = Some(migrated_from_lockup.lockup_account_id);
+        self.internal_set_account_internal(owner_account_id, account_internal);
     }
 
     /// Callback after the attempt to deploy the lockup contract.
@@ -128,57 +387,188 @@ impl Contract {
         }
     }
 
-    /// Returns the account ID for the lockup contract for the given account.
+    /// Callback after the attempt to deploy the new lockup contract for the migration.
+    /// Returns the new lockup contract account ID if the deployment was successful.
+    #[private]
+    pub fn on_migration_lockup_deployed(
+        &mut self,
+        version: Version,
+        account_id: AccountId,
+        lockup_update_nonce: U64,
+        lockup_deposit: NearToken,
+    ) -> Option<AccountId> {
+        if is_promise_success() {
+            let mut account_internal = self
+                .internal_get_account_internal(&account_id)
+                .expect("Account not found");
+            let lockup_account_id =
+                self.internal_get_migration_lockup_account_id(&account_id, version);
+            account_internal.lockup_migration = Some(LockupMigration {
+                lockup_account_id: lockup_account_id.clone(),
+                lockup_version: version,
+                lockup_update_nonce,
+            });
+
+            events::emit::lockup_action(
+                "lockup_migration_deployed",
+                &account_id,
+                version,
+                &None,
+                &None,
+                &None,
+            );
+
+            self.internal_set_account_internal(account_id, account_internal);
+
+            Some(lockup_account_id)
+        } else {
+            // Refunding the deposit if the lockup contract deployment failed.
+            Promise::new(account_id).transfer(lockup_deposit);
+            None
+        }
+    }
+
+    /// Returns the account ID for the lockup contract for the given account. It's the transferred
+    /// lockup contract if the account received one from another owner, or the new lockup contract
+    /// if the account migrated its lockup contract.
+    /// Otherwise, it's derived from the account ID and the number of lockup contracts the account
+    /// transferred to other owners.
     /// Note, the lockup contract is not guaranteed to be deployed.
     pub fn get_lockup_account_id(&self, account_id: &AccountId) -> AccountId {
-        let owner_account_id_hash = hex::encode(&env::sha256(account_id.as_bytes())[0..20]);
-        format!("{}.{}", owner_account_id_hash, env::current_account_id())
-            .try_into()
-            .expect("Failed to create lockup account ID")
+        self.internal_get_account_internal(account_id)
+            .and_then(|account_internal| account_internal.lockup_account_id)
+            .unwrap_or_else(|| self.internal_get_derived_lockup_account_id(account_id))
     }
 }
 
 /// Internal methods for the contract and lockup.
 impl Contract {
+    /// Returns the account ID of the lockup contract derived from the given account ID and the
+    /// number of lockup contracts the account transferred to other owners.
+    pub fn internal_get_derived_lockup_account_id(&self, account_id: &AccountId) -> AccountId {
+        format!(
+            "{}.{}",
+            self.internal_lockup_account_id_prefix(account_id),
+            env::current_account_id()
+        )
+        .try_into()
+        .expect("Failed to create lockup account ID")
+    }
+
+    pub fn internal_get_num_transferred_lockups(&self, account_id: &AccountId) -> u32 {
+        self.num_transferred_lockups
+            .get(account_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the prefix of the lockup account IDs of the given account ID. The prefix of the
+    /// account that hasn't transferred any lockup contracts is the hash of the account ID.
+    fn internal_lockup_account_id_prefix(&self, account_id: &AccountId) -> String {
+        let owner_account_id_hash = hex::encode(&env::sha256(account_id.as_bytes())[0..20]);
+        match self.internal_get_num_transferred_lockups(account_id) {
+            0 => owner_account_id_hash,
+            num_transferred_lockups => {
+                format!("{}-{}", owner_account_id_hash, num_transferred_lockups)
+            }
+        }
+    }
+
+    /// Returns the account ID of the new lockup contract of the given version for the migration of
+    /// the lockup contract of the given account ID.
+    pub fn internal_get_migration_lockup_account_id(
+        &self,
+        account_id: &AccountId,
+        version: Version,
+    ) -> AccountId {
+        format!(
+            "{}-v{}.{}",
+            self.internal_lockup_account_id_prefix(account_id),
+            version,
+            env::current_account_id()
+        )
+        .try_into()
+        .expect("Failed to create lockup account ID")
+    }
+
     pub fn internal_lockup_update(
         &mut self,
         account_id: AccountId,
         mut account_internal: AccountInternal,
-        lockup_update: LockupUpdateV1,
+        lockup_update_nonce: U64,
+        locked_near_balance: NearToken,
+        lock_boost: Option<LockBoost>,
     ) {
         require!(
-            lockup_update.lockup_update_nonce > account_internal.lockup_update_nonce,
+            lockup_update_nonce > account_internal.lockup_update_nonce,
             "Invalid nonce"
         );
-        account_internal.lockup_update_nonce = lockup_update.lockup_update_nonce;
+        account_internal.lockup_update_nonce = lockup_update_nonce;
 
         let mut account: Account = self.internal_expect_account_updated(&account_id);
         let old_balance = account.balance;
         let mut global_state: GlobalState = self.internal_global_state_updated();
+        self.internal_remove_from_total_balance(&mut global_state, &account);
+        // Updating balance and also adding internal balance deposit.
+        account.balance.near_balance = near_add(locked_near_balance, account_internal.deposit);
+        // The lock boost is kept separately from the balance, so it doesn't affect the forfeiture.
+        account.lock_boost = lock_boost;
         // Decreasing the locked NEAR will result in dropped extra veNEAR rewards.
-        if lockup_update.locked_near_balance < old_balance.near_balance {
-            account.balance.extra_venear_balance = NearToken::from_yoctonear(0);
+        if account.balance.near_balance < old_balance.near_balance {
+            account.balance.extra_venear_balance = match self.config.unlock_forfeiture_policy {
+                UnlockForfeiturePolicy::Full => NearToken::from_yoctonear(0),
+                UnlockForfeiturePolicy::Proportional => NearToken::from_yoctonear(
+                    Fraction {
+                        numerator: account.balance.near_balance.as_yoctonear().into(),
+                        denominator: old_balance.near_balance.as_yoctonear().into(),
+                    } * old_balance.extra_venear_balance.as_yoctonear(),
+                ),
+            };
         }
-        // Updating balance and also adding internal balance deposit.
-        account.balance.near_balance =
-            near_add(lockup_update.locked_near_balance, account_internal.deposit);
-        global_state.total_venear_balance = global_state
-            .total_venear_balance
-            .pooled_sub(&old_balance)
-            .pooled_add(&account.balance);
+        self.internal_add_to_total_balance(&mut global_state, &mut account);
 
-        if let Some(delegation) = &account.delegation {
-            let mut delegation_account =
-                self.internal_expect_account_updated(&delegation.account_id);
-            delegation_account.delegated_balance = delegation_account
-                .delegated_balance
-                .pooled_sub(&old_balance)
-                .pooled_add(&account.balance);
-            self.internal_set_account(delegation.account_id.clone(), delegation_account);
-        }
+        self.internal_update_delegated_balance(&mut global_state, &account, &old_balance);
         self.internal_set_account_internal(account_id.clone(), account_internal);
         self.internal_set_account(account_id, account);
         self.internal_set_global_state(global_state);
+        self.tree.flush();
+    }
+
+    /// Updates the delegated balance of the account the given account delegates to, after the
+    /// balance of the given account changed from the old balance.
+    fn internal_update_delegated_balance(
+        &mut self,
+        global_state: &mut GlobalState,
+        account: &Account,
+        old_balance: &VenearBalance,
+    ) {
+        if let Some(delegation) = &account.delegation {
+            self.internal_replace_delegated_balance(
+                global_state,
+                &delegation.account_id,
+                Some(old_balance),
+                Some(&account.balance),
+            );
+        }
+    }
+
+    /// Returns the NEAR equivalent of the locked fungible tokens multiplied by their weights.
+    pub fn internal_fungible_tokens_near_balance(
+        &self,
+        fungible_tokens: &[FungibleTokenBalance],
+    ) -> NearToken {
+        fungible_tokens
+            .iter()
+            .filter_map(|fungible_token| {
+                let weight = self
+                    .config
+                    .fungible_token_weights
+                    .get(&fungible_token.token_account_id)?;
+                Some(NearToken::from_yoctonear(
+                    *weight * fungible_token.near_balance.as_yoctonear(),
+                ))
+            })
+            .fold(NearToken::from_yoctonear(0), near_add)
     }
 
     pub fn internal_set_lockup(&mut self, contract_hash: CryptoHash) {
@@ -207,18 +597,74 @@ impl Contract {
     }
 
     pub fn internal_deploy_lockup(&mut self, owner_account_id: AccountId) {
+        let account_internal = self.internal_expect_registered_account_internal(&owner_account_id);
+        require!(
+            account_internal.lockup_account_id.is_none(),
+            "The account has a transferred lockup"
+        );
+        let lockup_account_id = self.internal_get_derived_lockup_account_id(&owner_account_id);
+        self.internal_deploy_lockup_contract(
+            owner_account_id,
+            lockup_account_id,
+            None,
+            b"on_lockup_deployed",
+        );
+    }
+
+    pub fn internal_deploy_migration_lockup(&mut self, owner_account_id: AccountId) {
+        let account_internal = self.internal_expect_registered_account_internal(&owner_account_id);
+        let lockup_version = account_internal
+            .lockup_version
+            .expect("The account doesn't have a lockup");
+        require!(
+            account_internal.lockup_migration.is_none()
+                && account_internal.migrated_from_lockup.is_none(),
+            "The lockup migration is already pending"
+        );
+        let contract_version = self
+            .config
+            .lockup_contract_config
+            .as_ref()
+            .expect("The lockup contract code is not initialized")
+            .contract_version;
+        require!(
+            lockup_version < contract_version,
+            "The lockup is already of the latest version"
+        );
+        let previous_lockup_account_id = self.get_lockup_account_id(&owner_account_id);
+        let lockup_account_id =
+            self.internal_get_migration_lockup_account_id(&owner_account_id, contract_version);
+        self.internal_deploy_lockup_contract(
+            owner_account_id,
+            lockup_account_id,
+            Some(previous_lockup_account_id),
+            b"on_migration_lockup_deployed",
+        );
+    }
+
+    fn internal_expect_registered_account_internal(
+        &self,
+        account_id: &AccountId,
+    ) -> AccountInternal {
+        self.internal_get_account_internal(account_id)
+            .unwrap_or_else(|| env::panic_str(&format!("Account {} is not registered", account_id)))
+    }
+
+    /// Deploys the active lockup contract code at the given account ID and calls the given
+    /// callback method after the deployment with `OnLockupDeployedArgs`.
+    fn internal_deploy_lockup_contract(
+        &mut self,
+        owner_account_id: AccountId,
+        lockup_account_id: AccountId,
+        previous_lockup_account_id: Option<AccountId>,
+        callback_method_name: &[u8],
+    ) {
         let remaining_gas = env::prepaid_gas().saturating_sub(env::used_gas());
         require!(
             remaining_gas >= MIN_INTERNAL_DEPLOY_LOCKUP_GAS,
             "Not enough gas for lockup deployment"
         );
         let lockup_deposit = env::attached_deposit();
-        assert!(
-            self.internal_get_account_internal(&owner_account_id)
-                .is_some(),
-            "Account {} is not registered",
-            owner_account_id
-        );
         let required_deposit = self.get_lockup_deployment_cost();
         assert!(
             lockup_deposit >= required_deposit,
@@ -230,7 +676,6 @@ impl Contract {
             .lockup_contract_config
             .as_ref()
             .expect("The lockup contract code is not initialized");
-        let lockup_account_id = self.get_lockup_account_id(&owner_account_id);
         let lockup_account_id = lockup_account_id.as_str();
         let contract_code_key =
             StorageKeys::LockupCode(lockup_contract_config.contract_hash.into()).into_storage_key();
@@ -264,6 +709,8 @@ impl Contract {
                 .clone(),
             lockup_update_nonce: lockup_update_nonce.into(),
             min_lockup_deposit: self.config.min_lockup_deposit,
+            owner_transfer_delay_ns: self.config.owner_transfer_delay_ns,
+            previous_lockup_account_id,
         };
         let arguments =
             serde_json::to_vec(&arguments).expect("Failed to serialize lockup init args");
@@ -283,7 +730,7 @@ impl Contract {
         }
         let current_account_id = env::current_account_id();
         let current_account_id = current_account_id.as_str();
-        let method_name = b"on_lockup_deployed";
+        let method_name = callback_method_name;
         let arguments = OnLockupDeployedArgs {
             version: lockup_contract_config.contract_version,
             account_id: owner_account_id.clone(),
@@ -1,11 +1,40 @@
 use crate::*;
+use near_sdk::json_types::Base58CryptoHash;
+use near_sdk::BlockHeight;
 
 #[near]
 impl Contract {
-    /// Returns the current snapshot of the Merkle tree and the global state.
+    /// Returns the current snapshot of the Merkle tree and the global state. The global state is
+    /// updated to the current timestamp.
     pub fn get_snapshot(&self) -> (MerkleTreeSnapshot, VGlobalState) {
         self.assert_not_paused();
-        self.tree.get_snapshot().expect("Snapshot is not available")
+        let (snapshot, global_state) = self.tree.get_snapshot().expect("Snapshot is not available");
+        (
+            snapshot,
+            self.internal_global_state_at(global_state.into(), env::block_timestamp().into())
+                .into(),
+        )
+    }
+
+    /// Returns the snapshot of the Merkle tree and the global state at the end of the given block
+    /// height. The block height has to be before the current block and the snapshot has to be
+    /// still retained.
+    pub fn get_snapshot_at(&self, block_height: BlockHeight) -> (MerkleTreeSnapshot, VGlobalState) {
+        self.assert_not_paused();
+        self.tree
+            .get_snapshot_at(block_height)
+            .expect("Snapshot is not available")
+    }
+
+    /// Returns the number of the blocks for which the previous snapshots of the Merkle tree are
+    /// retained.
+    pub fn get_snapshot_retention_blocks(&self) -> BlockHeight {
+        self.tree.get_snapshot_retention_blocks()
+    }
+
+    /// Returns the hashing format of the Merkle tree.
+    pub fn get_tree_format(&self) -> MerkleTreeFormat {
+        self.tree.get_format()
     }
 
     /// Returns the proof for the given account and the raw account value.
@@ -15,4 +44,70 @@ impl Contract {
             .get_proof(&account_id)
             .expect(format!("Account {} is not found", account_id).as_str())
     }
+
+    /// Returns the proof for the given accounts and the raw account values in the order of the
+    /// leaf indices of the proof. The duplicate accounts are proven once.
+    pub fn get_multi_proof(
+        &self,
+        account_ids: Vec<AccountId>,
+    ) -> (MerkleMultiProof, Vec<VAccount>) {
+        self.assert_not_paused();
+        for account_id in &account_ids {
+            require!(
+                self.tree.get(account_id).is_some(),
+                format!("Account {} is not found", account_id)
+            );
+        }
+        self.tree.get_multi_proof(&account_ids).unwrap()
+    }
+
+    /// Returns the root of the sorted keys tree of the current snapshot, which is used to verify
+    /// the non-membership proofs.
+    pub fn get_keys_root(&self) -> Base58CryptoHash {
+        let (snapshot, _) = self.get_snapshot();
+        snapshot.keys_root.expect("Sorted keys are not indexed yet")
+    }
+
+    /// Returns the proof that the given account is not registered. The proof is valid for the
+    /// keys root and the length of the current snapshot returned by `get_snapshot`.
+    pub fn get_non_membership_proof(&self, account_id: AccountId) -> MerkleNonMembershipProof {
+        // The current snapshot is the state of the tree at the end of the previous block.
+        self.get_non_membership_proof_at(account_id, env::block_height() - 1)
+    }
+
+    /// Returns the proof that the given account is not registered at the end of the given block
+    /// height. The proof is valid for the keys root and the length of the snapshot returned by
+    /// `get_snapshot_at`.
+    pub fn get_non_membership_proof_at(
+        &self,
+        account_id: AccountId,
+        block_height: BlockHeight,
+    ) -> MerkleNonMembershipProof {
+        let (snapshot, _) = self.get_snapshot_at(block_height);
+        require!(
+            snapshot.keys_root.is_some(),
+            "Sorted keys are not indexed yet"
+        );
+        self.tree
+            .get_non_membership_proof_at(&account_id, block_height)
+            .unwrap_or_else(|| env::panic_str(&format!("Account {} is registered", account_id)))
+    }
+
+    /// Returns the proof for the given account and the raw account value at the end of the given
+    /// block height. The proof is valid for the snapshot returned by `get_snapshot_at`.
+    pub fn get_proof_at(
+        &self,
+        account_id: AccountId,
+        block_height: BlockHeight,
+    ) -> (MerkleProof, VAccount) {
+        self.assert_not_paused();
+        self.tree
+            .get_proof_at(&account_id, block_height)
+            .unwrap_or_else(|| {
+                env::panic_str(&format!(
+                    "Account {} is not found in the snapshot",
+                    account_id
+                ))
+            })
+    }
 }
@@ -1,5 +1,5 @@
 use crate::*;
-use near_sdk::Promise;
+use near_sdk::{assert_one_yocto, Promise};
 
 #[near(serializers=[json])]
 pub struct StorageBalance {
@@ -56,6 +56,44 @@ impl Contract {
         self.internal_storage_balance_of(&account_id).unwrap()
     }
 
+    /// Unregisters the predecessor account and refunds its storage deposit. Returns `false` if the
+    /// account is not registered.
+    /// The account can only be unregistered if it doesn't have a lockup, doesn't delegate, doesn't
+    /// have delegated veNEAR and doesn't have NEAR balance beyond the storage deposit. The veNEAR
+    /// balance of the account is burned.
+    /// The forced unregistration is not supported.
+    /// Requires 1 yocto NEAR.
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        self.assert_not_paused();
+        require!(
+            !force.unwrap_or(false),
+            "Forced unregistration is not supported"
+        );
+        let account_id = env::predecessor_account_id();
+        let Some(account_internal) = self.internal_get_account_internal(&account_id) else {
+            return false;
+        };
+        require!(
+            account_internal.lockup_version.is_none(),
+            "The account has a lockup"
+        );
+        let account = self.internal_expect_account_updated(&account_id);
+        require!(account.delegation.is_none(), "The account delegates veNEAR");
+        require!(
+            account.delegated_balance.total() == NearToken::from_near(0),
+            "The account has delegated veNEAR"
+        );
+        require!(
+            account.balance.near_balance == account_internal.deposit,
+            "The account has NEAR balance beyond the storage deposit"
+        );
+        self.internal_unregister_account(&account_id, account);
+        Promise::new(account_id).transfer(account_internal.deposit);
+        true
+    }
+
     /// Method to match the interface of the storage deposit. Fails with a panic.
     #[payable]
     pub fn storage_withdraw(&mut self) {
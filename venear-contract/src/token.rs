@@ -1,4 +1,5 @@
 use crate::*;
+use common::TimestampNs;
 
 const ICON_BASE64: &str = "data:image/svg+xml;base64,PHN2ZyB3aWR0aD0iMTAwIiBoZWlnaHQ9IjEwMCIgdmlld0JveD0iMCAwIDEwMCAxMDAiIHhtbG5zPSJodHRwOi8vd3d3LnczLm9yZy8yMDAwL3N2ZyIgZmlsbD0ibm9uZSI+CiAgPHJlY3Qgd2lkdGg9IjEwMCIgaGVpZ2h0PSIxMDAiIHJ4PSIyMCIgZmlsbD0iIzAwMCIvPgogIDx0ZXh0IHg9IjQwIiB5PSI2NSIgZm9udC1mYW1pbHk9IkFyaWFsLCBzYW5zLXNlcmlmIiBmb250LXNpemU9IjYwIiBmaWxsPSIjZmZmIiBmb250LXdlaWdodD0iYm9sZCI+TjwvdGV4dD4KICA8dGV4dCB4PSIxNSIgeT0iNjUiIGZvbnQtZmFtaWx5PSJBcmlhbCwgc2Fucy1zZXJpZiIgZm9udC1zaXplPSIyMCIgZmlsbD0iI2ZmZiI+dmU8L3RleHQ+Cjwvc3ZnPgo=";
 
@@ -16,11 +17,55 @@ impl Contract {
             .unwrap_or_default()
     }
 
+    /// Returns the balance of the account in the veNEAR at the given past timestamp.
+    /// Returns 0 if the account was not registered at the given timestamp, or the history of the
+    /// account doesn't go back to the given timestamp.
+    pub fn ft_balance_of_at(&self, account_id: AccountId, timestamp: TimestampNs) -> NearToken {
+        require!(
+            timestamp.0 <= env::block_timestamp(),
+            "Timestamp must not be in the future"
+        );
+        // The capped growth config keeps the timestamp when the cap started, so the growth from
+        // the checkpoint is the growth that was in effect at the given timestamp.
+        self.internal_get_account_at(&account_id, timestamp)
+            .map(|account| {
+                account.total_balance(timestamp, self.internal_get_venear_growth_config())
+            })
+            .unwrap_or_default()
+    }
+
     /// Returns the total supply of the veNEAR.
     pub fn ft_total_supply(&self) -> NearToken {
-        self.internal_global_state_updated()
-            .total_venear_balance
-            .total()
+        self.internal_global_state_updated().total_balance()
+    }
+
+    /// Returns the projected balance of the account in the veNEAR at the given future timestamp,
+    /// assuming the account is not updated until then.
+    pub fn ft_balance_of_projected(
+        &self,
+        account_id: AccountId,
+        timestamp: TimestampNs,
+    ) -> NearToken {
+        require!(
+            timestamp.0 >= env::block_timestamp(),
+            "Timestamp must not be in the past"
+        );
+        self.internal_get_account(&account_id)
+            .map(|account| {
+                account.total_balance(timestamp, self.internal_get_venear_growth_config())
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the projected total supply of the veNEAR at the given future timestamp, assuming
+    /// no balances are changed until then.
+    pub fn ft_total_supply_projected(&self, timestamp: TimestampNs) -> NearToken {
+        require!(
+            timestamp.0 >= env::block_timestamp(),
+            "Timestamp must not be in the past"
+        );
+        self.internal_global_state_at(self.internal_global_state_updated(), timestamp)
+            .total_balance()
     }
 
     /// Method to match the fungible token interface. Can't be called.
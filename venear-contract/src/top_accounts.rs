@@ -0,0 +1,130 @@
+use crate::account::AccountSummary;
+use crate::*;
+use common::near_add;
+use std::ops::Bound;
+
+/// The maximum number of accounts returned by `get_top_accounts`.
+pub const MAX_TOP_ACCOUNTS_PAGE_SIZE: u32 = 100;
+
+/// The maximum rank returned by `get_account_rank`.
+pub const MAX_ACCOUNT_RANK: u32 = 1000;
+
+#[near]
+impl Contract {
+    /// Returns up to `limit` accounts with the largest voting power.
+    /// The accounts are selected by the voting power at their last update, while the returned
+    /// balances are the current veNEAR balances. The returned accounts are sorted by the
+    /// returned balances in the decreasing order. The accounts that delegated their balance are
+    /// not included.
+    /// The limit is capped by `MAX_TOP_ACCOUNTS_PAGE_SIZE`.
+    pub fn get_top_accounts(&self, limit: Option<u32>) -> Vec<AccountSummary> {
+        self.internal_assert_top_accounts_indexed();
+        let limit = limit
+            .unwrap_or(MAX_TOP_ACCOUNTS_PAGE_SIZE)
+            .min(MAX_TOP_ACCOUNTS_PAGE_SIZE);
+        let current_timestamp = env::block_timestamp().into();
+        let venear_growth_config = self.internal_get_venear_growth_config();
+        let mut accounts: Vec<AccountSummary> = self
+            .top_accounts
+            .keys()
+            .rev()
+            .take(limit as usize)
+            .map(|(_, account_id)| AccountSummary {
+                balance: self
+                    .internal_get_account(account_id)
+                    .unwrap()
+                    .total_balance(current_timestamp, venear_growth_config),
+                account_id: account_id.clone(),
+            })
+            .collect();
+        // The stable sort keeps the voting power order for the accounts with equal balances.
+        accounts.sort_by_key(|account| std::cmp::Reverse(account.balance));
+        accounts
+    }
+
+    /// Returns the 1-based rank of the account by the voting power at its last update.
+    /// Returns `None` if the account is not registered, has no voting power, or its rank is
+    /// larger than `MAX_ACCOUNT_RANK`.
+    pub fn get_account_rank(&self, account_id: AccountId) -> Option<u32> {
+        self.internal_assert_top_accounts_indexed();
+        let account = self.internal_get_account(&account_id)?;
+        let key = (internal_voting_power(&account), account_id);
+        if !self.top_accounts.contains_key(&key) {
+            return None;
+        }
+        let num_above = self
+            .top_accounts
+            .range((Bound::Excluded(key), Bound::Unbounded))
+            .take(MAX_ACCOUNT_RANK as usize)
+            .count() as u32;
+        Some(num_above + 1).filter(|&rank| rank <= MAX_ACCOUNT_RANK)
+    }
+
+    /// Returns whether the voting power of all accounts is indexed for the top accounts views.
+    pub fn are_top_accounts_indexed(&self) -> bool {
+        self.num_top_accounts_indexed.is_none()
+    }
+}
+
+impl Contract {
+    pub fn internal_assert_top_accounts_indexed(&self) {
+        require!(
+            self.are_top_accounts_indexed(),
+            "The top accounts are not indexed yet"
+        );
+    }
+
+    /// Updates the voting power index of the account from the old value to the new value.
+    pub fn internal_update_top_accounts(
+        &mut self,
+        old_account: Option<&Account>,
+        new_account: Option<&Account>,
+    ) {
+        if let Some(old_account) = old_account {
+            self.top_accounts.remove(&(
+                internal_voting_power(old_account),
+                old_account.account_id.clone(),
+            ));
+        }
+        if let Some(new_account) = new_account {
+            let voting_power = internal_voting_power(new_account);
+            if voting_power > NearToken::from_near(0) {
+                self.top_accounts
+                    .insert((voting_power, new_account.account_id.clone()), ());
+            }
+        }
+    }
+
+    /// Indexes the voting power of up to `limit` accounts that were registered before the index
+    /// was introduced. Returns `true` if all accounts are indexed.
+    pub fn internal_index_top_accounts(&mut self, limit: u32) -> bool {
+        let Some(from_index) = self.num_top_accounts_indexed else {
+            return true;
+        };
+        let to_index = std::cmp::min(from_index.saturating_add(limit), self.get_num_accounts());
+        for index in from_index..to_index {
+            if let Some(account) = self.tree.get_by_index(index) {
+                let account: Account = account.clone().into();
+                // The accounts updated since the migration are already indexed with the same key.
+                self.internal_update_top_accounts(None, Some(&account));
+            }
+        }
+        self.num_top_accounts_indexed =
+            Some(to_index).filter(|&index| index < self.get_num_accounts());
+        self.are_top_accounts_indexed()
+    }
+}
+
+/// Returns the voting power of the account at its last update: the delegated balance and the
+/// own balance with the lock boost, unless it's delegated to another account.
+pub fn internal_voting_power(account: &Account) -> NearToken {
+    let mut voting_power = account.delegated_balance.total();
+    if account.delegation.is_none() {
+        voting_power = near_add(voting_power, account.balance.total());
+        voting_power = near_add(
+            voting_power,
+            account.lock_boost_balance(account.update_timestamp),
+        );
+    }
+    voting_power
+}
@@ -1,16 +1,145 @@
+use crate::config::LockupContractConfig;
 use crate::*;
-use near_sdk::Gas;
+use merkle_tree::MerkleTreeV0;
+use near_sdk::json_types::U64;
+use near_sdk::{borsh, Gas};
 
 const MIGRATE_STATE_GAS: Gas = Gas::from_tgas(50);
 const GET_CONFIG_GAS: Gas = Gas::from_tgas(5);
 
+/// The storage key of the contract state.
+const STATE_KEY: &[u8] = b"STATE";
+
+/// The storage key of the version of the contract state layout. The contracts deployed before the
+/// version was introduced don't have it, so their state is `StateVersion::V0`.
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+
+/// The version of the contract state layout.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[near(serializers=[borsh])]
+pub enum StateVersion {
+    /// The layout before the retained snapshots of the Merkle tree were introduced.
+    V0,
+    /// The current layout.
+    V1,
+}
+
+impl StateVersion {
+    pub const CURRENT: Self = Self::V1;
+}
+
+/// The contract state of any supported version.
+pub enum VersionedContract {
+    V0(Box<ContractV0>),
+    V1(Box<Contract>),
+}
+
+/// The configuration of the contract in the `StateVersion::V0` layout.
+#[near(serializers=[borsh])]
+pub struct ConfigV0 {
+    pub lockup_contract_config: Option<LockupContractConfig>,
+    pub unlock_duration_ns: U64,
+    pub staking_pool_whitelist_account_id: AccountId,
+    pub lockup_code_deployers: Vec<AccountId>,
+    pub local_deposit: NearToken,
+    pub min_lockup_deposit: NearToken,
+    pub owner_account_id: AccountId,
+    pub guardians: Vec<AccountId>,
+    pub proposed_new_owner_account_id: Option<AccountId>,
+}
+
+/// The state of the contract in the `StateVersion::V0` layout.
+#[near(serializers=[borsh])]
+pub struct ContractV0 {
+    tree: MerkleTreeV0<VAccount, VGlobalState>,
+    accounts: LookupMap<AccountId, VAccountInternal>,
+    config: ConfigV0,
+    paused: bool,
+}
+
+impl VersionedContract {
+    /// Reads the contract state according to the stored state version.
+    pub fn read() -> Self {
+        let state_version = env::storage_read(STATE_VERSION_KEY)
+            .map(|bytes| borsh::from_slice(&bytes).expect("Failed to read the state version"))
+            .unwrap_or(StateVersion::V0);
+        let state = env::storage_read(STATE_KEY).expect("Contract state is missing");
+        match state_version {
+            StateVersion::V0 => Self::V0(Box::new(
+                borsh::from_slice(&state).expect("Failed to deserialize the V0 contract state"),
+            )),
+            StateVersion::V1 => Self::V1(Box::new(
+                borsh::from_slice(&state).expect("Failed to deserialize the V1 contract state"),
+            )),
+        }
+    }
+}
+
+impl From<VersionedContract> for Contract {
+    fn from(value: VersionedContract) -> Self {
+        match value {
+            VersionedContract::V0(contract) => {
+                let ContractV0 {
+                    tree,
+                    accounts,
+                    config,
+                    paused,
+                } = *contract;
+                Self {
+                    tree: MerkleTree::migrate_from_v0(StorageKeys::Tree, tree),
+                    accounts,
+                    config: Config {
+                        lockup_contract_config: config.lockup_contract_config,
+                        unlock_duration_ns: config.unlock_duration_ns,
+                        staking_pool_whitelist_account_id: config.staking_pool_whitelist_account_id,
+                        lockup_code_deployers: config.lockup_code_deployers,
+                        local_deposit: config.local_deposit,
+                        min_lockup_deposit: config.min_lockup_deposit,
+                        owner_account_id: config.owner_account_id,
+                        guardians: config.guardians,
+                        proposed_new_owner_account_id: config.proposed_new_owner_account_id,
+                        unlock_forfeiture_policy: Default::default(),
+                        snapshot_schedulers: vec![],
+                        fungible_token_weights: Default::default(),
+                        lock_duration_boost: None,
+                        owner_transfer_delay_ns: U64(0),
+                    },
+                    paused,
+                    account_checkpoints: LookupMap::new(StorageKeys::AccountCheckpoints),
+                    num_account_checkpoints: LookupMap::new(StorageKeys::NumAccountCheckpoints),
+                    top_accounts: TreeMap::new(StorageKeys::TopAccounts),
+                    num_top_accounts_indexed: Some(0),
+                    epoch_snapshots: Vector::new(StorageKeys::EpochSnapshots),
+                    pending_epochs: vec![],
+                    venear_saturations: TreeMap::new(StorageKeys::VenearSaturations),
+                    num_venear_growth_cap_migrated: None,
+                    num_transferred_lockups: LookupMap::new(StorageKeys::NumTransferredLockups),
+                    lock_boost_rate_changes: TreeMap::new(StorageKeys::LockBoostRateChanges),
+                }
+            }
+            VersionedContract::V1(contract) => *contract,
+        }
+    }
+}
+
+/// Writes the current version of the contract state layout.
+pub(crate) fn internal_write_state_version() {
+    env::storage_write(
+        STATE_VERSION_KEY,
+        &borsh::to_vec(&StateVersion::CURRENT).unwrap(),
+    );
+}
+
 #[near]
 impl Contract {
     /// Private method to migrate the contract state during the contract upgrade.
+    /// The state is read according to the stored state version and is migrated to the current
+    /// version.
     #[private]
     #[init(ignore_state)]
     pub fn migrate_state() -> Self {
-        let contract: Self = env::state_read().unwrap();
+        let contract: Self = VersionedContract::read().into();
+        internal_write_state_version();
         contract
     }
 
@@ -1,14 +1,19 @@
 use crate::metadata::ProposalMetadata;
 use crate::*;
-use common::{events, near_add, near_sub, TimestampNs};
-use near_sdk::json_types::U64;
-use near_sdk::Promise;
+use common::{events, near_add, near_sub, page_range, TimestampNs};
+use merkle_tree::MerkleTreeFormat;
+use near_sdk::json_types::{Base58CryptoHash, U64};
+use near_sdk::{BlockHeight, Promise};
 
 pub type ProposalId = u32;
 
+/// The maximum number of proposals returned by `get_proposals` and `get_approved_proposals`.
+pub const MAX_PROPOSALS_PAGE_SIZE: u32 = 50;
+
 #[derive(Clone)]
 #[near(serializers=[borsh])]
 pub enum VProposal {
+    V0(ProposalV0),
     Current(Proposal),
 }
 
@@ -21,11 +26,81 @@ impl From<Proposal> for VProposal {
 impl From<VProposal> for Proposal {
     fn from(value: VProposal) -> Self {
         match value {
+            VProposal::V0(proposal) => Proposal {
+                id: proposal.id,
+                creation_time_ns: proposal.creation_time_ns,
+                proposer_id: proposal.proposer_id,
+                reviewer_id: proposal.reviewer_id,
+                voting_start_time_ns: proposal.voting_start_time_ns,
+                voting_duration_ns: proposal.voting_duration_ns,
+                rejected: proposal.rejected,
+                snapshot_and_state: proposal.snapshot_and_state.map(|snapshot_and_state| {
+                    let SnapshotAndStateV0 {
+                        snapshot,
+                        timestamp_ns,
+                        total_venear,
+                        venear_growth_config,
+                    } = snapshot_and_state;
+                    SnapshotAndState {
+                        snapshot: MerkleTreeSnapshot {
+                            root: snapshot.root,
+                            length: snapshot.length,
+                            block_height: snapshot.block_height,
+                            // The snapshots were taken before the format was recorded in them.
+                            format: MerkleTreeFormat::Legacy,
+                            keys_root: None,
+                        },
+                        timestamp_ns,
+                        total_venear,
+                        venear_growth_config,
+                    }
+                }),
+                votes: proposal.votes,
+                total_votes: proposal.total_votes,
+                status: proposal.status,
+            },
             VProposal::Current(current) => current,
         }
     }
 }
 
+/// The proposal before the hashing format was recorded in the Merkle tree snapshots.
+#[derive(Clone)]
+#[near(serializers=[borsh])]
+pub struct ProposalV0 {
+    pub id: ProposalId,
+    pub creation_time_ns: U64,
+    pub proposer_id: AccountId,
+    pub reviewer_id: Option<AccountId>,
+    pub voting_start_time_ns: Option<U64>,
+    pub voting_duration_ns: U64,
+    pub rejected: bool,
+    pub snapshot_and_state: Option<SnapshotAndStateV0>,
+    pub votes: Vec<VoteStats>,
+    pub total_votes: VoteStats,
+    pub status: ProposalStatus,
+}
+
+/// The snapshot and the state of the proposal before the hashing format was recorded in the
+/// Merkle tree snapshots.
+#[derive(Clone)]
+#[near(serializers=[borsh])]
+pub struct SnapshotAndStateV0 {
+    pub snapshot: MerkleTreeSnapshotV0,
+    pub timestamp_ns: TimestampNs,
+    pub total_venear: NearToken,
+    pub venear_growth_config: VenearGrowthConfig,
+}
+
+/// The snapshot of the Merkle tree before the hashing format was recorded in it.
+#[derive(Clone)]
+#[near(serializers=[borsh])]
+pub struct MerkleTreeSnapshotV0 {
+    pub root: Base58CryptoHash,
+    pub length: u32,
+    pub block_height: BlockHeight,
+}
+
 /// The proposal structure that contains all the information about a proposal.
 #[derive(Clone)]
 #[near(serializers=[borsh, json])]
@@ -64,6 +139,16 @@ pub struct ProposalInfo {
     pub metadata: ProposalMetadata,
 }
 
+/// A page of the proposals.
+#[derive(Clone)]
+#[near(serializers=[json])]
+pub struct ProposalInfos {
+    /// The proposals in the page.
+    pub proposals: Vec<ProposalInfo>,
+    /// The index to request the next page from. `None` if it's the last page.
+    pub next_from_index: Option<u32>,
+}
+
 /// The status of the proposal
 #[derive(Clone, Copy, PartialEq)]
 #[near(serializers=[borsh, json])]
@@ -222,15 +307,17 @@ impl Contract {
         self.proposals.len()
     }
 
-    /// Returns a list of proposals from the given index based on the proposal ID order.
-    pub fn get_proposals(&self, from_index: u32, limit: Option<u32>) -> Vec<ProposalInfo> {
-        let from_index = from_index;
-        let limit = limit.unwrap_or(u32::MAX);
-        let to_index = std::cmp::min(from_index.saturating_add(limit), self.get_num_proposals());
-        (from_index..to_index)
-            .into_iter()
-            .filter_map(|i| self.get_proposal(i))
-            .collect()
+    /// Returns a list of proposals from the given index based on the proposal ID order, together
+    /// with the index of the next page.
+    /// The limit is capped by `MAX_PROPOSALS_PAGE_SIZE`.
+    pub fn get_proposals(&self, from_index: u32, limit: Option<u32>) -> ProposalInfos {
+        let num_proposals = self.get_num_proposals();
+        let range = page_range(from_index, limit, MAX_PROPOSALS_PAGE_SIZE, num_proposals);
+        let next_from_index = Some(range.end).filter(|&index| index < num_proposals);
+        ProposalInfos {
+            proposals: range.filter_map(|i| self.get_proposal(i)).collect(),
+            next_from_index,
+        }
     }
 
     /// Returns the number of approved proposals.
@@ -239,18 +326,18 @@ impl Contract {
     }
 
     /// Returns a list of approved proposals from the given index based on the approved proposals
-    /// order.
-    pub fn get_approved_proposals(&self, from_index: u32, limit: Option<u32>) -> Vec<ProposalInfo> {
-        let from_index = from_index;
-        let limit = limit.unwrap_or(u32::MAX);
-        let to_index = std::cmp::min(
-            from_index.saturating_add(limit),
-            self.get_num_approved_proposals(),
-        );
-        (from_index..to_index)
-            .into_iter()
-            .filter_map(|i| self.get_proposal(self.approved_proposals[i]))
-            .collect()
+    /// order, together with the index of the next page.
+    /// The limit is capped by `MAX_PROPOSALS_PAGE_SIZE`.
+    pub fn get_approved_proposals(&self, from_index: u32, limit: Option<u32>) -> ProposalInfos {
+        let num_proposals = self.get_num_approved_proposals();
+        let range = page_range(from_index, limit, MAX_PROPOSALS_PAGE_SIZE, num_proposals);
+        let next_from_index = Some(range.end).filter(|&index| index < num_proposals);
+        ProposalInfos {
+            proposals: range
+                .filter_map(|i| self.get_proposal(self.approved_proposals[i]))
+                .collect(),
+            next_from_index,
+        }
     }
 }
 
@@ -111,7 +111,7 @@ impl Contract {
         proposal.snapshot_and_state = Some(SnapshotAndState {
             snapshot: snapshot_and_state.0,
             timestamp_ns: timestamp.into(),
-            total_venear: global_state.total_venear_balance.total(),
+            total_venear: global_state.total_balance(),
             venear_growth_config: global_state.venear_growth_config,
         });
         proposal.status = ProposalStatus::Approved;
@@ -40,7 +40,12 @@ impl Contract {
         {
             let SnapshotAndState { snapshot, .. } = proposal.snapshot_and_state.as_ref().unwrap();
             require!(
-                merkle_proof.is_valid(snapshot.root.into(), snapshot.length, &v_account),
+                merkle_proof.is_valid(
+                    snapshot.format,
+                    snapshot.root.into(),
+                    snapshot.length,
+                    &v_account
+                ),
                 "Invalid merkle proof"
             );
         }